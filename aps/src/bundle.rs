@@ -0,0 +1,66 @@
+//! `aps bundle`: makes a single executable that runs one program, by
+//! appending the program to a copy of `aps`.
+//!
+//! The copy ends with the program's source, its length as 8 little-endian
+//! bytes, and [`MAGIC`]. At startup, `aps` checks its own file for this
+//! trailer before parsing arguments.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const MAGIC: &[u8; 8] = b"APSBUNDL";
+const TRAILER_LEN: u64 = 16;
+
+/// The length of the program appended to `file`, if any.
+fn payload_len(file: &mut File) -> io::Result<Option<u64>> {
+    let len = file.metadata()?.len();
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+
+    let (payload, magic) = trailer.split_at(8);
+    if magic != MAGIC {
+        return Ok(None);
+    }
+
+    let payload = u64::from_le_bytes(payload.try_into().unwrap_or_default());
+    Ok((payload <= len - TRAILER_LEN).then_some(payload))
+}
+
+/// The program bundled into the running executable, if it is a bundle.
+pub fn embedded() -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(std::env::current_exe()?)?;
+    let Some(len) = payload_len(&mut file)? else {
+        return Ok(None);
+    };
+
+    let mut source = vec![0; len as usize];
+    file.seek(SeekFrom::End(-((len + TRAILER_LEN) as i64)))?;
+    file.read_exact(&mut source)?;
+
+    Ok(Some(source))
+}
+
+/// Writes a copy of the running executable to `out` that runs `source`.
+pub fn create(source: &str, out: &Path) -> io::Result<()> {
+    let mut binary = fs::read(std::env::current_exe()?)?;
+    binary.extend_from_slice(source.as_bytes());
+    binary.extend_from_slice(&(source.len() as u64).to_le_bytes());
+    binary.extend_from_slice(MAGIC);
+    fs::write(out, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(out, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! `aps run --cache`: keeps parsed programs on disk so that running the same
+//! source again skips parsing.
+//!
+//! Entries are named by a hash of the source, its parse options, and the `aps`
+//! binary, since
+//! the stored format changes along with the interpreter. Each entry also holds
+//! its source, so a hash collision only costs a parse. The cache is a speedup
+//! and never an error: entries that cannot be read or written are ignored.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use aps_core::{
+    ast::Stmt,
+    engine::{self, ParseOptions},
+};
+use codespan_reporting::diagnostic::Diagnostic;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    source: String,
+    scope: Box<[Stmt]>,
+}
+
+/// `$XDG_CACHE_HOME/aps`, or `~/.cache/aps` if that is not set.
+pub fn default_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cache")))
+        .map(|dir| dir.join("aps"))
+}
+
+fn key(source: &str, options: ParseOptions) -> String {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    options.hash(&mut hasher);
+
+    if let Some(exe) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| fs::metadata(exe).ok())
+    {
+        exe.len().hash(&mut hasher);
+        exe.modified().ok().hash(&mut hasher);
+    }
+
+    format!("{:016x}.bin", hasher.finish())
+}
+
+/// Parses `source`, or loads the program parsed from it last time. Programs
+/// that fail to parse are not cached.
+pub fn parse(
+    dir: &Path,
+    source: &str,
+    options: ParseOptions,
+) -> Result<Box<[Stmt]>, Vec<Diagnostic<usize>>> {
+    let path = dir.join(key(source, options));
+
+    if let Ok(bytes) = fs::read(&path) {
+        if let Ok(entry) = bincode::deserialize::<Entry>(&bytes) {
+            if entry.source == source {
+                return Ok(entry.scope);
+            }
+        }
+    }
+
+    let scope = engine::parse_with(source, options)?;
+
+    let entry = Entry {
+        source: source.into(),
+        scope,
+    };
+    if let Ok(bytes) = bincode::serialize(&entry) {
+        // Writing to a temporary file first keeps other runs from reading a
+        // partial entry.
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        _ = fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&temp, bytes))
+            .and_then(|()| fs::rename(&temp, &path));
+    }
+
+    Ok(entry.scope)
+}
@@ -0,0 +1,142 @@
+//! `aps diff-run`: runs two programs, or one program with two sets of answers
+//! to INPUT, and shows how what they display differs.
+//!
+//! Both runs use the same seed for RANDOM, so differences come from the
+//! programs or their input rather than chance.
+
+use std::io::{self, IsTerminal, Write};
+
+use aps_core::engine::{run_isolated, IsolatedResult};
+use codespan_reporting::{
+    files::SimpleFiles,
+    term::termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor},
+};
+
+use crate::emit_diagnostics;
+
+/// One program run to compare.
+pub struct Run<'a> {
+    /// The program's path, shown in the diff's header.
+    pub name: &'a str,
+    /// Where `input` came from, shown in the header when both runs are of the
+    /// same program.
+    pub input_name: Option<&'a str>,
+    pub source: &'a str,
+    /// Answers to INPUT. Running out of answers is an exception, so neither
+    /// run waits on the terminal.
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines of `a` and `b`, marked by whether they are in both, using their
+/// longest common subsequence.
+fn diff<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Line<'a>> {
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and
+    // b[j..].
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(Line::Same(a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(Line::Removed(a[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(b[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// What `run` displayed, followed by the exception that stopped it, if any.
+/// Exits if the program cannot be parsed.
+fn output(run: &Run, seed: u64) -> Result<String, Box<dyn std::error::Error>> {
+    let input = run.input.clone();
+    let IsolatedResult {
+        diagnostics,
+        mut output,
+        exception,
+        ..
+    } = run_isolated(run.source, move |engine| {
+        engine.with_seed(seed).with_input(input)
+    });
+
+    if !diagnostics.is_empty() {
+        let mut files = SimpleFiles::new();
+        files.add(run.name, run.source);
+        emit_diagnostics(&files, &diagnostics)?;
+        std::process::exit(2);
+    }
+
+    if let Some(e) = exception {
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push_str(&format!("error: {}\n", e.message));
+    }
+
+    Ok(output)
+}
+
+/// Prints a unified-style diff of what the two runs display. Returns whether
+/// they displayed the same thing.
+pub fn run(a: &Run, b: &Run, seed: u64) -> Result<bool, Box<dyn std::error::Error>> {
+    let (out_a, out_b) = (output(a, seed)?, output(b, seed)?);
+    let lines = diff(
+        &out_a.lines().collect::<Vec<_>>(),
+        &out_b.lines().collect::<Vec<_>>(),
+    );
+
+    let same = lines.iter().all(|line| matches!(line, Line::Same(_)));
+    if same {
+        println!("no differences");
+        return Ok(true);
+    }
+
+    let color = match io::stdout().is_terminal() {
+        true => ColorChoice::Auto,
+        false => ColorChoice::Never,
+    };
+    let mut stdout = StandardStream::stdout(color);
+    let mut print = |color: Option<Color>, text: &str| -> io::Result<()> {
+        stdout.set_color(ColorSpec::new().set_fg(color))?;
+        write!(stdout, "{text}")?;
+        stdout.reset()?;
+        writeln!(stdout)
+    };
+
+    let header = |run: &Run| match run.input_name {
+        Some(input) if a.name == b.name => format!("{} (input from {input})", run.name),
+        _ => run.name.to_string(),
+    };
+    print(Some(Color::Red), &format!("--- {}", header(a)))?;
+    print(Some(Color::Green), &format!("+++ {}", header(b)))?;
+    for line in lines {
+        match line {
+            Line::Same(text) => print(None, &format!(" {text}"))?,
+            Line::Removed(text) => print(Some(Color::Red), &format!("-{text}"))?,
+            Line::Added(text) => print(Some(Color::Green), &format!("+{text}"))?,
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,178 @@
+//! Syntax highlighting for the source excerpts in terminal diagnostics.
+//!
+//! codespan-reporting has no hook for styling source text, so [`Highlight`]
+//! sits between it and the terminal. It holds back each line of output and,
+//! when the line is an excerpt of the source, colors the tokens that are not
+//! already colored as part of a label.
+
+use std::io::{self, Write};
+
+use aps_core::lexer::{Lexer, Token};
+use codespan_reporting::term::termcolor::{Color, ColorSpec, WriteColor};
+
+fn token_color(token: Token) -> Option<Color> {
+    match token {
+        Token::Keyword(_) => Some(Color::Magenta),
+        Token::StringLiteral | Token::InvalidStringLiteral => Some(Color::Green),
+        Token::IntegerLiteral | Token::FloatLiteral | Token::BinaryLiteral | Token::HexLiteral => {
+            Some(Color::Cyan)
+        }
+        _ => None,
+    }
+}
+
+/// The color of each byte of `line`.
+fn classify(line: &str) -> Vec<Option<Color>> {
+    let mut colors = vec![None; line.len()];
+
+    let mut lex = Lexer::new(line.as_bytes());
+    lex.next();
+    while lex.token != Token::EOF {
+        let span = lex.span();
+        colors[span.start as usize..span.end as usize].fill(token_color(lex.token));
+        lex.next();
+    }
+
+    colors
+}
+
+pub struct Highlight<'a, W> {
+    inner: W,
+    source: &'a str,
+    color: Option<ColorSpec>,
+    /// The current line of output, split where its color changes.
+    line: Vec<(Option<ColorSpec>, String)>,
+}
+
+impl<'a, W: WriteColor> Highlight<'a, W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            source: "",
+            color: None,
+            line: Vec::new(),
+        }
+    }
+
+    /// Sets the file that the next diagnostics are about.
+    pub fn set_source(&mut self, source: &'a str) {
+        self.source = source;
+    }
+
+    /// Where the excerpt of the source begins in `line`, if it is one. Source
+    /// lines are printed after a gutter holding their line number.
+    fn excerpt_start(&self, line: &str) -> Option<usize> {
+        let number = line.trim_start().split(' ').next()?.parse::<usize>().ok()?;
+        let text = self
+            .source
+            .lines()
+            .nth(number.checked_sub(1)?)?
+            .trim_end_matches(['\n', '\r', '\0']);
+
+        if text.is_empty() || text.contains('\t') || !line.ends_with(text) {
+            return None;
+        }
+
+        Some(line.len() - text.len())
+    }
+
+    fn write_run(&mut self, color: Option<&ColorSpec>, text: &str) -> io::Result<()> {
+        match color {
+            Some(color) => {
+                self.inner.set_color(color)?;
+                self.inner.write_all(text.as_bytes())?;
+                self.inner.reset()
+            }
+            None => self.inner.write_all(text.as_bytes()),
+        }
+    }
+
+    fn flush_line(&mut self) -> io::Result<()> {
+        let line = std::mem::take(&mut self.line);
+        let plain = line
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect::<String>();
+        let content = plain.trim_end_matches('\n');
+
+        let Some(start) = self.excerpt_start(content) else {
+            for (color, text) in &line {
+                self.write_run(color.as_ref(), text)?;
+            }
+            return Ok(());
+        };
+
+        let tokens = classify(&content[start..]);
+        let mut offset = 0;
+
+        for (color, text) in &line {
+            let end = offset + text.len();
+            if color.is_some() || end <= start {
+                self.write_run(color.as_ref(), text)?;
+                offset = end;
+                continue;
+            }
+
+            // Split the uncolored text into runs of the same token color.
+            let mut run_start = offset;
+            while run_start < end {
+                let token = |idx: usize| idx.checked_sub(start).and_then(|idx| *tokens.get(idx)?);
+                let token_color = token(run_start);
+                let mut run_end = run_start + 1;
+                while run_end < end && token(run_end) == token_color {
+                    run_end += 1;
+                }
+
+                let text = &plain[run_start..run_end];
+                match token_color {
+                    Some(token_color) => {
+                        self.write_run(Some(ColorSpec::new().set_fg(Some(token_color))), text)?
+                    }
+                    None => self.write_run(None, text)?,
+                }
+                run_start = run_end;
+            }
+            offset = end;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: WriteColor> Write for Highlight<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for text in String::from_utf8_lossy(buf).split_inclusive('\n') {
+            match self.line.last_mut() {
+                Some((color, last)) if *color == self.color => last.push_str(text),
+                _ => self.line.push((self.color.clone(), text.into())),
+            }
+
+            if text.ends_with('\n') {
+                self.flush_line()?;
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_line()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for Highlight<'_, W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.color = Some(spec.clone());
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.color = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,767 @@
+//! A language server for editors, started with `aps lsp` and spoken to over
+//! stdio.
+
+use std::{cell::RefCell, collections::HashMap, error::Error, rc::Rc};
+
+use codespan_reporting::diagnostic::{Diagnostic, LabelStyle, Severity};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response, ResponseError};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{
+        CodeActionRequest, DocumentSymbolRequest, GotoDefinition, HoverRequest,
+        PrepareRenameRequest, References, Rename, Request as RequestTrait,
+        SemanticTokensFullRequest,
+    },
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, Diagnostic as LspDiagnostic,
+    DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams,
+    DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, NumberOrString,
+    OneOf, Position, PrepareRenameResponse, PublishDiagnosticsParams, Range, ReferenceParams,
+    RenameOptions, RenameParams, SemanticToken, SemanticTokenModifier, SemanticTokenType,
+    SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+    SemanticTokensParams, SemanticTokensServerCapabilities, ServerCapabilities,
+    SymbolKind as LspSymbolKind, TextDocumentPositionParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use aps_core::{
+    analysis::{Analysis, Symbol, SymbolKind},
+    ast::{Expr, Span, Stmt},
+    edit::Fix,
+    lexer::{Lexer, Token},
+    lint,
+    parser::Parser,
+    stdlib::DEFAULT_MODULES,
+    vm::{Env, Value, VM},
+};
+
+/// The semantic token types we report, indexed by [`SemanticToken::token_type`].
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::COMMENT,
+];
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+fn token_type(ty: SemanticTokenType) -> u32 {
+    TOKEN_TYPES.iter().position(|t| *t == ty).unwrap() as u32
+}
+
+pub fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: Default::default(),
+        })),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: TOKEN_MODIFIERS.to_vec(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    Server {
+        connection,
+        documents: HashMap::new(),
+    }
+    .main_loop()?;
+
+    io_threads.join()?;
+    Ok(())
+}
+
+/// An open file, kept in sync with the editor.
+struct Document {
+    text: String,
+}
+
+impl Document {
+    /// Converts an LSP position, which counts UTF-16 code units, into a byte
+    /// offset.
+    fn offset(&self, position: Position) -> u32 {
+        let mut line_start = 0;
+        for _ in 0..position.line {
+            match self.text[line_start..].find('\n') {
+                Some(idx) => line_start += idx + 1,
+                None => return self.text.len() as u32,
+            }
+        }
+
+        let mut units = 0;
+        for (idx, c) in self.text[line_start..].char_indices() {
+            if units >= position.character || c == '\n' {
+                return (line_start + idx) as u32;
+            }
+            units += c.len_utf16() as u32;
+        }
+
+        self.text.len() as u32
+    }
+
+    fn position(&self, offset: u32) -> Position {
+        let before = &self.text[..offset as usize];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+        Position {
+            line: before.matches('\n').count() as u32,
+            character: before[line_start..].encode_utf16().count() as u32,
+        }
+    }
+
+    fn range(&self, span: Span) -> Range {
+        Range {
+            start: self.position(span.start),
+            end: self.position(span.end),
+        }
+    }
+
+    /// The document's program, or `None` if it does not parse.
+    fn parse(&self) -> Option<Box<[Stmt]>> {
+        let mut parser = Parser::new(0, self.text.as_bytes());
+        parser.tolerant = true;
+
+        let program = parser.parse_program();
+        match parser.has_errors() {
+            true => None,
+            false => program.ok(),
+        }
+    }
+
+    fn analysis(&self) -> Option<Analysis> {
+        let program = self.parse()?;
+        Some(Analysis::new(&self.text, &program))
+    }
+
+    /// The parse errors and lints in the document.
+    fn diagnostics(&self, uri: &Url) -> Vec<LspDiagnostic> {
+        let mut parser = Parser::new(0, self.text.as_bytes());
+        parser.tolerant = true;
+        let program = parser.parse_program();
+        let program = match parser.has_errors() {
+            true => None,
+            false => program.ok(),
+        };
+
+        let lints = lint::lint(&self.text, program.as_deref(), &Default::default());
+        parser
+            .diagnostics
+            .iter()
+            .cloned()
+            .chain(lints.iter().map(|lint| lint.to_diagnostic(0)))
+            .map(|diagnostic| self.to_lsp(&diagnostic, uri))
+            .collect()
+    }
+
+    /// Converts a diagnostic, which points at byte ranges, into one that
+    /// points at LSP ranges. Notes are added to the message, and labels other
+    /// than the primary one become related information.
+    fn to_lsp(&self, diagnostic: &Diagnostic<usize>, uri: &Url) -> LspDiagnostic {
+        let range = |range: &std::ops::Range<usize>| {
+            self.range(Span {
+                start: range.start as u32,
+                end: range.end as u32,
+            })
+        };
+
+        let primary = diagnostic
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .or(diagnostic.labels.first());
+
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push('\n');
+            message.push_str(note);
+        }
+
+        let related = diagnostic
+            .labels
+            .iter()
+            .filter(|label| label.style == LabelStyle::Secondary && !label.message.is_empty())
+            .map(|label| DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), range(&label.range)),
+                message: label.message.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        LspDiagnostic {
+            range: primary.map_or_else(Range::default, |label| range(&label.range)),
+            severity: Some(match diagnostic.severity {
+                Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Note => DiagnosticSeverity::INFORMATION,
+                Severity::Help => DiagnosticSeverity::HINT,
+            }),
+            code: diagnostic.code.clone().map(NumberOrString::String),
+            source: Some("aps".into()),
+            message,
+            related_information: (!related.is_empty()).then_some(related),
+            ..Default::default()
+        }
+    }
+}
+
+/// Whether `expr` is made only of literals, so that it can be evaluated
+/// without running the program.
+fn is_constant(expr: &Expr) -> bool {
+    let mut constant = true;
+    expr.walk(&mut |expr| {
+        constant &= matches!(
+            expr,
+            Expr::True { .. }
+                | Expr::False { .. }
+                | Expr::IntegerLiteral { .. }
+                | Expr::BinaryLiteral { .. }
+                | Expr::HexLiteral { .. }
+                | Expr::FloatLiteral { .. }
+                | Expr::StringLiteral { .. }
+                | Expr::ArrayLiteral { .. }
+                | Expr::MapLiteral { .. }
+                | Expr::UnaryOp { .. }
+                | Expr::BinaryOp { .. }
+                | Expr::Paren { .. }
+        )
+    });
+    constant
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+}
+
+fn params<T: DeserializeOwned>(value: serde_json::Value) -> Result<T, ResponseError> {
+    serde_json::from_value(value).map_err(|e| ResponseError {
+        code: ErrorCode::InvalidParams as i32,
+        message: e.to_string(),
+        data: None,
+    })
+}
+
+fn result<T: Serialize>(value: T) -> Result<serde_json::Value, ResponseError> {
+    Ok(serde_json::to_value(value).unwrap())
+}
+
+impl Server {
+    /// Handles messages until the client shuts the server down. Consumes the
+    /// server so the connection is closed before the IO threads are joined.
+    fn main_loop(mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        break;
+                    }
+
+                    let id = req.id.clone();
+                    let response = match self.handle_request(req) {
+                        Ok(result) => Response {
+                            id,
+                            result: Some(result),
+                            error: None,
+                        },
+                        Err(error) => Response {
+                            id,
+                            result: None,
+                            error: Some(error),
+                        },
+                    };
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(not) => self.handle_notification(not),
+                Message::Response(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_notification(&mut self, not: Notification) {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) = params::<DidOpenTextDocumentParams>(not.params) else {
+                    return;
+                };
+                let uri = params.text_document.uri;
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        text: params.text_document.text,
+                    },
+                );
+                self.publish_diagnostics(uri);
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(params) = params::<DidChangeTextDocumentParams>(not.params) else {
+                    return;
+                };
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    let uri = params.text_document.uri;
+                    self.documents
+                        .insert(uri.clone(), Document { text: change.text });
+                    self.publish_diagnostics(uri);
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) = params::<DidCloseTextDocumentParams>(not.params) else {
+                    return;
+                };
+                self.documents.remove(&params.text_document.uri);
+                // The editor keeps showing diagnostics until they are
+                // replaced, even for closed files.
+                self.publish_diagnostics(params.text_document.uri);
+            }
+            _ => {}
+        }
+    }
+
+    /// Sends the diagnostics for the document at `uri`, or none if it is not
+    /// open.
+    fn publish_diagnostics(&self, uri: Url) {
+        let diagnostics = match self.documents.get(&uri) {
+            Some(document) => document.diagnostics(&uri),
+            None => Vec::new(),
+        };
+
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        _ = self
+            .connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.into(),
+                params,
+            )));
+    }
+
+    fn handle_request(&mut self, req: Request) -> Result<serde_json::Value, ResponseError> {
+        match req.method.as_str() {
+            GotoDefinition::METHOD => result(self.definition(params(req.params)?)),
+            HoverRequest::METHOD => result(self.hover(params(req.params)?)),
+            References::METHOD => result(self.references(params(req.params)?)),
+            PrepareRenameRequest::METHOD => result(self.prepare_rename(params(req.params)?)),
+            Rename::METHOD => result(self.rename(params(req.params)?)?),
+            DocumentSymbolRequest::METHOD => result(self.document_symbols(params(req.params)?)),
+            CodeActionRequest::METHOD => result(self.code_actions(params(req.params)?)),
+            SemanticTokensFullRequest::METHOD => result(self.semantic_tokens(params(req.params)?)),
+            _ => Err(ResponseError {
+                code: ErrorCode::MethodNotFound as i32,
+                message: format!("unsupported request `{}`", req.method),
+                data: None,
+            }),
+        }
+    }
+
+    /// Finds the document and the symbol under the cursor.
+    fn symbol_at(&self, position: &TextDocumentPositionParams) -> Option<(&Document, Symbol)> {
+        let document = self.documents.get(&position.text_document.uri)?;
+        let analysis = document.analysis()?;
+        let symbol = analysis.symbol_at(document.offset(position.position))?;
+        Some((document, symbol.clone()))
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let (document, symbol) = self.symbol_at(&position)?;
+
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            position.text_document.uri,
+            document.range(symbol.definition),
+        )))
+    }
+
+    /// Shows a PROCEDURE's parameters, or the value of a variable that is
+    /// only ever assigned a literal, such as `x <- 10 * 2`.
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let program = document.parse()?;
+        let analysis = Analysis::new(&document.text, &program);
+        let offset = document.offset(position.position);
+        let symbol = analysis.symbol_at(offset)?;
+        let text = |span: Span| &document.text[span.start as usize..span.end as usize];
+
+        let mut params = None;
+        let mut assignments = Vec::new();
+        for stmt in program.iter() {
+            stmt.walk(&mut |stmt| match stmt {
+                Stmt::Procedure(proc) if proc.name == symbol.definition => {
+                    params = Some(proc.params.clone())
+                }
+                Stmt::VarAssign { name, value } if symbol.references.contains(name) => {
+                    assignments.push(Some(value.clone()))
+                }
+                Stmt::Destructure { names, .. }
+                    if names.iter().any(|name| symbol.references.contains(name)) =>
+                {
+                    assignments.push(None)
+                }
+                Stmt::For { alias, index, .. }
+                    if symbol.references.contains(alias)
+                        || index.is_some_and(|index| symbol.references.contains(&index)) =>
+                {
+                    assignments.push(None)
+                }
+                _ => {}
+            });
+        }
+
+        let value = match &assignments[..] {
+            [Some(value)] if symbol.kind == SymbolKind::Variable && is_constant(value) => {
+                let mut vm = VM::new(&document.text);
+                match vm.eval_expr(value, Rc::new(RefCell::new(Env::new()))) {
+                    Value::Exception(_) => None,
+                    value => Some(value),
+                }
+            }
+            _ => None,
+        };
+
+        let signature = match (symbol.kind, params, value) {
+            (SymbolKind::Procedure, Some(params), _) => format!(
+                "PROCEDURE {}({})",
+                symbol.name,
+                params
+                    .iter()
+                    .map(|&param| text(param))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            (SymbolKind::Parameter, ..) => format!("(parameter) {}", symbol.name),
+            (_, _, Some(value)) => format!("{} <- {value:?}", symbol.name),
+            _ => symbol.name.clone(),
+        };
+
+        let span = symbol
+            .references
+            .iter()
+            .find(|span| span.start <= offset && offset <= span.end)?;
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```\n{signature}\n```"),
+            }),
+            range: Some(document.range(*span)),
+        })
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let (document, symbol) = self.symbol_at(&params.text_document_position)?;
+        let uri = &params.text_document_position.text_document.uri;
+
+        Some(
+            symbol
+                .references
+                .iter()
+                .filter(|&&span| params.context.include_declaration || span != symbol.definition)
+                .map(|&span| Location::new(uri.clone(), document.range(span)))
+                .collect(),
+        )
+    }
+
+    fn prepare_rename(&self, params: TextDocumentPositionParams) -> Option<PrepareRenameResponse> {
+        let (document, symbol) = self.symbol_at(&params)?;
+        let offset = document.offset(params.position);
+        let span = symbol
+            .references
+            .iter()
+            .find(|span| span.start <= offset && offset <= span.end)?;
+
+        Some(PrepareRenameResponse::Range(document.range(*span)))
+    }
+
+    fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>, ResponseError> {
+        let mut lex = Lexer::new(params.new_name.as_bytes());
+        lex.next();
+        if lex.token != Token::Identifier || lex.index != params.new_name.len() {
+            return Err(ResponseError {
+                code: ErrorCode::InvalidParams as i32,
+                message: format!("`{}` is not a valid name", params.new_name),
+                data: None,
+            });
+        }
+
+        let Some((document, symbol)) = self.symbol_at(&params.text_document_position) else {
+            return Ok(None);
+        };
+
+        let edits = symbol
+            .references
+            .iter()
+            .map(|&span| TextEdit::new(document.range(span), params.new_name.clone()))
+            .collect();
+
+        Ok(Some(WorkspaceEdit::new(HashMap::from([(
+            params.text_document_position.text_document.uri,
+            edits,
+        )]))))
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let program = document.parse()?;
+        let analysis = Analysis::new(&document.text, &program);
+
+        let symbol = |symbol: &Symbol, range: Span, children| {
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: symbol.name.clone(),
+                detail: None,
+                kind: match symbol.kind {
+                    SymbolKind::Procedure => LspSymbolKind::FUNCTION,
+                    SymbolKind::Variable | SymbolKind::Parameter => LspSymbolKind::VARIABLE,
+                },
+                tags: None,
+                deprecated: None,
+                range: document.range(range),
+                selection_range: document.range(symbol.definition),
+                children,
+            }
+        };
+
+        let symbols = analysis
+            .symbols
+            .iter()
+            .filter(|global| global.scope.is_none())
+            .map(|global| {
+                let proc = program.iter().find_map(|stmt| match stmt {
+                    Stmt::Procedure(proc) if proc.name == global.definition => Some(proc),
+                    _ => None,
+                });
+
+                match proc {
+                    Some(proc) => {
+                        let locals = analysis
+                            .symbols
+                            .iter()
+                            .filter(|local| local.scope == Some(proc.name))
+                            .map(|local| symbol(local, local.definition, None))
+                            .collect();
+                        symbol(global, proc.span, Some(locals))
+                    }
+                    None => symbol(global, global.definition, None),
+                }
+            })
+            .collect();
+
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+
+    fn semantic_tokens(&self, params: SemanticTokensParams) -> Option<SemanticTokens> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let text = &document.text;
+
+        // Without a program we still highlight lexically, just without
+        // knowing which identifiers are procedures or parameters.
+        let analysis = document.analysis();
+        let symbols = analysis
+            .iter()
+            .flat_map(|analysis| &analysis.symbols)
+            .flat_map(|symbol| {
+                symbol
+                    .references
+                    .iter()
+                    .map(|span| (span.start, (symbol.kind, *span == symbol.definition)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut tokens = Vec::new();
+        let mut push = |span: Span, ty: SemanticTokenType, modifiers: u32| {
+            tokens.push((span, token_type(ty), modifiers));
+        };
+
+        let mut lex = Lexer::new(text.as_bytes());
+        let mut prev_end = 0;
+        loop {
+            lex.next();
+
+            // The lexer skips comments, so recover them from the gaps between
+            // tokens.
+            let gap = &text[prev_end..lex.start];
+            let mut offset = 0;
+            while let Some(idx) = gap[offset..].find('#') {
+                let start = offset + idx;
+                let end = gap[start..].find('\n').map_or(gap.len(), |idx| start + idx);
+                push(
+                    Span {
+                        start: (prev_end + start) as u32,
+                        end: (prev_end + end) as u32,
+                    },
+                    SemanticTokenType::COMMENT,
+                    0,
+                );
+                offset = end;
+            }
+            prev_end = lex.index;
+
+            let span = lex.span();
+            match lex.token {
+                Token::EOF => break,
+                Token::Keyword(_) => push(span, SemanticTokenType::KEYWORD, 0),
+                Token::IntegerLiteral
+                | Token::FloatLiteral
+                | Token::BinaryLiteral
+                | Token::HexLiteral => push(span, SemanticTokenType::NUMBER, 0),
+                Token::StringLiteral | Token::InvalidStringLiteral => {
+                    push(span, SemanticTokenType::STRING, 0)
+                }
+                Token::Identifier => match symbols.get(&span.start) {
+                    Some(&(kind, declaration)) => push(
+                        span,
+                        match kind {
+                            SymbolKind::Procedure => SemanticTokenType::FUNCTION,
+                            SymbolKind::Parameter => SemanticTokenType::PARAMETER,
+                            SymbolKind::Variable => SemanticTokenType::VARIABLE,
+                        },
+                        declaration as u32,
+                    ),
+                    None => {
+                        let name = &text[span.start as usize..span.end as usize];
+                        let is_builtin = DEFAULT_MODULES
+                            .iter()
+                            .flat_map(|module| module.builtins)
+                            .any(|(builtin, _)| *builtin == name);
+                        match is_builtin {
+                            true => push(span, SemanticTokenType::FUNCTION, 1 << 1),
+                            false => push(span, SemanticTokenType::VARIABLE, 0),
+                        }
+                    }
+                },
+                Token::ThinArrow
+                | Token::Add
+                | Token::Sub
+                | Token::Mul
+                | Token::Div
+                | Token::Equal
+                | Token::NotEqual
+                | Token::Greater
+                | Token::GreaterEqual
+                | Token::Less
+                | Token::LessEqual => push(span, SemanticTokenType::OPERATOR, 0),
+                _ => {}
+            }
+        }
+
+        let mut data = Vec::with_capacity(tokens.len());
+        let mut prev = Position::new(0, 0);
+        for (span, token_type, token_modifiers_bitset) in tokens {
+            let start = document.position(span.start);
+            let end = document.position(span.end);
+            if start.line != end.line {
+                continue;
+            }
+
+            data.push(SemanticToken {
+                delta_line: start.line - prev.line,
+                delta_start: match start.line == prev.line {
+                    true => start.character - prev.character,
+                    false => start.character,
+                },
+                length: end.character - start.character,
+                token_type,
+                token_modifiers_bitset,
+            });
+            prev = start;
+        }
+
+        Some(SemanticTokens {
+            result_id: None,
+            data,
+        })
+    }
+
+    /// Offers the fixes suggested by the parser and the linter for anything
+    /// overlapping the requested range.
+    fn code_actions(&self, params: CodeActionParams) -> Option<CodeActionResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let start = document.offset(params.range.start);
+        let end = document.offset(params.range.end);
+
+        let mut parser = Parser::new(0, document.text.as_bytes());
+        parser.tolerant = true;
+        let program = parser.parse_program();
+        let program = match parser.has_errors() {
+            true => None,
+            false => program.ok(),
+        };
+
+        let mut fixes = parser
+            .fixes
+            .iter()
+            .filter_map(|(idx, fix)| {
+                let label = parser.diagnostics[*idx].labels.first()?;
+                let span = Span {
+                    start: label.range.start as u32,
+                    end: label.range.end as u32,
+                };
+                Some((span, fix.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        for lint in lint::lint(&document.text, program.as_deref(), &Default::default()) {
+            if let Some(fix) = lint.fix {
+                fixes.push((lint.span, fix));
+            }
+        }
+
+        let mut actions: Vec<CodeActionOrCommand> = Vec::new();
+        let mut seen = Vec::<(Span, String)>::new();
+        for (span, Fix { title, edits }) in fixes {
+            if span.end < start || span.start > end || seen.contains(&(span, title.clone())) {
+                continue;
+            }
+            seen.push((span, title.clone()));
+
+            let edits = edits
+                .into_iter()
+                .map(|edit| TextEdit::new(document.range(edit.span), edit.replacement))
+                .collect();
+
+            actions.push(
+                CodeAction {
+                    title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(
+                        params.text_document.uri.clone(),
+                        edits,
+                    )]))),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        Some(actions)
+    }
+}
@@ -1,7 +1,19 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    thread,
+    time::Instant,
+};
 
 use codespan_reporting::{
-    diagnostic::{Diagnostic, Label},
+    diagnostic::{Diagnostic, Severity},
     files::SimpleFiles,
     term::{
         self,
@@ -10,12 +22,37 @@ use codespan_reporting::{
 };
 
 use aps_core::{
-    parser::Parser,
-    stdlib,
-    vm::{Env, Value, VM},
+    analysis::CallGraph,
+    ast::{Span, Stmt},
+    bytecode::Backend,
+    doc,
+    edit::{self, Fix},
+    encoding,
+    engine::{self, Engine, ParseOptions, RunOutcome, STACK_SIZE},
+    fmt, imports, lint,
+    locale::{self, Lang},
+    metrics::Metrics,
+    parser::{Dialect, Parser},
+    permissions::Permissions,
+    stdlib::{self, robot::Robot},
+    vm::{Env, Exception, Stats, Value, VM},
 };
 
 use clap::{Parser as ClapParser, Subcommand};
+use highlight::Highlight;
+use serde::Serialize;
+use trace_table::TraceTable;
+
+mod bundle;
+mod cache;
+mod diff;
+mod highlight;
+mod lsp;
+mod repl;
+mod runner;
+mod tokens;
+mod trace_table;
+mod tree;
 
 #[derive(Debug, ClapParser)]
 #[command(name = "aps")]
@@ -23,75 +60,1005 @@ use clap::{Parser as ClapParser, Subcommand};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// The language of error messages, such as `es` for Spanish. Defaults to
+    /// `APS_LANG` or else `LANG`.
+    #[arg(long, global = true, value_name = "LANG", value_parser = parse_lang)]
+    lang: Option<Lang>,
 }
 
+fn parse_lang(tag: &str) -> Result<Lang, String> {
+    Lang::from_tag(tag).ok_or_else(|| format!("no translations for {tag:?}; expected en or es"))
+}
+
+/// The language chosen for error messages. Set once at startup.
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// The language named by `APS_LANG` or `LANG`, if it has translations.
+fn env_lang() -> Option<Lang> {
+    ["APS_LANG", "LANG"]
+        .into_iter()
+        .find_map(|var| Lang::from_tag(&std::env::var(var).ok()?))
+}
+
+// Parsed once at startup, so how much larger `Run` is does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Runs a given file.
     #[command(arg_required_else_help = true)]
-    Run { file: String },
+    Run {
+        file: String,
+        /// Answers INPUT calls with the lines of this file instead of stdin,
+        /// e.g. for automated grading.
+        #[arg(long, value_name = "FILE", alias = "stdin-file")]
+        input_file: Option<String>,
+        /// Writes program output to this file instead of stdout.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+        /// Also echoes program output to stdout when using `--output`.
+        #[arg(long, requires = "output")]
+        tee: bool,
+        /// Prints a single JSON object describing the run to stdout.
+        #[arg(long)]
+        json_result: bool,
+        /// Global variable (or parameterless procedure) whose final value is
+        /// reported by `--json-result`.
+        #[arg(long, value_name = "NAME", requires = "json_result")]
+        result: Option<String>,
+        /// Seeds the random number generator so that runs are repeatable.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Saves what the program drew with the turtle builtins as an SVG
+        /// image.
+        #[arg(long, value_name = "FILE")]
+        svg: Option<String>,
+        /// Puts the robot on the grid in this file, drawn with `.` for open
+        /// squares, `#` for blocked ones, and `^`, `>`, `v`, or `<` for the
+        /// robot. Where it ends up is printed to stderr after the run.
+        #[arg(long, value_name = "FILE")]
+        robot_grid: Option<PathBuf>,
+        /// Reuses the parsed program from an earlier run of the same source,
+        /// stored in `~/.cache/aps` by default.
+        #[arg(long)]
+        cache: bool,
+        /// Where `--cache` stores parsed programs.
+        #[arg(long, value_name = "DIR", requires = "cache")]
+        cache_dir: Option<PathBuf>,
+        /// Prints each assignment to this variable to stderr, with its old and
+        /// new values. Can be given more than once.
+        #[arg(long, value_name = "NAME")]
+        watch_var: Vec<String>,
+        /// Prints a table to stderr after the run with a row for each
+        /// assignment to one of these variables, showing all of their values
+        /// after it, like `--trace-table x,y,i`.
+        #[arg(long, value_name = "VARS", value_delimiter = ',')]
+        trace_table: Vec<String>,
+        /// How `--trace-table` lays out the table.
+        #[arg(long, value_enum, default_value = "csv", requires = "trace_table")]
+        trace_format: trace_table::Format,
+        /// Describes each statement in plain English on stderr as it runs.
+        #[arg(long)]
+        explain: bool,
+        /// Warns about arithmetic results too large to be stored exactly.
+        #[arg(long)]
+        check_precision: bool,
+        /// Fails when a list or set would hold more than this many items.
+        /// Defaults to 67108864.
+        #[arg(long, value_name = "N")]
+        max_array_len: Option<usize>,
+        /// Fails when the program has added more than this many items to lists
+        /// and sets in total.
+        #[arg(long, value_name = "N")]
+        max_items: Option<usize>,
+        /// Fails when a builtin would build a string longer than this many
+        /// characters. Defaults to 67108864.
+        #[arg(long, value_name = "N")]
+        max_string_len: Option<usize>,
+        /// Fails when more than this many PROCEDURE calls would be running at
+        /// once, such as in recursion that never stops. Defaults to 1000.
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        /// Fails once the program has run this many statements and
+        /// expressions, such as in a loop that never ends.
+        #[arg(long, value_name = "N")]
+        max_steps: Option<u64>,
+        /// Lets builtins read files in this directory. Can be given more than
+        /// once.
+        #[arg(long, value_name = "DIR")]
+        allow_read: Vec<PathBuf>,
+        /// Lets builtins create and write files in this directory. Can be
+        /// given more than once.
+        #[arg(long, value_name = "DIR")]
+        allow_write: Vec<PathBuf>,
+        /// Accepts the extended dialect, which adds IF expressions and
+        /// PROCEDUREs inside PROCEDUREs.
+        #[arg(long)]
+        extended: bool,
+        /// Allows RETURN outside of PROCEDUREs and prints the program's
+        /// result: the value it RETURNs or the value of its last line.
+        #[arg(long)]
+        script: bool,
+        /// Compiles the program to bytecode before running it, which is
+        /// faster for long loops. `--explain` and `--script` still use the
+        /// tree-walker.
+        #[arg(long)]
+        bytecode: bool,
+    },
+    /// Evaluates a single expression and prints its value.
+    #[command(arg_required_else_help = true)]
+    Eval { expr: String },
+    /// Checks a file for style issues.
+    #[command(arg_required_else_help = true)]
+    Lint {
+        file: String,
+        /// Also enforces stricter style rules, such as uppercase keywords.
+        #[arg(long)]
+        strict: bool,
+        /// Rewrites the file to resolve every mechanically fixable lint.
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Prints a program laid out the standard way: a tab for each level of
+    /// nesting and single spaces around `<-` and operators.
+    #[command(arg_required_else_help = true)]
+    Fmt {
+        file: String,
+        /// Exits with 1 instead of printing if the file is not already
+        /// formatted.
+        #[arg(long, conflicts_with = "write")]
+        check: bool,
+        /// Rewrites the file instead of printing it.
+        #[arg(long)]
+        write: bool,
+        /// Accepts the extended dialect, which adds IF expressions and
+        /// PROCEDUREs inside PROCEDUREs.
+        #[arg(long)]
+        extended: bool,
+        /// Allows RETURN outside of PROCEDUREs.
+        #[arg(long)]
+        script: bool,
+    },
+    /// Prints the syntax tree of a program.
+    #[command(arg_required_else_help = true)]
+    Ast {
+        file: String,
+        /// Prints an indented tree, or JSON for other tools to read.
+        #[arg(long, value_enum, default_value = "tree")]
+        format: tree::Format,
+        /// Accepts the extended dialect, which adds IF expressions and
+        /// PROCEDUREs inside PROCEDUREs.
+        #[arg(long)]
+        extended: bool,
+        /// Allows RETURN outside of PROCEDUREs.
+        #[arg(long)]
+        script: bool,
+    },
+    /// Prints the tokens the lexer reads from a file, with where each one is
+    /// and its text.
+    #[command(arg_required_else_help = true)]
+    Tokens {
+        file: String,
+        /// Prints the tokens as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Prints documentation for a file's PROCEDUREs, taken from the comments
+    /// right above each one.
+    #[command(arg_required_else_help = true)]
+    Doc {
+        file: String,
+        /// Writes an HTML page instead of Markdown.
+        #[arg(long)]
+        html: bool,
+    },
+    /// Prints a graph of a program in Graphviz's DOT language.
+    #[command(arg_required_else_help = true)]
+    Graph {
+        file: String,
+        /// Graphs which PROCEDUREs call which. This is the only kind of graph
+        /// so far.
+        #[arg(long, required = true)]
+        calls: bool,
+    },
+    /// Reports how large and complex a program is: how many statements and
+    /// PROCEDUREs it has, how deeply its blocks nest, and the cyclomatic
+    /// complexity of each PROCEDURE.
+    #[command(arg_required_else_help = true)]
+    Stats {
+        file: String,
+        /// Prints the report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Runs two programs, or one program with two sets of input, and shows
+    /// how what they display differs. Exits with 1 if it differs.
+    #[command(arg_required_else_help = true)]
+    DiffRun {
+        file: String,
+        /// A second program to compare with the first.
+        #[arg(required_unless_present = "other_input")]
+        other: Option<String>,
+        /// Answers INPUT calls in the first run with the lines of this file.
+        #[arg(long, value_name = "FILE")]
+        input: Option<String>,
+        /// Answers INPUT calls in the second run with the lines of this file.
+        /// Defaults to the first run's input.
+        #[arg(long, value_name = "FILE")]
+        other_input: Option<String>,
+        /// Seeds RANDOM the same way in both runs.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Starts a language server that communicates over stdio.
+    Lsp,
+    /// Starts an interactive session.
+    Repl,
+    /// Runs every `.aps` file that has a matching `.out` file and checks that
+    /// it displays the expected output.
+    Test {
+        /// Files or directories to search for tests.
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        /// How many tests to run at once. Defaults to the number of CPUs.
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<usize>,
+    },
+    /// Creates an executable that runs a program without needing `aps`.
+    #[command(arg_required_else_help = true)]
+    Bundle {
+        file: String,
+        /// Where to write the executable. Defaults to the program's name
+        /// without its extension.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Duplicates everything written to it into both writers.
+struct Tee<A, B>(A, B);
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
+    }
+}
+
+#[derive(Serialize)]
+struct RunStats {
+    #[serde(flatten)]
+    vm: Stats,
+    duration_ms: f64,
+}
+
+/// The document printed by `aps run --json-result`.
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    output: &'a str,
+    result: Option<&'a Value>,
+    stats: Option<RunStats>,
+    exception: Option<JsonException<'a>>,
+    /// With file ids that are indices into `files`.
+    diagnostics: Vec<Diagnostic<usize>>,
+    warnings: Vec<JsonWarning<'a>>,
+    /// The program and then each file it imports.
+    files: Vec<&'a str>,
+}
+
+/// A span within one of [`JsonResult::files`], by its index.
+#[derive(Serialize)]
+struct FileSpan {
+    file: usize,
+    start: usize,
+    end: usize,
+}
+
+impl FileSpan {
+    fn new(program: &imports::Program, span: Span) -> Self {
+        let (file, range) = program.relocate_range(span.into());
+        Self {
+            file,
+            start: range.start,
+            end: range.end,
+        }
+    }
+}
+
+/// An [`Exception`] with its spans relocated to the files they are in.
+#[derive(Serialize)]
+struct JsonException<'a> {
+    message: &'a str,
+    span: FileSpan,
+    stack: Vec<JsonFrame<'a>>,
+    help: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonFrame<'a> {
+    name: &'a str,
+    call_span: FileSpan,
+}
+
+impl<'a> JsonException<'a> {
+    fn new(program: &imports::Program, e: &'a Exception) -> Self {
+        Self {
+            message: &e.message,
+            span: FileSpan::new(program, e.span),
+            stack: e
+                .stack
+                .iter()
+                .map(|frame| JsonFrame {
+                    name: &frame.name,
+                    call_span: FileSpan::new(program, frame.call_span),
+                })
+                .collect(),
+            help: e.help.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonWarning<'a> {
+    message: &'a str,
+    span: FileSpan,
+}
+
+fn emit_diagnostics(
+    files: &SimpleFiles<&str, &str>,
+    diagnostics: &[Diagnostic<usize>],
+) -> Result<(), codespan_reporting::files::Error> {
+    let writer = StandardStream::stderr(ColorChoice::Always);
+    let config = codespan_reporting::term::Config::default();
+    let mut writer = Highlight::new(writer.lock());
+
+    let mut diagnostics = diagnostics.to_vec();
+    locale::localize(LANG.get().copied().unwrap_or_default(), &mut diagnostics);
+
+    for diagnostic in &diagnostics {
+        if let Some(label) = diagnostic.labels.first() {
+            writer.set_source(files.get(label.file_id)?.source());
+        }
+        term::emit(&mut writer, &config, files, diagnostic)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a program, dropping any byte order mark. If the file is not valid
+/// UTF-8, the invalid bytes are reported and the process exits.
+fn read_program(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let decoded = encoding::decode(&std::fs::read(path)?);
+
+    if !decoded.invalid.is_empty() {
+        let mut files = SimpleFiles::new();
+        let fid = files.add(path, decoded.text.as_str());
+        emit_diagnostics(&files, &decoded.diagnostics(fid))?;
+        std::process::exit(1);
+    }
+
+    Ok(decoded.text)
+}
+
+/// The file that `span` of `program`'s source is in, along with the 1-based
+/// line and column where it starts.
+fn program_location(program: &imports::Program, span: Span) -> (&str, usize, usize) {
+    let (_, file, offset) = program.locate(span.start as usize);
+    let (line, column) = location(&program.source[file.range.clone()], offset);
+    (&file.name, line, column)
+}
+
+/// The 1-based line and column of the byte `offset` in `source`.
+fn location(source: &str, offset: usize) -> (usize, usize) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+    let line = source[..offset].matches('\n').count() + 1;
+    let column = source[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+fn emit_exception(
+    files: &SimpleFiles<&str, &str>,
+    fid: usize,
+    e: &Exception,
+) -> Result<(), codespan_reporting::files::Error> {
+    emit_diagnostics(files, &e.to_diagnostics(fid))
+}
+
+/// Parses and lints `source`, returning the parser's diagnostics along with
+/// the lints. The parser is tolerant, so mistakes like `x = 5` come back as
+/// warnings, and the fixes for those warnings are returned too.
+fn lint_source(
+    fid: usize,
+    source: &str,
+    options: &lint::Options,
+) -> (Vec<Diagnostic<usize>>, Vec<lint::Lint>, Vec<Fix>) {
+    let mut parser = Parser::new(fid, source.as_bytes());
+    parser.tolerant = true;
+
+    let program = parser.parse_program();
+    let program = match parser.has_errors() {
+        true => None,
+        false => program.ok(),
+    };
+
+    let fixes = parser
+        .fixes
+        .into_iter()
+        .filter(|(idx, _)| parser.diagnostics[*idx].severity == Severity::Warning)
+        .map(|(_, fix)| fix)
+        .collect();
+
+    let lints = lint::lint(source, program.as_deref(), options);
+    (parser.diagnostics, lints, fixes)
+}
+
+/// Parses `input`, read from `file`, or reports why it cannot be parsed and
+/// exits.
+fn parse_or_exit(
+    file: &str,
+    input: &str,
+    options: ParseOptions,
+) -> Result<Box<[Stmt]>, Box<dyn std::error::Error>> {
+    match engine::parse_with(input, options) {
+        Ok(scope) => Ok(scope),
+        Err(diagnostics) => {
+            let mut files = SimpleFiles::new();
+            files.add(file, input);
+            emit_diagnostics(&files, &diagnostics)?;
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the program bundled into this executable, passing INPUT and output
+/// through the terminal as `aps run` would.
+fn run_bundled(source: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let decoded = encoding::decode(source);
+
+    let mut files = SimpleFiles::new();
+    let fid = files.add("<bundle>", decoded.text.as_str());
+
+    let outcome = Engine::new().run(&decoded.text);
+    let errors = outcome.errors(fid);
+    emit_diagnostics(&files, &errors)?;
+
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The main thread's stack is too small for deep recursion in debug
+    // builds, so everything runs on a thread with a larger one.
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| cli().map_err(|e| e.to_string()))?;
+    match cli.join() {
+        Ok(res) => Ok(res?),
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+fn cli() -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(source) = bundle::embedded()? {
+        _ = LANG.set(env_lang().unwrap_or_default());
+        return run_bundled(&source);
+    }
+
     let args = Cli::parse();
+    _ = LANG.set(args.lang.or_else(env_lang).unwrap_or_default());
 
     match args.command {
-        Commands::Run { file } => {
-            let input = std::fs::read_to_string(&file)?;
+        Commands::Run {
+            file,
+            input_file,
+            output,
+            tee,
+            json_result,
+            result,
+            seed,
+            svg,
+            robot_grid,
+            cache,
+            cache_dir,
+            watch_var,
+            trace_table,
+            trace_format,
+            explain,
+            check_precision,
+            max_array_len,
+            max_items,
+            max_string_len,
+            max_depth,
+            max_steps,
+            allow_read,
+            allow_write,
+            extended,
+            script,
+            bytecode,
+        } => {
+            let input = read_program(&file)?;
+
+            let mut engine = Engine::new();
+
+            if extended {
+                engine = engine.with_dialect(Dialect::Extended);
+            }
+
+            if script {
+                engine = engine.script_mode();
+            }
+
+            let parsed = match cache_dir.or_else(cache::default_dir) {
+                Some(dir) if cache => cache::parse(&dir, &input, engine.parse_options()),
+                _ => engine::parse_with(&input, engine.parse_options()),
+            };
+            let permissions = Permissions {
+                read: allow_read,
+                write: allow_write,
+            };
+            let program = Rc::new(imports::load(
+                Path::new(&file),
+                &input,
+                parsed,
+                engine.parse_options(),
+                &permissions,
+            ));
 
             let mut files = SimpleFiles::new();
-            let fid = files.add(&file, &input);
+            for file in &program.files {
+                files.add(file.name.as_str(), &program.source[file.range.clone()]);
+            }
+
+            if let Some(input_file) = input_file {
+                let answers = std::fs::read_to_string(input_file)?;
+                engine = engine.with_input(answers.lines());
+            }
+
+            // `--json-result` owns stdout, so output it would have shown is
+            // captured into the JSON instead.
+            if json_result && (output.is_none() || tee) {
+                engine = engine.capture_output();
+            }
+
+            if let Some(output) = output {
+                let file = File::create(output)?;
+                engine = if tee && !json_result {
+                    engine.with_output(Tee(file, io::stdout()))
+                } else {
+                    engine.with_output(file)
+                };
+            }
+
+            if let Some(name) = &result {
+                engine = engine.with_result(name);
+            }
+
+            if let Some(seed) = seed {
+                engine = engine.with_seed(seed);
+            }
+
+            if let Some(path) = &robot_grid {
+                let layout = std::fs::read_to_string(path)?;
+                let robot = Robot::parse(&layout)
+                    .map_err(|message| format!("{}: {message}", path.display()))?;
+                engine = engine.with_robot(robot);
+            }
+
+            if !watch_var.is_empty() {
+                let program = program.clone();
+                engine = engine.on_assign(move |assignment| {
+                    if !watch_var.iter().any(|name| name == assignment.name) {
+                        return;
+                    }
+
+                    let (file, line, column) = program_location(&program, assignment.span);
+                    let old = match assignment.old {
+                        Some(old) => format!("{old:?}"),
+                        None => "undefined".into(),
+                    };
+                    eprintln!(
+                        "{file}:{line}:{column}: {}: {old} \u{2192} {:?}",
+                        assignment.name, assignment.new
+                    );
+                });
+            }
+
+            let trace_table = (!trace_table.is_empty())
+                .then(|| Rc::new(RefCell::new(TraceTable::new(trace_table))));
+            if let Some(table) = &trace_table {
+                let (table, program) = (table.clone(), program.clone());
+                engine = engine.on_assign(move |assignment| {
+                    let (_, line, _) = program_location(&program, assignment.span);
+                    table.borrow_mut().record(line, assignment);
+                });
+            }
+
+            if explain {
+                let program = program.clone();
+                engine = engine.on_explain(move |explanation| {
+                    let (file, line, column) = program_location(&program, explanation.span);
+                    eprintln!("{file}:{line}:{column}: {}", explanation.text);
+                });
+            }
+
+            if check_precision {
+                engine = engine.check_precision();
+            }
+
+            if let Some(max) = max_array_len {
+                engine = engine.with_max_array_len(max);
+            }
+
+            if let Some(max) = max_items {
+                engine = engine.with_max_items(max);
+            }
 
-            let mut parser = Parser::new(fid, input.as_bytes());
-            parser.lex.next();
+            if let Some(max) = max_string_len {
+                engine = engine.with_max_string_len(max);
+            }
+            if let Some(max) = max_depth {
+                engine = engine.with_max_depth(max);
+            }
+            if let Some(max) = max_steps {
+                engine = engine.with_max_steps(max);
+            }
 
-            let value = parser.parse_scope(true);
+            engine = engine.with_permissions(permissions);
 
-            if parser.diagnostics.len() != 0 {
-                let writer = StandardStream::stderr(ColorChoice::Always);
-                let config = codespan_reporting::term::Config::default();
-                let mut writer = writer.lock();
+            if bytecode {
+                engine = engine.with_backend(Backend::Bytecode);
+            }
 
-                for diagnostic in parser.diagnostics.iter() {
-                    term::emit(&mut writer, &config, &files, diagnostic)?;
+            // Ctrl-C stops the program with an exception that shows where it
+            // was. A second Ctrl-C exits right away, since a program waiting
+            // for INPUT does not reach the next statement.
+            let interrupt = Arc::new(AtomicBool::new(false));
+            let flag = interrupt.clone();
+            ctrlc::set_handler(move || {
+                if flag.swap(true, Ordering::Relaxed) {
+                    std::process::exit(130);
                 }
+            })?;
+            engine = engine.with_interrupt(interrupt.clone());
+
+            let started = Instant::now();
+            let outcome = match program.diagnostics.is_empty() {
+                true => engine.run_parsed(&program.source, &program.scope),
+                false => RunOutcome::failed(program.diagnostics.clone()),
+            };
+            let duration = started.elapsed();
+
+            let mut warnings = outcome
+                .warnings
+                .iter()
+                .map(|warning| warning.to_diagnostic(0))
+                .collect::<Vec<_>>();
+            program.relocate(&mut warnings);
+            emit_diagnostics(&files, &warnings)?;
+            let mut errors = outcome.errors(0);
+            program.relocate(&mut errors);
+            emit_diagnostics(&files, &errors)?;
+
+            if let (Some(svg), true) = (svg, outcome.diagnostics.is_empty()) {
+                std::fs::write(svg, outcome.turtle.to_svg())?;
+            }
+
+            if let (Some(robot), true) = (&outcome.robot, robot_grid.is_some()) {
+                eprint!("{robot}");
+            }
+
+            if let (Some(table), true) = (trace_table, outcome.diagnostics.is_empty()) {
+                eprint!("{}", table.borrow().render(trace_format));
+            }
+
+            if json_result {
+                let ran = outcome.diagnostics.is_empty();
+                let mut diagnostics = outcome.diagnostics.clone();
+                program.relocate(&mut diagnostics);
+                println!(
+                    "{}",
+                    serde_json::to_string(&JsonResult {
+                        output: &outcome.captured_output,
+                        result: (ran
+                            && (result.is_some() || script)
+                            && outcome.exception.is_none())
+                        .then_some(&outcome.value),
+                        stats: ran.then_some(RunStats {
+                            vm: outcome.stats,
+                            duration_ms: duration.as_secs_f64() * 1000.,
+                        }),
+                        exception: outcome
+                            .exception
+                            .as_ref()
+                            .map(|e| JsonException::new(&program, e)),
+                        diagnostics,
+                        warnings: outcome
+                            .warnings
+                            .iter()
+                            .map(|warning| JsonWarning {
+                                message: &warning.message,
+                                span: FileSpan::new(&program, warning.span),
+                            })
+                            .collect(),
+                        files: program
+                            .files
+                            .iter()
+                            .map(|file| file.name.as_str())
+                            .collect(),
+                    })?
+                );
+            }
+
+            if interrupt.load(Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+
+            if !outcome.diagnostics.is_empty() || outcome.exception.is_some() {
                 std::process::exit(1);
             }
 
-            let mut vm = VM::new(&input);
+            if script && !json_result && !matches!(outcome.value, Value::Void) {
+                println!("{}", outcome.value);
+            }
+        }
+        Commands::Eval { expr } => {
+            let mut files = SimpleFiles::new();
+            let fid = files.add("<eval>", expr.as_str());
 
+            let mut vm = VM::new(&expr);
             let mut env = Env::new();
             stdlib::inject(&mut env);
-            let value = vm.eval_scope(&value.unwrap(), Rc::new(RefCell::new(env)));
-
-            if let Value::Exception(e) = &value {
-                let writer = StandardStream::stderr(ColorChoice::Always);
-                let config = codespan_reporting::term::Config::default();
-                let mut writer = writer.lock();
-
-                term::emit(
-                    &mut writer,
-                    &config,
-                    &files,
-                    &Diagnostic::error()
-                        .with_message(&e.message)
-                        .with_labels(vec![Label::primary(fid, e.span)]),
-                )?;
-
-                for itm in e.stack.iter() {
-                    term::emit(
-                        &mut writer,
-                        &config,
-                        &files,
-                        &Diagnostic::note()
-                            .with_message("called here")
-                            .with_labels(vec![Label::primary(fid, *itm)]),
-                    )?;
+
+            match vm.eval_str(&expr, Rc::new(RefCell::new(env))) {
+                Ok(Value::Void) => {}
+                Ok(value) => println!("{value}"),
+                Err(e) => {
+                    emit_exception(&files, fid, &e)?;
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Lint { file, strict, fix } => {
+            let mut input = read_program(&file)?;
+            let options = lint::Options { strict };
+
+            if fix {
+                let original = input.clone();
+
+                // Fixes can make a file parse and so expose more fixable lints,
+                // e.g. `if` becoming `IF`, so repeat until nothing changes.
+                for _ in 0..10 {
+                    let (_, lints, fixes) = lint_source(0, &input, &options);
+                    let edits = lints
+                        .into_iter()
+                        .flat_map(|lint| lint.fix)
+                        .chain(fixes)
+                        .flat_map(|fix| fix.edits)
+                        .collect::<Vec<_>>();
+                    if edits.is_empty() {
+                        break;
+                    }
+                    input = edit::apply(&input, &edits);
                 }
 
+                if input != original {
+                    std::fs::write(&file, &input)?;
+                }
+            }
+
+            let mut files = SimpleFiles::new();
+            let fid = files.add(file.as_str(), input.as_str());
+
+            let (mut diagnostics, lints, _) = lint_source(fid, &input, &options);
+            diagnostics.extend(lints.iter().map(|lint| lint.to_diagnostic(fid)));
+            emit_diagnostics(&files, &diagnostics)?;
+
+            if !diagnostics.is_empty() {
                 std::process::exit(1);
             }
         }
+        Commands::Fmt {
+            file,
+            check,
+            write,
+            extended,
+            script,
+        } => {
+            let input = read_program(&file)?;
+            let options = ParseOptions {
+                dialect: match extended {
+                    true => Dialect::Extended,
+                    false => Dialect::Ap,
+                },
+                script,
+            };
+            let scope = parse_or_exit(&file, &input, options)?;
+            let formatted = fmt::format(&input, &scope);
+
+            if check {
+                if formatted != input {
+                    eprintln!("{file} is not formatted; run `aps fmt --write {file}`");
+                    std::process::exit(1);
+                }
+            } else if write {
+                if formatted != input {
+                    std::fs::write(&file, &formatted)?;
+                }
+            } else {
+                print!("{formatted}");
+            }
+        }
+        Commands::Ast {
+            file,
+            format,
+            extended,
+            script,
+        } => {
+            let input = read_program(&file)?;
+            let options = ParseOptions {
+                dialect: match extended {
+                    true => Dialect::Extended,
+                    false => Dialect::Ap,
+                },
+                script,
+            };
+            let scope = parse_or_exit(&file, &input, options)?;
+
+            match format {
+                tree::Format::Tree => {
+                    print!("{}", tree::render(&input, &serde_json::to_value(&scope)?))
+                }
+                tree::Format::Json => println!("{}", serde_json::to_string_pretty(&scope)?),
+            }
+        }
+        Commands::Tokens { file, json } => {
+            let input = read_program(&file)?;
+            let tokens = tokens::tokens(&input);
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&tokens)?);
+            } else {
+                print!("{}", tokens::render(&tokens));
+            }
+        }
+        Commands::Doc { file, html } => {
+            let input = read_program(&file)?;
+
+            let scope = parse_or_exit(&file, &input, ParseOptions::default())?;
+
+            let title = Path::new(&file)
+                .file_name()
+                .map_or(file.clone(), |name| name.to_string_lossy().into_owned());
+            let docs = doc::extract(&input, &scope);
+            if html {
+                print!("{}", doc::to_html(&title, &docs));
+            } else {
+                print!("{}", doc::to_markdown(&title, &docs));
+            }
+        }
+        Commands::Graph { file, calls: _ } => {
+            let input = read_program(&file)?;
+
+            let scope = parse_or_exit(&file, &input, ParseOptions::default())?;
+
+            print!("{}", CallGraph::new(&input, &scope).to_dot());
+        }
+        Commands::Stats { file, json } => {
+            let input = read_program(&file)?;
+            let scope = parse_or_exit(&file, &input, ParseOptions::default())?;
+            let metrics = Metrics::new(&input, &scope);
+
+            if json {
+                println!("{}", serde_json::to_string(&metrics)?);
+                return Ok(());
+            }
+
+            println!("statements: {}", metrics.statements);
+            println!("procedures: {}", metrics.procedures.len());
+            println!("max nesting depth: {}", metrics.max_depth);
+
+            if !metrics.procedures.is_empty() {
+                let width = metrics
+                    .procedures
+                    .iter()
+                    .map(|procedure| procedure.name.len())
+                    .chain(["procedure".len()])
+                    .max()
+                    .unwrap_or_default();
+
+                println!("\n{:width$}  statements  depth  complexity", "procedure");
+                for procedure in &metrics.procedures {
+                    println!(
+                        "{:width$}  {:>10}  {:>5}  {:>10}",
+                        procedure.name,
+                        procedure.statements,
+                        procedure.max_depth,
+                        procedure.complexity
+                    );
+                }
+            }
+        }
+        Commands::DiffRun {
+            file,
+            other,
+            input,
+            other_input,
+            seed,
+        } => {
+            let read_input = |path: Option<String>| -> io::Result<Vec<String>> {
+                Ok(match path {
+                    Some(path) => std::fs::read_to_string(path)?
+                        .lines()
+                        .map(String::from)
+                        .collect(),
+                    None => Vec::new(),
+                })
+            };
+
+            let source = read_program(&file)?;
+            let other_file = other.unwrap_or_else(|| file.clone());
+            let other_source = read_program(&other_file)?;
+            let other_input = other_input.or_else(|| input.clone());
+
+            let first = diff::Run {
+                name: &file,
+                source: &source,
+                input_name: input.as_deref(),
+                input: read_input(input.clone())?,
+            };
+            let second = diff::Run {
+                name: &other_file,
+                source: &other_source,
+                input_name: other_input.as_deref(),
+                input: read_input(other_input.clone())?,
+            };
+
+            if !diff::run(&first, &second, seed)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Lsp => lsp::run().map_err(|e| e as Box<dyn std::error::Error>)?,
+        Commands::Repl => repl::run()?,
+        Commands::Test { paths, jobs } => {
+            let jobs = jobs
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |jobs| jobs.get()));
+
+            if !runner::run(&paths, jobs)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Bundle { file, output } => {
+            let input = read_program(&file)?;
+
+            // A program that cannot parse would fail every time it runs.
+            if let Err(diagnostics) = engine::parse(&input) {
+                let mut files = SimpleFiles::new();
+                files.add(file.as_str(), input.as_str());
+                emit_diagnostics(&files, &diagnostics)?;
+                std::process::exit(1);
+            }
+
+            let output = output.unwrap_or_else(|| {
+                let stem = Path::new(&file).file_stem().unwrap_or_default();
+                PathBuf::from(stem).with_extension(std::env::consts::EXE_EXTENSION)
+            });
+            if output == Path::new(&file) {
+                eprintln!("refusing to overwrite {file}; choose another path with --output");
+                std::process::exit(1);
+            }
+
+            bundle::create(&input, &output)?;
+            println!("wrote {}", output.display());
+        }
     }
 
     Ok(())
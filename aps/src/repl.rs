@@ -0,0 +1,197 @@
+//! `aps repl`: an interactive session that keeps its globals between inputs.
+//!
+//! Besides program text, the REPL accepts these commands:
+//!
+//! - `:save FILE` writes every input that ran without errors to `FILE`.
+//! - `:load FILE` runs `FILE` in the session.
+//! - `:vars` lists the variables and PROCEDUREs defined so far.
+//! - `:quit` exits.
+//!
+//! Input is read with line editing, Ctrl-R history search, and tab completion
+//! of keywords and names. History is kept in `~/.aps_history`.
+
+use aps_core::{encoding, engine::Session, lexer::KEYWORDS, parser::Parser, vm::Value};
+use codespan_reporting::files::SimpleFiles;
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor,
+};
+
+use crate::emit_diagnostics;
+
+/// Whether `source` only failed to parse because it ended too early, such as
+/// a PROCEDURE whose body has not been typed yet.
+fn incomplete(source: &str) -> bool {
+    let mut parser = Parser::new((), source.as_bytes());
+    _ = parser.parse_program();
+
+    parser.diagnostics.iter().any(|diagnostic| {
+        diagnostic
+            .labels
+            .iter()
+            .any(|label| label.range.start >= source.trim_end().len())
+    })
+}
+
+fn eval(session: &mut Session, source: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let outcome = session.eval(source);
+
+    let mut files = SimpleFiles::new();
+    let fid = files.add("<repl>", session.source());
+    emit_diagnostics(&files, &outcome.errors(fid))?;
+
+    if !matches!(outcome.value, Value::Void) {
+        println!("{}", outcome.value);
+    }
+
+    Ok(())
+}
+
+fn command(session: &mut Session, line: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+    let arg = arg.trim();
+
+    match name {
+        ":quit" | ":q" => return Ok(false),
+        ":vars" => {
+            for (name, value) in session.globals() {
+                println!("{name} = {value:?}");
+            }
+        }
+        ":save" | ":load" if arg.is_empty() => eprintln!("usage: {name} FILE"),
+        ":save" => match std::fs::write(arg, session.save()) {
+            Ok(()) => println!("saved session to {arg}"),
+            Err(e) => eprintln!("could not write {arg}: {e}"),
+        },
+        ":load" => match std::fs::read(arg) {
+            Ok(bytes) => {
+                let decoded = encoding::decode(&bytes);
+                if decoded.invalid.is_empty() {
+                    eval(session, &decoded.text)?;
+                } else {
+                    let mut files = SimpleFiles::new();
+                    let fid = files.add(arg, decoded.text.as_str());
+                    emit_diagnostics(&files, &decoded.diagnostics(fid))?;
+                }
+            }
+            Err(e) => eprintln!("could not read {arg}: {e}"),
+        },
+        _ => eprintln!("unknown command {name}; expected :save, :load, :vars, or :quit"),
+    }
+
+    Ok(true)
+}
+
+/// Completes keywords and the names of globals, including builtins.
+#[derive(Default)]
+struct Helper {
+    names: Vec<String>,
+}
+
+impl Completer for Helper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(pos, |(idx, _)| idx);
+        let word = &line[start..pos];
+
+        if word.is_empty() {
+            return Ok((pos, Vec::new()));
+        }
+
+        let mut candidates = KEYWORDS
+            .keys()
+            .map(|keyword| keyword.to_string())
+            .chain(self.names.iter().cloned())
+            .filter(|name| name.starts_with(word))
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Helper {
+    type Hint = String;
+}
+
+impl Highlighter for Helper {}
+
+impl Validator for Helper {}
+
+impl rustyline::Helper for Helper {}
+
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut session = Session::new();
+    let mut input = String::new();
+
+    let mut editor = Editor::<Helper, DefaultHistory>::new()?;
+    editor.set_helper(Some(Helper {
+        names: session.names(),
+    }));
+
+    let history = std::env::home_dir().map(|home| home.join(".aps_history"));
+    if let Some(history) = &history {
+        // There is no history yet the first time the REPL runs.
+        _ = editor.load_history(history);
+    }
+
+    loop {
+        let prompt = if input.is_empty() { "> " } else { "... " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C discards the current input, like in a shell.
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        if !line.trim().is_empty() {
+            editor.add_history_entry(&line)?;
+        }
+
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            if !command(&mut session, line.trim())? {
+                break;
+            }
+        } else {
+            input.push_str(&line);
+            input.push('\n');
+
+            // Keep reading until the input parses so that blocks can span
+            // several lines. A blank line runs the input as it is.
+            if !line.trim().is_empty() && incomplete(&input) {
+                continue;
+            }
+
+            if !input.trim().is_empty() {
+                eval(&mut session, input.trim_end())?;
+            }
+            input.clear();
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = session.names();
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+
+    Ok(())
+}
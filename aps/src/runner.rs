@@ -0,0 +1,154 @@
+//! `aps test`: runs every program that has an expected-output file next to it
+//! and compares what it displays.
+//!
+//! A test is a `name.aps` file with a `name.out` file holding its expected
+//! output. If a `name.in` file exists, its lines answer the program's `INPUT`
+//! calls.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use aps_core::{encoding, engine::run_isolated};
+
+enum Outcome {
+    Passed,
+    Failed(String),
+}
+
+fn discover(path: &Path, tests: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            discover(&entry?.path(), tests)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "aps")
+        && path.with_extension("out").is_file()
+    {
+        tests.push(path.to_path_buf());
+    }
+
+    Ok(())
+}
+
+/// Normalizes line endings and trailing whitespace so that expected-output
+/// files can be written by hand.
+fn normalize(output: &str) -> String {
+    output
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_string()
+}
+
+fn run_test(path: &Path) -> io::Result<Outcome> {
+    let source = encoding::decode(&fs::read(path)?);
+    if let Some((offset, _)) = source.invalid.first() {
+        return Ok(Outcome::Failed(format!(
+            "error: file is not valid UTF-8 (invalid byte at offset {offset})"
+        )));
+    }
+    let source = source.text;
+    let expected = fs::read_to_string(path.with_extension("out"))?;
+    let input = match fs::read_to_string(path.with_extension("in")) {
+        Ok(input) => Some(input.lines().map(String::from).collect::<Vec<_>>()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let result = run_isolated(&source, move |engine| match input {
+        Some(input) => engine.with_input(input),
+        None => engine,
+    });
+
+    if !result.diagnostics.is_empty() {
+        let messages = result
+            .diagnostics
+            .iter()
+            .map(|diagnostic| format!("error: {}", diagnostic.message))
+            .collect::<Vec<_>>();
+        return Ok(Outcome::Failed(messages.join("\n")));
+    }
+
+    if let Some(e) = result.exception {
+        return Ok(Outcome::Failed(format!(
+            "exception: {}\n\noutput before the exception:\n{}",
+            e.message, result.output
+        )));
+    }
+
+    let (expected, actual) = (normalize(&expected), normalize(&result.output));
+    if expected == actual {
+        Ok(Outcome::Passed)
+    } else {
+        Ok(Outcome::Failed(format!(
+            "expected output:\n{expected}\n\nactual output:\n{actual}"
+        )))
+    }
+}
+
+/// Runs the tests found under `paths` on `jobs` worker threads and reports the
+/// results in path order. Returns whether every test passed.
+pub fn run(paths: &[PathBuf], jobs: usize) -> io::Result<bool> {
+    let mut tests = Vec::new();
+    for path in paths {
+        discover(path, &mut tests)?;
+    }
+    tests.sort();
+
+    let next = AtomicUsize::new(0);
+    let mut outcomes = thread::scope(|scope| {
+        let workers = (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outcomes = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(test) = tests.get(idx) else {
+                            break outcomes;
+                        };
+                        outcomes.push((idx, run_test(test)));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    outcomes.sort_by_key(|(idx, _)| *idx);
+
+    let mut failures = Vec::new();
+    for (idx, outcome) in outcomes {
+        let test = tests[idx].display();
+        match outcome? {
+            Outcome::Passed => println!("test {test} ... ok"),
+            Outcome::Failed(reason) => {
+                println!("test {test} ... FAILED");
+                failures.push((test, reason));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (test, reason) in &failures {
+            println!("\n---- {test} ----\n{reason}");
+        }
+    }
+
+    let passed = tests.len() - failures.len();
+    println!(
+        "\ntest result: {}. {passed} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        failures.len()
+    );
+
+    Ok(failures.is_empty())
+}
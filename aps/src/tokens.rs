@@ -0,0 +1,54 @@
+use std::fmt::Write;
+
+use aps_core::{
+    ast::Span,
+    lexer::{Lexer, Token},
+};
+use serde::Serialize;
+
+/// A token as printed by `aps tokens`.
+#[derive(Serialize)]
+pub struct TokenInfo<'a> {
+    /// The token's variant, like `Identifier` or `Keyword(If)`.
+    pub kind: String,
+    pub span: Span,
+    pub text: &'a str,
+    /// Whether a line break comes between this token and the one before.
+    pub newline_before: bool,
+}
+
+/// Runs the lexer over all of `source`, leaving out the final end of file.
+pub fn tokens(source: &str) -> Vec<TokenInfo<'_>> {
+    let mut lex = Lexer::new(source.as_bytes());
+    let mut tokens = Vec::new();
+
+    loop {
+        lex.next();
+        if lex.token == Token::EOF {
+            break;
+        }
+
+        tokens.push(TokenInfo {
+            kind: format!("{:?}", lex.token),
+            span: lex.span(),
+            text: source.get(lex.start..lex.index).unwrap_or_default(),
+            newline_before: lex.has_newline_before,
+        });
+    }
+
+    tokens
+}
+
+/// Lays out tokens one per line, like `Identifier@0..1 "x"`, with a blank
+/// line wherever the source starts a new line.
+pub fn render(tokens: &[TokenInfo]) -> String {
+    let mut out = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.newline_before && i > 0 {
+            out.push('\n');
+        }
+        let Span { start, end } = token.span;
+        _ = writeln!(out, "{}@{start}..{end} {:?}", token.kind, token.text);
+    }
+    out
+}
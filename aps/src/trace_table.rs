@@ -0,0 +1,99 @@
+//! `aps run --trace-table`: the values of chosen variables after each
+//! assignment to one of them, laid out like the trace tables in AP exam
+//! questions.
+
+use aps_core::vm::Assignment;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Csv,
+    Markdown,
+}
+
+pub struct TraceTable {
+    vars: Vec<String>,
+    /// The latest value of each of `vars`, or `None` before it is assigned.
+    current: Vec<Option<String>>,
+    /// The line of each assignment and the values of `vars` right after it.
+    rows: Vec<(usize, Vec<Option<String>>)>,
+}
+
+impl TraceTable {
+    pub fn new(vars: Vec<String>) -> Self {
+        Self {
+            current: vec![None; vars.len()],
+            vars,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row if `assignment` is to one of the variables. Variables are
+    /// matched by name, so those of the same name in different PROCEDUREs
+    /// share a column.
+    pub fn record(&mut self, line: usize, assignment: &Assignment) {
+        let Some(idx) = self.vars.iter().position(|var| var == assignment.name) else {
+            return;
+        };
+
+        self.current[idx] = Some(format!("{:?}", assignment.new));
+        self.rows.push((line, self.current.clone()));
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Csv => self.to_csv(),
+            Format::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::from("line");
+        for var in &self.vars {
+            out.push(',');
+            out.push_str(&csv_field(var));
+        }
+        out.push('\n');
+
+        for (line, values) in &self.rows {
+            out.push_str(&line.to_string());
+            for value in values {
+                out.push(',');
+                out.push_str(&csv_field(value.as_deref().unwrap_or_default()));
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::from("| line |");
+        for var in &self.vars {
+            out.push_str(&format!(" {var} |"));
+        }
+        out.push_str("\n| ---: |");
+        out.push_str(&" --- |".repeat(self.vars.len()));
+        out.push('\n');
+
+        for (line, values) in &self.rows {
+            out.push_str(&format!("| {line} |"));
+            for value in values {
+                let value = value.as_deref().unwrap_or_default().replace('|', "\\|");
+                out.push_str(&format!(" {value} |"));
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+/// Quotes `text` if it has a character that CSV gives a meaning to.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.into()
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Tree,
+    Json,
+}
+
+/// Source text longer than this is left out of the tree.
+const MAX_TEXT_LEN: usize = 40;
+
+/// Lays out a syntax tree, serialized as JSON, as indented lines like
+/// `Identifier@32..33 "a"`, with the byte range of each node and the source
+/// text of the ones without children. Empty and missing fields are left out.
+pub fn render(source: &str, tree: &Value) -> String {
+    let mut out = String::new();
+    match tree {
+        Value::Array(stmts) => stmts
+            .iter()
+            .for_each(|stmt| node(&mut out, source, 0, None, stmt)),
+        tree => node(&mut out, source, 0, None, tree),
+    }
+    out
+}
+
+fn node(out: &mut String, source: &str, depth: usize, label: Option<&str>, value: &Value) {
+    let indent = "  ".repeat(depth);
+    let prefix = label.map_or(String::new(), |label| format!("{label}: "));
+
+    match value {
+        Value::Null => {}
+        Value::Array(items) if items.is_empty() => {}
+        Value::Array(items) => {
+            _ = writeln!(
+                out,
+                "{indent}{}",
+                label.map_or("-".into(), |l| format!("{l}:"))
+            );
+            for item in items {
+                node(out, source, depth + 1, None, item);
+            }
+        }
+        Value::Object(fields) => {
+            if let Some((start, end)) = span(value) {
+                _ = writeln!(
+                    out,
+                    "{indent}{prefix}@{start}..{end}{}",
+                    text(source, start, end)
+                );
+                return;
+            }
+
+            // Enum variants are objects with a single key, their name.
+            let (name, fields) = match variant(fields) {
+                Some((name, Value::Object(inner)))
+                    if span(&fields[name]).is_none() && variant(inner).is_none() =>
+                {
+                    (name.as_str(), inner)
+                }
+                Some((name, inner)) => {
+                    _ = writeln!(out, "{indent}{prefix}{name}");
+                    node(out, source, depth + 1, None, inner);
+                    return;
+                }
+                None => {
+                    _ = writeln!(
+                        out,
+                        "{indent}{}",
+                        label.map_or("-".into(), |l| format!("{l}:"))
+                    );
+                    for (key, value) in fields {
+                        node(out, source, depth + 1, Some(key), value);
+                    }
+                    return;
+                }
+            };
+
+            let mut header = format!("{indent}{prefix}{name}");
+            if let Some((start, end)) = fields.get("span").and_then(span) {
+                _ = write!(header, "@{start}..{end}");
+                let leaf = fields.values().all(|value| match value {
+                    Value::Array(_) => false,
+                    Value::Object(_) => span(value).is_some(),
+                    _ => true,
+                });
+                if leaf {
+                    header.push_str(&text(source, start, end));
+                }
+            }
+            _ = writeln!(out, "{header}");
+
+            for (key, value) in fields.iter().filter(|(key, _)| *key != "span") {
+                node(out, source, depth + 1, Some(key), value);
+            }
+        }
+        Value::Number(n) => {
+            _ = writeln!(out, "{indent}{prefix}{}", n.as_f64().unwrap_or_default());
+        }
+        Value::String(s) => _ = writeln!(out, "{indent}{prefix}{s}"),
+        Value::Bool(b) => _ = writeln!(out, "{indent}{prefix}{b}"),
+    }
+}
+
+/// The name and fields of an enum variant.
+fn variant(fields: &Map<String, Value>) -> Option<(&String, &Value)> {
+    let (name, value) = fields.iter().next().filter(|_| fields.len() == 1)?;
+    name.starts_with(char::is_uppercase)
+        .then_some((name, value))
+}
+
+/// The byte range of a serialized `Span`.
+fn span(value: &Value) -> Option<(u64, u64)> {
+    let fields = value.as_object().filter(|fields| fields.len() == 2)?;
+    Some((fields.get("start")?.as_u64()?, fields.get("end")?.as_u64()?))
+}
+
+/// The source text at `start..end`, quoted and preceded by a space, if it is
+/// short and on one line.
+fn text(source: &str, start: u64, end: u64) -> String {
+    match source.get(start as usize..end as usize) {
+        Some(text) if text.len() <= MAX_TEXT_LEN && !text.contains('\n') => format!(" {text:?}"),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,69 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+use serde_json::{json, Value};
+
+const SOURCE: &str = "x <- \"hi\"\nDISPLAY(x)\n";
+
+/// Writes `SOURCE` to a file of its own for the test `name`.
+fn program(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("aps-tokens-{}-{name}.aps", std::process::id()));
+    fs::write(&path, SOURCE).unwrap();
+    path
+}
+
+fn tokens(args: &[&str], name: &str) -> String {
+    let path = program(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_aps"))
+        .arg("tokens")
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_each_token_with_its_span_and_text() {
+    assert_eq!(
+        tokens(&[], "text"),
+        r#"Identifier@0..1 "x"
+ThinArrow@2..4 "<-"
+StringLiteral@5..9 "\"hi\""
+
+Identifier@10..17 "DISPLAY"
+LeftParen@17..18 "("
+Identifier@18..19 "x"
+RightParen@19..20 ")"
+"#
+    );
+}
+
+#[test]
+fn prints_json_that_parses() {
+    let tokens: Value = serde_json::from_str(&tokens(&["--json"], "json")).unwrap();
+    let tokens = tokens.as_array().unwrap();
+    assert_eq!(tokens.len(), 7);
+
+    for token in tokens {
+        let span = &token["span"];
+        let (start, end) = (
+            span["start"].as_u64().unwrap(),
+            span["end"].as_u64().unwrap(),
+        );
+        assert_eq!(token["text"], SOURCE[start as usize..end as usize]);
+    }
+    assert_eq!(
+        tokens[1],
+        json!({
+            "kind": "ThinArrow",
+            "span": { "start": 2, "end": 4 },
+            "text": "<-",
+            "newline_before": false,
+        })
+    );
+    assert_eq!(tokens[3]["kind"], "Identifier");
+    assert_eq!(tokens[3]["newline_before"], true);
+}
@@ -0,0 +1,299 @@
+//! Static name resolution for editor tooling, and the call graph for
+//! `aps graph`.
+//!
+//! Mirrors how the VM binds names: PROCEDUREs and anything assigned at the top
+//! level are globals, a PROCEDURE's parameters are local to it, and any other
+//! name assigned inside a PROCEDURE is a local unless a global of that name
+//! exists.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Expr, Procedure, Span, Stmt, MAX_NESTING};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Procedure,
+    Parameter,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The PROCEDURE the symbol is local to, or `None` for globals.
+    pub scope: Option<Span>,
+    /// The first place the symbol is bound.
+    pub definition: Span,
+    /// Every occurrence of the symbol, including its definition, in source
+    /// order.
+    pub references: Vec<Span>,
+}
+
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub symbols: Vec<Symbol>,
+}
+
+impl Analysis {
+    pub fn new(source: &str, program: &[Stmt]) -> Self {
+        let mut resolver = Resolver {
+            source,
+            symbols: Vec::new(),
+            globals: HashMap::new(),
+            locals: HashMap::new(),
+            scope: None,
+            depth: 0,
+        };
+
+        for stmt in program {
+            resolver.bind_globals(stmt);
+        }
+        for stmt in program {
+            resolver.stmt(stmt);
+        }
+
+        let mut symbols = resolver.symbols;
+        for symbol in &mut symbols {
+            symbol.references.sort_by_key(|span| span.start);
+            symbol.references.dedup();
+        }
+
+        Self { symbols }
+    }
+
+    /// The symbol with an occurrence covering `offset`.
+    pub fn symbol_at(&self, offset: u32) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| {
+            symbol
+                .references
+                .iter()
+                .any(|span| span.start <= offset && offset <= span.end)
+        })
+    }
+}
+
+struct Resolver<'a> {
+    source: &'a str,
+    symbols: Vec<Symbol>,
+    globals: HashMap<&'a str, usize>,
+    locals: HashMap<&'a str, usize>,
+    scope: Option<Span>,
+    /// How many blocks enclose the statement being resolved. Blocks deeper
+    /// than [`MAX_NESTING`] never come from the parser and are skipped.
+    depth: u32,
+}
+
+impl<'a> Resolver<'a> {
+    /// Calls `f` on each statement of `scope`, one block deeper.
+    fn nested<'s>(&mut self, scope: &'s [Stmt], f: fn(&mut Self, &'s Stmt)) {
+        if self.depth == MAX_NESTING {
+            return;
+        }
+        self.depth += 1;
+        scope.iter().for_each(|stmt| f(self, stmt));
+        self.depth -= 1;
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        &self.source[span.start as usize..span.end as usize]
+    }
+
+    fn define(&mut self, span: Span, kind: SymbolKind) -> usize {
+        self.symbols.push(Symbol {
+            name: self.text(span).into(),
+            kind,
+            scope: self.scope,
+            definition: span,
+            references: Vec::new(),
+        });
+        self.symbols.len() - 1
+    }
+
+    fn bind_globals(&mut self, stmt: &Stmt) {
+        let bindings = match stmt {
+            Stmt::Procedure(proc) => vec![(proc.name, SymbolKind::Procedure)],
+            Stmt::VarAssign { name, .. } => vec![(*name, SymbolKind::Variable)],
+            Stmt::Destructure { names, .. } => names
+                .iter()
+                .map(|&name| (name, SymbolKind::Variable))
+                .collect(),
+            Stmt::For { alias, index, .. } => [*alias]
+                .into_iter()
+                .chain(*index)
+                .map(|name| (name, SymbolKind::Variable))
+                .collect(),
+            _ => vec![],
+        };
+
+        for (span, kind) in bindings {
+            let name = self.text(span);
+            if !self.globals.contains_key(name) {
+                let id = self.define(span, kind);
+                self.globals.insert(name, id);
+            }
+        }
+
+        // Names assigned inside a PROCEDURE are not globals.
+        if !matches!(stmt, Stmt::Procedure(_)) {
+            for scope in stmt.scopes() {
+                self.nested(scope, Self::bind_globals);
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.locals
+            .get(name)
+            .or_else(|| self.globals.get(name))
+            .copied()
+    }
+
+    fn reference(&mut self, span: Span) {
+        if let Some(id) = self.lookup(self.text(span)) {
+            self.symbols[id].references.push(span);
+        }
+    }
+
+    /// Resolves a binding, declaring a new local if it is inside a PROCEDURE
+    /// and does not refer to anything already.
+    fn bind(&mut self, span: Span) {
+        let name = self.text(span);
+        if self.scope.is_some() && self.lookup(name).is_none() {
+            let id = self.define(span, SymbolKind::Variable);
+            self.locals.insert(name, id);
+        }
+        self.reference(span);
+    }
+
+    fn procedure(&mut self, proc: &Procedure) {
+        self.reference(proc.name);
+
+        self.scope = Some(proc.name);
+        for &param in proc.params.iter() {
+            let id = self.define(param, SymbolKind::Parameter);
+            self.symbols[id].references.push(param);
+            self.locals.insert(self.text(param), id);
+        }
+
+        self.nested(&proc.scope, Self::stmt);
+
+        self.locals.clear();
+        self.scope = None;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        for expr in stmt.exprs() {
+            expr.walk(&mut |expr| {
+                if let &Expr::Identifier { span } = expr {
+                    self.reference(span);
+                }
+            });
+        }
+
+        match stmt {
+            Stmt::Procedure(proc) => return self.procedure(proc),
+            Stmt::VarAssign { name, .. } => self.bind(*name),
+            Stmt::Destructure { names, .. } => names.iter().for_each(|&name| self.bind(name)),
+            Stmt::For { alias, index, .. } => {
+                self.bind(*alias);
+                if let Some(index) = index {
+                    self.bind(*index);
+                }
+            }
+            _ => {}
+        }
+
+        for scope in stmt.scopes() {
+            self.nested(scope, Self::stmt);
+        }
+    }
+}
+
+/// Which PROCEDUREs call which.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    /// The PROCEDUREs defined at the top level, in order.
+    pub procedures: Vec<String>,
+    /// Each `(caller, callee)` pair, as indexes into `procedures`. A `None`
+    /// caller is the top level of the program.
+    pub calls: Vec<(Option<usize>, usize)>,
+}
+
+impl CallGraph {
+    pub fn new(source: &str, program: &[Stmt]) -> Self {
+        let text = |span: Span| &source[span.start as usize..span.end as usize];
+
+        let procedures = program
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Procedure(proc) => Some(text(proc.name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut calls = Vec::new();
+        let mut find_calls = |caller: Option<usize>, stmt: &Stmt| {
+            stmt.walk(&mut |stmt| {
+                for expr in stmt.exprs() {
+                    expr.walk(&mut |expr| {
+                        let Expr::FnCall { calle, .. } = expr else {
+                            return;
+                        };
+                        let &Expr::Identifier { span } = &**calle else {
+                            return;
+                        };
+                        if let Some(callee) = procedures.iter().position(|&name| name == text(span))
+                        {
+                            calls.push((caller, callee));
+                        }
+                    });
+                }
+            });
+        };
+
+        let mut idx = 0;
+        for stmt in program {
+            match stmt {
+                Stmt::Procedure(proc) => {
+                    proc.scope
+                        .iter()
+                        .for_each(|stmt| find_calls(Some(idx), stmt));
+                    idx += 1;
+                }
+                stmt => find_calls(None, stmt),
+            }
+        }
+
+        let mut seen = HashSet::new();
+        calls.retain(|&call| seen.insert(call));
+
+        Self {
+            procedures: procedures.into_iter().map(String::from).collect(),
+            calls,
+        }
+    }
+
+    /// Renders the graph in Graphviz's DOT language. The top level of the
+    /// program is drawn as a box.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph calls {\n");
+
+        if self.calls.iter().any(|(caller, _)| caller.is_none()) {
+            out.push_str("    \"(program)\" [shape=box];\n");
+        }
+        for name in &self.procedures {
+            out.push_str(&format!("    \"{name}\";\n"));
+        }
+        for &(caller, callee) in &self.calls {
+            let caller = caller.map_or("(program)", |caller| &self.procedures[caller]);
+            out.push_str(&format!(
+                "    \"{caller}\" -> \"{}\";\n",
+                self.procedures[callee]
+            ));
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
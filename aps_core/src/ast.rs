@@ -1,18 +1,22 @@
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
 use crate::lexer::{Keyword, Token};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: u32,
     pub end: u32,
 }
 
-impl Into<std::ops::Range<usize>> for Span {
-    fn into(self) -> std::ops::Range<usize> {
-        self.start as usize..self.end as usize
+impl From<Span> for core::ops::Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start as usize..span.end as usize
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Void,
     True {
@@ -28,6 +32,11 @@ pub enum Expr {
         span: Span,
         values: Box<[Expr]>,
     },
+    /// `{key: value, ...}`, with the keys and values in the order written.
+    MapLiteral {
+        span: Span,
+        entries: Box<[(Expr, Expr)]>,
+    },
     Index {
         span: Span,
         value: Box<Expr>,
@@ -35,18 +44,26 @@ pub enum Expr {
     },
     StringLiteral {
         span: Span,
+        /// The text between the quotes, with escape sequences replaced.
+        value: String,
     },
     IntegerLiteral {
         span: Span,
+        value: f64,
     },
     FloatLiteral {
         span: Span,
+        value: f64,
     },
+    /// `0b` followed by binary digits.
     BinaryLiteral {
         span: Span,
+        value: f64,
     },
+    /// `0x` followed by hex digits.
     HexLiteral {
         span: Span,
+        value: f64,
     },
     FnCall {
         span: Span,
@@ -67,8 +84,22 @@ pub enum Expr {
         span: Span,
         value: Box<Expr>,
     },
+    /// An IF used as a value in the extended dialect. Each block ends with
+    /// the expression that gives its value.
+    If {
+        span: Span,
+        cond: Box<Expr>,
+        scope: Box<[Stmt]>,
+        else_ifs: Box<[ElseIf]>,
+        els: Box<[Stmt]>,
+    },
 }
 
+/// How deeply expressions and blocks can be nested in a program. The parser
+/// rejects anything deeper, so code that walks the tree recursively never
+/// runs out of stack.
+pub const MAX_NESTING: u32 = 200;
+
 pub trait Node {
     fn span(&self) -> Span;
 }
@@ -76,7 +107,8 @@ pub trait Node {
 impl Node for Expr {
     fn span(&self) -> Span {
         match self {
-            Self::Void => unreachable!(),
+            // Only stands in for a missing `RETURN` value, which has no text.
+            Self::Void => Span { start: 0, end: 0 },
             &Self::True { start } => Span {
                 start,
                 end: start + 4,
@@ -87,15 +119,17 @@ impl Node for Expr {
             },
             &Self::Identifier { span }
             | &Self::ArrayLiteral { span, .. }
+            | &Self::MapLiteral { span, .. }
             | &Self::Index { span, .. }
             | &Self::FnCall { span, .. }
             | &Self::UnaryOp { span, .. }
-            | &Self::IntegerLiteral { span }
-            | &Self::FloatLiteral { span }
-            | &Self::BinaryLiteral { span }
-            | &Self::StringLiteral { span }
-            | &Self::HexLiteral { span }
-            | &Self::Paren { span, .. } => span,
+            | &Self::IntegerLiteral { span, .. }
+            | &Self::FloatLiteral { span, .. }
+            | &Self::BinaryLiteral { span, .. }
+            | &Self::StringLiteral { span, .. }
+            | &Self::HexLiteral { span, .. }
+            | &Self::Paren { span, .. }
+            | &Self::If { span, .. } => span,
             Self::BinaryOp { lhs, rhs, .. } => Span {
                 start: lhs.span().start,
                 end: rhs.span().end,
@@ -104,7 +138,54 @@ impl Node for Expr {
     }
 }
 
-#[derive(Debug, Clone)]
+impl Expr {
+    /// Calls `f` on this expression and then on every expression nested in it,
+    /// up to [`MAX_NESTING`] levels deep.
+    pub fn walk(&self, f: &mut impl FnMut(&Expr)) {
+        self.walk_within(MAX_NESTING, f);
+    }
+
+    fn walk_within(&self, depth: u32, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        // Deeper expressions never come from the parser.
+        let Some(depth) = depth.checked_sub(1) else {
+            return;
+        };
+        match self {
+            Self::ArrayLiteral { values, .. } => {
+                values.iter().for_each(|value| value.walk_within(depth, f))
+            }
+            Self::MapLiteral { entries, .. } => entries.iter().for_each(|(key, value)| {
+                key.walk_within(depth, f);
+                value.walk_within(depth, f);
+            }),
+            Self::Index { value, index, .. } => {
+                value.walk_within(depth, f);
+                index.walk_within(depth, f);
+            }
+            Self::FnCall { calle, args, .. } => {
+                calle.walk_within(depth, f);
+                args.iter().for_each(|arg| arg.walk_within(depth, f));
+            }
+            Self::UnaryOp { value, .. } | Self::Paren { value, .. } => value.walk_within(depth, f),
+            Self::BinaryOp { lhs, rhs, .. } => {
+                lhs.walk_within(depth, f);
+                rhs.walk_within(depth, f);
+            }
+            // Like `Stmt::exprs`, this leaves out the statements in blocks.
+            Self::If { cond, else_ifs, .. } => {
+                cond.walk_within(depth, f);
+                else_ifs
+                    .iter()
+                    .for_each(|else_if| else_if.cond.walk_within(depth, f));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOpKind {
     Add,
     Sub,
@@ -121,9 +202,12 @@ pub enum BinaryOpKind {
     Or,
 }
 
-impl From<Token> for BinaryOpKind {
-    fn from(value: Token) -> Self {
-        match value {
+/// Fails with the token if it is not a binary operator.
+impl TryFrom<Token> for BinaryOpKind {
+    type Error = Token;
+
+    fn try_from(value: Token) -> Result<Self, Token> {
+        Ok(match value {
             Token::Add => Self::Add,
             Token::Sub => Self::Sub,
             Token::Mul => Self::Mul,
@@ -137,12 +221,13 @@ impl From<Token> for BinaryOpKind {
             Token::GreaterEqual => Self::GreaterEqual,
             Token::Keyword(Keyword::And) => Self::And,
             Token::Keyword(Keyword::Or) => Self::Or,
-            tok => panic!("unsupported: {tok:?}"),
-        }
+            tok => return Err(tok),
+        })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOpKind {
     Pos,
     Neg,
@@ -150,10 +235,14 @@ pub enum UnaryOpKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     Return {
         start: u32,
         value: Expr,
+        /// Whether nothing in the PROCEDURE can run after this RETURN, so a
+        /// PROCEDURE it calls can reuse the caller's frame.
+        tail: bool,
     },
     Expr(Expr),
     VarAssign {
@@ -165,6 +254,14 @@ pub enum Stmt {
         index: Box<Expr>,
         value: Expr,
     },
+    /// `[a, b] <- list`, which assigns the items of `list` to the names in
+    /// order.
+    Destructure {
+        /// The brackets and the names between them.
+        span: Span,
+        names: Box<[Span]>,
+        value: Expr,
+    },
     If {
         cond: Box<Expr>,
         scope: Box<[Stmt]>,
@@ -182,18 +279,42 @@ pub enum Stmt {
     },
     For {
         alias: Span,
+        /// The `i` in `FOR EACH item, i IN list`, which holds the position of
+        /// `item`.
+        index: Option<Span>,
         array: Box<Expr>,
         scope: Box<[Stmt]>,
     },
+    /// `IMPORT "file.aps"`, which runs another file and defines its
+    /// PROCEDUREs.
+    Import {
+        span: Span,
+        /// The file's path, relative to the importing file.
+        path: String,
+        /// The file's statements, once they have been loaded by
+        /// [`crate::imports`].
+        scope: Option<Box<[Stmt]>>,
+    },
+    /// A statement from a registered [`crate::parser::StatementForm`].
+    Custom {
+        span: Span,
+        handler: String,
+        args: Box<[Expr]>,
+    },
 }
 
-#[derive(Debug, gc::Finalize, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "std", derive(gc::Finalize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Procedure {
+    /// From `PROCEDURE` through the closing brace.
+    pub span: Span,
     pub name: Span,
     pub params: Box<[Span]>,
     pub scope: Box<[Stmt]>,
 }
 
+#[cfg(feature = "std")]
 unsafe impl gc::Trace for Procedure {
     unsafe fn trace(&self) {}
     unsafe fn root(&self) {}
@@ -201,10 +322,76 @@ unsafe impl gc::Trace for Procedure {
     fn finalize_glue(&self) {}
 }
 
+impl Stmt {
+    /// The expressions written directly in this statement, excluding those in
+    /// nested blocks.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Self::Return {
+                value: Expr::Void, ..
+            } => vec![],
+            Self::Return { value, .. }
+            | Self::Expr(value)
+            | Self::VarAssign { value, .. }
+            | Self::Destructure { value, .. } => {
+                vec![value]
+            }
+            Self::IndexAssign { root, index, value } => vec![root, index, value],
+            Self::If { cond, else_ifs, .. } => [cond.as_ref()]
+                .into_iter()
+                .chain(else_ifs.iter().map(|else_if| &else_if.cond))
+                .collect(),
+            Self::Procedure(_) | Self::Import { .. } => vec![],
+            Self::RepeatN { n: value, .. }
+            | Self::RepeatUntil { cond: value, .. }
+            | Self::For { array: value, .. } => vec![value],
+            Self::Custom { args, .. } => args.iter().collect(),
+        }
+    }
+
+    /// The blocks nested directly in this statement.
+    pub fn scopes(&self) -> Vec<&[Stmt]> {
+        match self {
+            Self::If {
+                scope,
+                else_ifs,
+                els,
+                ..
+            } => [scope.as_ref()]
+                .into_iter()
+                .chain(else_ifs.iter().map(|else_if| else_if.scope.as_ref()))
+                .chain(els.as_deref())
+                .collect(),
+            Self::Procedure(proc) => vec![&proc.scope],
+            Self::RepeatN { scope, .. }
+            | Self::RepeatUntil { scope, .. }
+            | Self::For { scope, .. } => vec![scope],
+            _ => vec![],
+        }
+    }
+
+    /// Calls `f` on this statement and then on every statement nested in it,
+    /// up to [`MAX_NESTING`] blocks deep.
+    pub fn walk(&self, f: &mut impl FnMut(&Stmt)) {
+        self.walk_within(MAX_NESTING, f);
+    }
+
+    fn walk_within(&self, depth: u32, f: &mut impl FnMut(&Stmt)) {
+        f(self);
+        // Deeper blocks never come from the parser.
+        let Some(depth) = depth.checked_sub(1) else {
+            return;
+        };
+        for scope in self.scopes() {
+            scope.iter().for_each(|stmt| stmt.walk_within(depth, f));
+        }
+    }
+}
+
 impl Node for Stmt {
     fn span(&self) -> Span {
         match self {
-            Self::Return { start, value } => Span {
+            Self::Return { start, value, .. } => Span {
                 start: *start,
                 end: if let Expr::Void = value {
                     start + 6
@@ -224,12 +411,27 @@ impl Node for Stmt {
                 start: root.span().start,
                 end: value.span().end,
             },
-            _ => panic!(),
+            &Self::Custom { span, .. } | &Self::Import { span, .. } => span,
+            Self::Destructure { span, value, .. } => Span {
+                start: span.start,
+                end: value.span().end,
+            },
+            Self::Procedure(proc) => proc.span,
+            Self::Expr(expr) => expr.span(),
+            // Blocks do not record where their keyword is, so they are
+            // located by the expression in their header.
+            Self::If { cond, .. } | Self::RepeatUntil { cond, .. } => cond.span(),
+            Self::RepeatN { n, .. } => n.span(),
+            Self::For { alias, array, .. } => Span {
+                start: alias.start,
+                end: array.span().end,
+            },
         }
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElseIf {
     pub cond: Expr,
     pub scope: Box<[Stmt]>,
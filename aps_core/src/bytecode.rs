@@ -0,0 +1,761 @@
+//! A backend that compiles statements to a flat list of instructions for a
+//! stack machine, so that loops run without walking the tree on every
+//! iteration.
+//!
+//! Every exception is raised by the same [`VM`] helpers as in the tree-walker,
+//! at the same spans, so a program fails the same way with either backend.
+//! PROCEDUREs are compiled on their first call, and calls still nest on the
+//! Rust stack as they do in the tree-walker.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    ast::{BinaryOpKind, Expr, Node, Procedure, Span, Stmt, UnaryOpKind},
+    fail, tee,
+    vm::{
+        check_arity, check_repeat_count, unary_op, Env, ForEach, TailCall, Value, STEPS_HELP, VM,
+    },
+};
+
+/// How a [`VM`] runs programs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Evaluates the syntax tree directly.
+    #[default]
+    Tree,
+    /// Compiles the syntax tree to bytecode first. Runs loops faster, but
+    /// [`VM::on_explain`] is only supported by the tree-walker, which is used
+    /// instead while it is set.
+    Bytecode,
+}
+
+/// A position in a [`Chunk`]'s instructions, looked up in its `labels`.
+#[derive(Debug, Clone, Copy)]
+struct Label(usize);
+
+#[derive(Debug)]
+enum Op {
+    /// Counts a statement, failing at the span if the program was
+    /// interrupted.
+    Statement(Span),
+    /// Counts the expression that ends the block of an IF used as a value.
+    Count,
+    /// Fails at the span if the program was interrupted, for loops whose
+    /// block may have no statements to check.
+    Interrupt(Span),
+    /// Fails at the span if there are no steps left, before the expression
+    /// there is evaluated. Only emitted when compiling with `steps`.
+    Step(Span),
+    Const(Value),
+    /// Pushes the variable named at the span.
+    Load(Span),
+    /// Fails unless the top of the stack can be indexed.
+    CheckIndexable(Span),
+    Index {
+        value: Span,
+        index: Span,
+        span: Span,
+    },
+    Unary(UnaryOpKind, Span),
+    Binary {
+        kind: BinaryOpKind,
+        lhs: Span,
+        rhs: Span,
+    },
+    /// Pops the left side of an AND, or of an OR if `or`, and jumps to `end`
+    /// with the result if it decides it.
+    ShortCircuit {
+        or: bool,
+        lhs: Span,
+        end: Label,
+    },
+    /// Fails unless the top of the stack is a boolean.
+    CheckBool(Span),
+    /// Pops `len` items into a new list.
+    Array {
+        len: usize,
+        span: Span,
+    },
+    /// Pops a key and value for each of the `keys` into a new map.
+    Map {
+        keys: Box<[Span]>,
+        span: Span,
+    },
+    /// Fails unless the top of the stack can be called.
+    CheckCallable(Span),
+    /// Pops the arguments, whose spans are `args`, and the callee below them,
+    /// and pushes what the call returns.
+    Call {
+        span: Span,
+        args: Box<[Span]>,
+    },
+    /// Pushes the value of the handler of a custom statement.
+    LoadHandler {
+        name: String,
+        span: Span,
+    },
+    Pop,
+    Jump(Label),
+    /// Pops a condition and jumps if it is `FALSE`, or `TRUE` if `when`.
+    JumpIf {
+        when: bool,
+        cond: Span,
+        to: Label,
+    },
+    Assign {
+        name: Span,
+        span: Span,
+    },
+    Destructure {
+        names: Box<[Span]>,
+        value: Span,
+        span: Span,
+    },
+    Define(Rc<Procedure>),
+    /// Runs an imported file and defines its PROCEDUREs.
+    Import {
+        path: String,
+        scope: Option<Rc<[Stmt]>>,
+        span: Span,
+    },
+    /// Fails unless the top of the stack is a list or map that can be assigned
+    /// into.
+    CheckArray(Span),
+    /// Fails unless the top of the stack is a position that can be assigned
+    /// to in the list below it.
+    CheckIndex {
+        root: Span,
+        index: Span,
+        span: Span,
+    },
+    /// Pops a value, a position, and a list, and assigns the value there.
+    StoreIndex {
+        root: Span,
+        index: Span,
+        span: Span,
+    },
+    /// Pops a value and returns it. A RETURN without a value only leaves the
+    /// innermost block, continuing at the label, as in the tree-walker.
+    Return(Label),
+    /// Pops the arguments and callee of a RETURN marked as `tail` and leaves
+    /// the call for [`VM::call`] to make.
+    TailCall {
+        span: Span,
+        args: Box<[Span]>,
+    },
+    /// Pops how many times a `REPEAT n TIMES` loop runs and starts the loop.
+    RepeatStart(Span),
+    /// Leaves the innermost `REPEAT n TIMES` loop for `end` once it has run
+    /// enough times.
+    RepeatNext {
+        end: Label,
+    },
+    /// Pops what a `FOR EACH` loop visits and starts the loop.
+    ForStart(Span),
+    /// Defines the variables for the next item of the innermost `FOR EACH`
+    /// loop, or leaves it for `end` if there are no more.
+    ForNext {
+        alias: Span,
+        index: Option<Span>,
+        end: Label,
+    },
+}
+
+/// The instructions for a program's top-level statements or a PROCEDURE's
+/// body.
+#[derive(Debug)]
+pub struct Chunk {
+    ops: Vec<Op>,
+    labels: Vec<usize>,
+}
+
+/// The state of a loop that is running.
+enum Loop {
+    Repeat(u32),
+    For { items: ForEach, next: usize },
+}
+
+/// Compiles `scope`, the top-level statements of a program or a PROCEDURE's
+/// body. Each expression uses up a step of [`VM::set_max_steps`] if `steps`
+/// is set, as in the tree-walker; otherwise only statements are counted, which
+/// keeps loops fast when there is no limit.
+pub fn compile(scope: &[Stmt], steps: bool) -> Chunk {
+    let mut compiler = Compiler {
+        chunk: Chunk {
+            ops: Vec::new(),
+            labels: Vec::new(),
+        },
+        void: Label(0),
+        steps,
+    };
+
+    let end = compiler.label();
+    compiler.block(scope, end);
+    compiler.place(end);
+    compiler.chunk
+}
+
+struct Compiler {
+    chunk: Chunk,
+    /// Where a RETURN without a value continues.
+    void: Label,
+    /// Whether to emit [`Op::Step`] before each expression.
+    steps: bool,
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op) {
+        self.chunk.ops.push(op);
+    }
+
+    fn label(&mut self) -> Label {
+        self.chunk.labels.push(usize::MAX);
+        Label(self.chunk.labels.len() - 1)
+    }
+
+    /// Makes `label` refer to the next instruction.
+    fn place(&mut self, label: Label) {
+        self.chunk.labels[label.0] = self.chunk.ops.len();
+    }
+
+    /// Compiles the statements of a block, where a RETURN without a value
+    /// continues at `void`.
+    fn block(&mut self, scope: &[Stmt], void: Label) {
+        let outer = std::mem::replace(&mut self.void, void);
+        for stmt in scope {
+            self.stmt(stmt);
+        }
+        self.void = outer;
+    }
+
+    /// Compiles a block of an IF used as a value, like
+    /// [`VM::eval_scope_value`]. The parser does not allow RETURN in these
+    /// blocks, so only the value of the last line is left on the stack.
+    fn block_value(&mut self, scope: &[Stmt]) {
+        let rest = self.label();
+        match scope.split_last() {
+            Some((Stmt::Expr(expr), stmts)) => {
+                self.block(stmts, rest);
+                self.place(rest);
+                self.emit(Op::Count);
+                self.expr(expr);
+            }
+            _ => {
+                self.block(scope, rest);
+                self.place(rest);
+                self.emit(Op::Const(Value::Void));
+            }
+        }
+    }
+
+    fn loop_block(&mut self, scope: &[Stmt], next: Label) {
+        self.block(scope, next);
+        self.emit(Op::Jump(next));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.emit(Op::Statement(stmt.span()));
+
+        match stmt {
+            Stmt::Expr(e) => {
+                self.expr(e);
+                self.emit(Op::Pop);
+            }
+            Stmt::VarAssign { name, value } => {
+                self.expr(value);
+                self.emit(Op::Assign {
+                    name: *name,
+                    span: stmt.span(),
+                });
+            }
+            Stmt::Destructure { names, value, .. } => {
+                self.expr(value);
+                self.emit(Op::Destructure {
+                    names: names.clone(),
+                    value: value.span(),
+                    span: stmt.span(),
+                });
+            }
+            Stmt::Procedure(proc) => self.emit(Op::Define(Rc::new(proc.clone()))),
+            Stmt::Import { span, path, scope } => self.emit(Op::Import {
+                path: path.clone(),
+                scope: scope.as_deref().map(Rc::from),
+                span: *span,
+            }),
+            Stmt::IndexAssign { root, index, value } => {
+                self.expr(root);
+                self.emit(Op::CheckArray(root.span()));
+                self.expr(index);
+                self.emit(Op::CheckIndex {
+                    root: root.span(),
+                    index: index.span(),
+                    span: stmt.span(),
+                });
+                self.expr(value);
+                self.emit(Op::StoreIndex {
+                    root: root.span(),
+                    index: index.span(),
+                    span: stmt.span(),
+                });
+            }
+            Stmt::Return { value, tail, .. } => {
+                let mut call = value;
+                while let Expr::Paren { value, .. } = call {
+                    call = value;
+                }
+                if let (true, Expr::FnCall { calle, args, span }) = (tail, call) {
+                    self.expr(calle);
+                    self.args(args);
+                    self.emit(Op::TailCall {
+                        span: *span,
+                        args: args.iter().map(Expr::span).collect(),
+                    });
+                    return;
+                }
+
+                self.expr(value);
+                self.emit(Op::Return(self.void));
+            }
+            Stmt::If {
+                cond,
+                scope,
+                else_ifs,
+                els,
+            } => {
+                let end = self.label();
+                let branches = std::iter::once((cond.as_ref(), scope)).chain(
+                    else_ifs
+                        .iter()
+                        .map(|else_if| (&else_if.cond, &else_if.scope)),
+                );
+                for (cond, scope) in branches {
+                    let next = self.label();
+                    self.expr(cond);
+                    self.emit(Op::JumpIf {
+                        when: false,
+                        cond: cond.span(),
+                        to: next,
+                    });
+                    self.block(scope, end);
+                    self.emit(Op::Jump(end));
+                    self.place(next);
+                }
+                if let Some(els) = els {
+                    self.block(els, end);
+                }
+                self.place(end);
+            }
+            Stmt::RepeatN { n, scope } => {
+                let (next, end) = (self.label(), self.label());
+                self.expr(n);
+                self.emit(Op::RepeatStart(n.span()));
+                self.place(next);
+                self.emit(Op::RepeatNext { end });
+                self.loop_block(scope, next);
+                self.place(end);
+            }
+            Stmt::RepeatUntil { cond, scope } => {
+                let (next, end) = (self.label(), self.label());
+                self.place(next);
+                self.emit(Op::Interrupt(cond.span()));
+                self.expr(cond);
+                self.emit(Op::JumpIf {
+                    when: true,
+                    cond: cond.span(),
+                    to: end,
+                });
+                self.loop_block(scope, next);
+                self.place(end);
+            }
+            Stmt::Custom {
+                span,
+                handler,
+                args,
+            } => {
+                self.emit(Op::LoadHandler {
+                    name: handler.clone(),
+                    span: *span,
+                });
+                self.args(args);
+                self.emit(Op::Call {
+                    span: *span,
+                    args: args.iter().map(Expr::span).collect(),
+                });
+                self.emit(Op::Pop);
+            }
+            Stmt::For {
+                alias,
+                index,
+                array,
+                scope,
+            } => {
+                let (next, end) = (self.label(), self.label());
+                self.expr(array);
+                self.emit(Op::ForStart(array.span()));
+                self.place(next);
+                self.emit(Op::ForNext {
+                    alias: *alias,
+                    index: *index,
+                    end,
+                });
+                self.loop_block(scope, next);
+                self.place(end);
+            }
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) {
+        for arg in args {
+            self.expr(arg);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        if self.steps {
+            self.emit(Op::Step(expr.span()));
+        }
+
+        match expr {
+            Expr::Void => self.emit(Op::Const(Value::Void)),
+            Expr::Index { value, index, span } => {
+                self.expr(value);
+                self.emit(Op::CheckIndexable(*span));
+                self.expr(index);
+                self.emit(Op::Index {
+                    value: value.span(),
+                    index: index.span(),
+                    span: *span,
+                });
+            }
+            Expr::True { .. } => self.emit(Op::Const(Value::Bool(true))),
+            Expr::False { .. } => self.emit(Op::Const(Value::Bool(false))),
+            &Expr::IntegerLiteral { value, .. }
+            | &Expr::FloatLiteral { value, .. }
+            | &Expr::BinaryLiteral { value, .. }
+            | &Expr::HexLiteral { value, .. } => self.emit(Op::Const(Value::Number(value))),
+            &Expr::Identifier { span } => self.emit(Op::Load(span)),
+            Expr::StringLiteral { value, .. } => self.emit(Op::Const(value.as_str().into())),
+            Expr::UnaryOp { kind, value, .. } => {
+                self.expr(value);
+                self.emit(Op::Unary(*kind, value.span()));
+            }
+            Expr::BinaryOp { kind, lhs, rhs } => match kind {
+                BinaryOpKind::And | BinaryOpKind::Or => {
+                    let end = self.label();
+                    self.expr(lhs);
+                    self.emit(Op::ShortCircuit {
+                        or: matches!(kind, BinaryOpKind::Or),
+                        lhs: lhs.span(),
+                        end,
+                    });
+                    self.expr(rhs);
+                    self.emit(Op::CheckBool(rhs.span()));
+                    self.place(end);
+                }
+                _ => {
+                    self.expr(lhs);
+                    self.expr(rhs);
+                    self.emit(Op::Binary {
+                        kind: *kind,
+                        lhs: lhs.span(),
+                        rhs: rhs.span(),
+                    });
+                }
+            },
+            Expr::Paren { value, .. } => self.expr(value),
+            Expr::If {
+                cond,
+                scope,
+                else_ifs,
+                els,
+                ..
+            } => {
+                let end = self.label();
+                let branches = std::iter::once((cond.as_ref(), scope)).chain(
+                    else_ifs
+                        .iter()
+                        .map(|else_if| (&else_if.cond, &else_if.scope)),
+                );
+                for (cond, scope) in branches {
+                    let next = self.label();
+                    self.expr(cond);
+                    self.emit(Op::JumpIf {
+                        when: false,
+                        cond: cond.span(),
+                        to: next,
+                    });
+                    self.block_value(scope);
+                    self.emit(Op::Jump(end));
+                    self.place(next);
+                }
+                self.block_value(els);
+                self.place(end);
+            }
+            Expr::ArrayLiteral { values, .. } => {
+                self.args(values);
+                self.emit(Op::Array {
+                    len: values.len(),
+                    span: expr.span(),
+                });
+            }
+            Expr::MapLiteral { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.expr(key);
+                    self.expr(value);
+                }
+                self.emit(Op::Map {
+                    keys: entries.iter().map(|(key, _)| key.span()).collect(),
+                    span: expr.span(),
+                });
+            }
+            Expr::FnCall { calle, args, span } => {
+                self.expr(calle);
+                self.emit(Op::CheckCallable(calle.span()));
+                self.args(args);
+                self.emit(Op::Call {
+                    span: *span,
+                    args: args.iter().map(Expr::span).collect(),
+                });
+            }
+        }
+    }
+}
+
+impl VM<'_> {
+    /// Runs `chunk` in `env`, giving back what it RETURNs, if anything.
+    pub(crate) fn run_chunk(&mut self, chunk: &Chunk, env: Rc<RefCell<Env>>) -> Value {
+        let mut stack: Vec<Value> = Vec::new();
+        let mut loops: Vec<Loop> = Vec::new();
+        let mut pc = 0;
+
+        while let Some(op) = chunk.ops.get(pc) {
+            pc += 1;
+
+            match op {
+                &Op::Statement(span) => {
+                    self.stats.statements += 1;
+                    if self.interrupted() {
+                        fail!("the program was interrupted", span);
+                    }
+                    if !self.take_step() {
+                        fail!("execution limit exceeded", span, Some(STEPS_HELP.into()));
+                    }
+                }
+                Op::Count => self.stats.statements += 1,
+                &Op::Interrupt(span) => {
+                    if self.interrupted() {
+                        fail!("the program was interrupted", span);
+                    }
+                }
+                &Op::Step(span) => {
+                    if !self.take_step() {
+                        fail!("execution limit exceeded", span, Some(STEPS_HELP.into()));
+                    }
+                }
+                Op::Const(v) => stack.push(v.clone()),
+                &Op::Load(span) => {
+                    let name = self.text(span);
+                    let Some(v) = env.borrow().get(name) else {
+                        fail!(format!("'{}' is not defined", name), span);
+                    };
+                    stack.push(v);
+                }
+                &Op::CheckIndexable(span) => {
+                    let v = stack.last().unwrap();
+                    if !matches!(v, Value::Array(_) | Value::String(_) | Value::Map(_)) {
+                        fail!(format!("{v:?} is not an array"), span);
+                    }
+                }
+                &Op::Index { value, index, span } => {
+                    let idx = stack.pop().unwrap();
+                    let v = stack.pop().unwrap();
+                    stack.push(tee!(self.index(&v, idx, value, index, span)));
+                }
+                &Op::Unary(kind, span) => {
+                    let val = stack.pop().unwrap();
+                    stack.push(tee!(unary_op(kind, val, span)));
+                }
+                &Op::Binary { kind, lhs, rhs } => {
+                    let rhs_value = stack.pop().unwrap();
+                    let lhs_value = stack.pop().unwrap();
+                    stack.push(tee!(self.binary_op(kind, lhs_value, rhs_value, lhs, rhs)));
+                }
+                &Op::ShortCircuit { or, lhs, end } => {
+                    let lhs_value = stack.pop().unwrap();
+                    let Value::Bool(b) = lhs_value else {
+                        fail!(format!("{lhs_value:?} is not a boolean"), lhs);
+                    };
+
+                    if b == or {
+                        stack.push(Value::Bool(b));
+                        pc = chunk.labels[end.0];
+                    }
+                }
+                &Op::CheckBool(span) => {
+                    let v = stack.last().unwrap();
+                    if !matches!(v, Value::Bool(_)) {
+                        fail!(format!("{v:?} is not a boolean"), span);
+                    }
+                }
+                &Op::Array { len, span } => {
+                    let items = stack.split_off(stack.len() - len);
+                    let mut res = self.add_items(items.len(), items.len());
+                    if let Value::Exception(e) = &mut res {
+                        e.span = span;
+                        return res;
+                    }
+                    stack.push(items.into());
+                }
+                Op::Map { keys, span } => {
+                    let mut values = stack.split_off(stack.len() - 2 * keys.len()).into_iter();
+                    let mut entries = Vec::with_capacity(keys.len());
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        entries.push((key, value));
+                    }
+                    stack.push(tee!(self.map(entries, keys, *span)));
+                }
+                &Op::CheckCallable(span) => {
+                    let v = stack.last().unwrap();
+                    if !matches!(v, Value::Procedure(_) | Value::Builtin(_)) {
+                        fail!(format!("{v:?} is not a function"), span);
+                    }
+                }
+                Op::Call { span, args } => {
+                    let argv = stack.split_off(stack.len() - args.len());
+                    let callee = stack.pop().unwrap();
+                    let arg_span = |idx: usize| args.get(idx).copied();
+                    stack.push(tee!(self.call_with(
+                        &callee,
+                        &argv,
+                        arg_span,
+                        *span,
+                        env.clone()
+                    )));
+                }
+                Op::LoadHandler { name, span } => {
+                    let Some(callee) = env.borrow().get(name) else {
+                        fail!(format!("'{name}' is not defined"), *span);
+                    };
+                    stack.push(callee);
+                }
+                Op::Pop => {
+                    stack.pop();
+                }
+                &Op::Jump(to) => pc = chunk.labels[to.0],
+                &Op::JumpIf { when, cond, to } => {
+                    let c = stack.pop().unwrap();
+                    let Value::Bool(b) = c else {
+                        fail!(format!("{c:?} is not a boolean"), cond);
+                    };
+
+                    if b == when {
+                        pc = chunk.labels[to.0];
+                    }
+                }
+                &Op::Assign { name, span } => {
+                    let v = stack.pop().unwrap();
+                    self.assign(&env, name, v, span);
+                }
+                Op::Destructure { names, value, span } => {
+                    let v = stack.pop().unwrap();
+                    tee!(self.destructure(&env, names, v, *value, *span));
+                }
+                Op::Define(proc) => self.define_procedure(&env, proc.clone()),
+                Op::Import { path, scope, span } => {
+                    tee!(self.import(path, scope.as_deref(), *span, &env));
+                }
+                &Op::CheckArray(span) => {
+                    let v = stack.last().unwrap();
+                    if !matches!(v, Value::Array(_) | Value::Map(_)) {
+                        fail!(format!("{v:?} is not an array"), span);
+                    }
+                }
+                &Op::CheckIndex { root, index, span } => {
+                    let [rootv, indexv] = &stack[stack.len() - 2..] else {
+                        unreachable!();
+                    };
+                    tee!(self.check_index(rootv, indexv, root, index, span));
+                }
+                &Op::StoreIndex { root, index, span } => {
+                    let v = stack.pop().unwrap();
+                    let indexv = stack.pop().unwrap();
+                    let rootv = stack.pop().unwrap();
+                    tee!(self.store_index(&rootv, &indexv, v, root, index, span));
+                }
+                &Op::Return(void) => match stack.pop().unwrap() {
+                    Value::Void => pc = chunk.labels[void.0],
+                    v => return v,
+                },
+                Op::TailCall { span, args } => {
+                    let argv = stack.split_off(stack.len() - args.len());
+                    let callee = stack.pop().unwrap();
+
+                    let Value::Procedure(proc) = &callee else {
+                        let arg_span = |idx: usize| args.get(idx).copied();
+                        return self.call_with(&callee, &argv, arg_span, *span, env);
+                    };
+
+                    tee!(check_arity(proc, argv.len(), *span));
+
+                    // Nothing runs after a tail RETURN, so the Void given back
+                    // here unwinds straight to `VM::call`, which makes the
+                    // call.
+                    self.tail_call = Some(TailCall {
+                        proc: proc.clone(),
+                        args: argv,
+                        span: *span,
+                    });
+                    return Value::Void;
+                }
+                &Op::RepeatStart(span) => {
+                    let count = stack.pop().unwrap();
+                    tee!(check_repeat_count(&count, span));
+
+                    let Value::Number(n) = count else {
+                        unreachable!();
+                    };
+                    loops.push(Loop::Repeat(n as u32));
+                }
+                &Op::RepeatNext { end } => {
+                    let Some(Loop::Repeat(n)) = loops.last_mut() else {
+                        unreachable!();
+                    };
+
+                    if *n == 0 {
+                        loops.pop();
+                        pc = chunk.labels[end.0];
+                    } else {
+                        *n -= 1;
+                    }
+                }
+                &Op::ForStart(span) => {
+                    let items = match ForEach::new(stack.pop().unwrap(), span) {
+                        Ok(items) => items,
+                        Err(e) => return e,
+                    };
+                    loops.push(Loop::For { items, next: 0 });
+                }
+                &Op::ForNext { alias, index, end } => {
+                    let Some(Loop::For { items, next }) = loops.last_mut() else {
+                        unreachable!();
+                    };
+
+                    let Some(val) = items.get(*next) else {
+                        loops.pop();
+                        pc = chunk.labels[end.0];
+                        continue;
+                    };
+
+                    *next += 1;
+                    let i = *next;
+                    self.define(&env, alias, val);
+                    if let Some(index) = index {
+                        self.define(&env, index, Value::Number(i as f64));
+                    }
+                }
+            }
+        }
+
+        Value::Void
+    }
+}
@@ -0,0 +1,124 @@
+//! The diagnostic types reported by the parser. With the `std` feature these
+//! are codespan-reporting's. Without it, a minimal copy with the same fields
+//! and builders is used so that the lexer and parser only need `alloc`.
+
+#[cfg(feature = "std")]
+pub use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle, Severity};
+
+#[cfg(not(feature = "std"))]
+pub use lite::*;
+
+#[cfg(not(feature = "std"))]
+mod lite {
+    use alloc::{string::String, vec::Vec};
+    use core::ops::Range;
+
+    /// Ordered from least to most severe.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum Severity {
+        Help,
+        Note,
+        Warning,
+        Error,
+        Bug,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum LabelStyle {
+        Primary,
+        Secondary,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Label<FileId> {
+        pub style: LabelStyle,
+        pub file_id: FileId,
+        pub range: Range<usize>,
+        pub message: String,
+    }
+
+    impl<FileId> Label<FileId> {
+        pub fn new(style: LabelStyle, file_id: FileId, range: impl Into<Range<usize>>) -> Self {
+            Self {
+                style,
+                file_id,
+                range: range.into(),
+                message: String::new(),
+            }
+        }
+
+        pub fn primary(file_id: FileId, range: impl Into<Range<usize>>) -> Self {
+            Self::new(LabelStyle::Primary, file_id, range)
+        }
+
+        pub fn secondary(file_id: FileId, range: impl Into<Range<usize>>) -> Self {
+            Self::new(LabelStyle::Secondary, file_id, range)
+        }
+
+        pub fn with_message(mut self, message: impl Into<String>) -> Self {
+            self.message = message.into();
+            self
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Diagnostic<FileId> {
+        pub severity: Severity,
+        pub code: Option<String>,
+        pub message: String,
+        pub labels: Vec<Label<FileId>>,
+        pub notes: Vec<String>,
+    }
+
+    impl<FileId> Diagnostic<FileId> {
+        pub fn new(severity: Severity) -> Self {
+            Self {
+                severity,
+                code: None,
+                message: String::new(),
+                labels: Vec::new(),
+                notes: Vec::new(),
+            }
+        }
+
+        pub fn bug() -> Self {
+            Self::new(Severity::Bug)
+        }
+
+        pub fn error() -> Self {
+            Self::new(Severity::Error)
+        }
+
+        pub fn warning() -> Self {
+            Self::new(Severity::Warning)
+        }
+
+        pub fn note() -> Self {
+            Self::new(Severity::Note)
+        }
+
+        pub fn help() -> Self {
+            Self::new(Severity::Help)
+        }
+
+        pub fn with_code(mut self, code: impl Into<String>) -> Self {
+            self.code = Some(code.into());
+            self
+        }
+
+        pub fn with_message(mut self, message: impl Into<String>) -> Self {
+            self.message = message.into();
+            self
+        }
+
+        pub fn with_labels(mut self, mut labels: Vec<Label<FileId>>) -> Self {
+            self.labels.append(&mut labels);
+            self
+        }
+
+        pub fn with_notes(mut self, mut notes: Vec<String>) -> Self {
+            self.notes.append(&mut notes);
+            self
+        }
+    }
+}
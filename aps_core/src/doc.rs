@@ -0,0 +1,120 @@
+//! Documentation for the PROCEDUREs of a file, taken from the comments written
+//! directly above each one, for `aps doc`.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::ast::{Span, Stmt};
+
+/// A PROCEDURE and the comment that describes it.
+#[derive(Debug, Clone)]
+pub struct ProcedureDoc {
+    pub name: String,
+    pub params: Vec<String>,
+    /// The comment lines above the PROCEDURE without their `#`, or empty if it
+    /// has none.
+    pub doc: Vec<String>,
+}
+
+impl ProcedureDoc {
+    /// How the PROCEDURE is called, like `area(width, height)`.
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.params.join(", "))
+    }
+
+    /// The comment split into paragraphs at lines with only a `#`.
+    fn paragraphs(&self) -> Vec<String> {
+        self.doc
+            .split(|line| line.is_empty())
+            .filter(|lines| !lines.is_empty())
+            .map(|lines| lines.join("\n"))
+            .collect()
+    }
+}
+
+/// The documentation of each global PROCEDURE in `scope`, which was parsed
+/// from `source`, in the order they are defined.
+pub fn extract(source: &str, scope: &[Stmt]) -> Vec<ProcedureDoc> {
+    let text = |span: Span| &source[span.start as usize..span.end as usize];
+
+    scope
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Procedure(proc) => Some(ProcedureDoc {
+                name: text(proc.name).into(),
+                params: proc
+                    .params
+                    .iter()
+                    .map(|&param| text(param).into())
+                    .collect(),
+                doc: comment_above(source, proc.span.start as usize),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The comment lines right above the line containing `offset`, stopping at
+/// the first line that is not a comment.
+fn comment_above(source: &str, offset: usize) -> Vec<String> {
+    let line_start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+
+    let mut doc = source[..line_start]
+        .lines()
+        .rev()
+        .map_while(|line| line.trim().strip_prefix('#'))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end().into())
+        .collect::<Vec<String>>();
+    doc.reverse();
+    doc
+}
+
+/// Renders the documentation of a file called `title` as Markdown.
+pub fn to_markdown(title: &str, docs: &[ProcedureDoc]) -> String {
+    let mut out = format!("# {title}\n");
+
+    for doc in docs {
+        out.push_str(&format!("\n## `{}`\n", doc.signature()));
+        for paragraph in doc.paragraphs() {
+            out.push_str(&format!("\n{paragraph}\n"));
+        }
+    }
+
+    out
+}
+
+/// Renders the documentation of a file called `title` as a standalone HTML
+/// page.
+pub fn to_html(title: &str, docs: &[ProcedureDoc]) -> String {
+    let title = escape(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    for doc in docs {
+        out.push_str(&format!(
+            "<h2 id=\"{}\"><code>{}</code></h2>\n",
+            escape(&doc.name),
+            escape(&doc.signature())
+        ));
+        for paragraph in doc.paragraphs() {
+            out.push_str(&format!("<p>{}</p>\n", escape(&paragraph)));
+        }
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
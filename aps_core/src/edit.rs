@@ -0,0 +1,66 @@
+//! Span-based text edits, used to apply lint fixes and formatting changes.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Edit {
+    pub fn replace(span: Span, replacement: impl Into<String>) -> Self {
+        Self {
+            span,
+            replacement: replacement.into(),
+        }
+    }
+
+    pub fn insert(offset: u32, text: impl Into<String>) -> Self {
+        Self::replace(
+            Span {
+                start: offset,
+                end: offset,
+            },
+            text,
+        )
+    }
+
+    pub fn delete(span: Span) -> Self {
+        Self::replace(span, "")
+    }
+}
+
+/// A named set of edits that resolves a diagnostic, such as an editor's quick
+/// fix.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub title: String,
+    pub edits: Vec<Edit>,
+}
+
+/// Applies `edits` to `source` in order of position. An edit that overlaps
+/// one before it is skipped so that conflicting fixes never corrupt the text.
+pub fn apply(source: &str, edits: &[Edit]) -> String {
+    let mut edits = edits.iter().collect::<Vec<_>>();
+    edits.sort_by_key(|edit| (edit.span.start, edit.span.end));
+
+    let mut out = String::with_capacity(source.len());
+    let mut cursor = 0;
+
+    for edit in edits {
+        let (start, end) = (edit.span.start as usize, edit.span.end as usize);
+        if start < cursor {
+            continue;
+        }
+
+        out.push_str(&source[cursor..start]);
+        out.push_str(&edit.replacement);
+        cursor = end;
+    }
+
+    out.push_str(&source[cursor..]);
+    out
+}
@@ -0,0 +1,85 @@
+//! Turns the raw bytes of a file into source text. The lexer only understands
+//! UTF-8, so files are checked up front rather than failing in confusing ways
+//! later.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::diagnostic::{Diagnostic, Label};
+
+/// A file's text along with any bytes that had to be replaced.
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub text: String,
+    /// Each run of bytes that was not valid UTF-8: its offset in the file and
+    /// where the U+FFFD that replaced it is in `text`.
+    pub invalid: Vec<(usize, Range<usize>)>,
+}
+
+impl Decoded {
+    /// An error pointing at every replaced run of bytes, or nothing if the
+    /// file was valid.
+    pub fn diagnostics<T: Copy>(&self, fid: T) -> Vec<Diagnostic<T>> {
+        if self.invalid.is_empty() {
+            return Vec::new();
+        }
+
+        let labels = self
+            .invalid
+            .iter()
+            .map(|(offset, range)| {
+                Label::primary(fid, range.clone())
+                    .with_message(format!("invalid UTF-8 at byte {offset}"))
+            })
+            .collect();
+
+        vec![Diagnostic::error()
+            .with_message("file is not valid UTF-8")
+            .with_labels(labels)
+            .with_notes(vec!["save the file with UTF-8 encoding".into()])]
+    }
+}
+
+/// Decodes `bytes` as UTF-8, dropping a leading byte order mark and replacing
+/// invalid bytes with U+FFFD.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+    let start = if bytes.starts_with(BOM) { BOM.len() } else { 0 };
+    let mut rest = &bytes[start..];
+    let mut offset = start;
+
+    let mut decoded = Decoded {
+        text: String::with_capacity(rest.len()),
+        invalid: Vec::new(),
+    };
+
+    loop {
+        match core::str::from_utf8(rest) {
+            Ok(text) => {
+                decoded.text.push_str(text);
+                break;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                // `valid_up_to` guarantees that this prefix is valid.
+                decoded
+                    .text
+                    .push_str(core::str::from_utf8(valid).unwrap_or_default());
+
+                let len = e.error_len().unwrap_or(after.len());
+                let at = decoded.text.len();
+                decoded.text.push(char::REPLACEMENT_CHARACTER);
+                decoded.invalid.push((
+                    offset + valid.len(),
+                    at..at + char::REPLACEMENT_CHARACTER.len_utf8(),
+                ));
+
+                offset += valid.len() + len;
+                rest = &after[len..];
+            }
+        }
+    }
+
+    decoded
+}
@@ -0,0 +1,754 @@
+//! A high-level API for hosts that embed the interpreter, such as graders and
+//! the web playground.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    io::Write,
+    ops::Range,
+    rc::Rc,
+    sync::{atomic::AtomicBool, Arc},
+    thread,
+};
+
+use codespan_reporting::diagnostic::Diagnostic;
+use gc::{Gc, GcCell};
+
+#[cfg(feature = "js")]
+use crate::vm::DisplayHook;
+use crate::{
+    ast::{Span, Stmt},
+    bytecode::Backend,
+    parser::{Dialect, Parser},
+    permissions::Permissions,
+    stdlib::{self, robot::Robot, turtle::Turtle},
+    vm::{
+        Array, AssignHook, Assignment, Env, Exception, ExplainHook, Explanation, InputProvider,
+        Map, Set, Stats, Value, Warning, VM,
+    },
+};
+
+/// The stack size to give threads that run programs. Each nested PROCEDURE
+/// call takes several frames of the interpreter, and without optimizations
+/// [`DEFAULT_MAX_DEPTH`](crate::vm::DEFAULT_MAX_DEPTH) of them do not fit in
+/// the usual 2 MiB, or even in the 8 MiB of a main thread.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Runs programs with host-provided setup, such as pre-seeded globals.
+#[derive(Default)]
+pub struct Engine {
+    globals: Vec<(String, Value)>,
+    input: Option<Vec<String>>,
+    input_provider: Option<Rc<RefCell<dyn InputProvider>>>,
+    capture_output: bool,
+    output: Option<Rc<RefCell<dyn Write>>>,
+    result: Option<String>,
+    seed: Option<u64>,
+    on_assign: Vec<Rc<RefCell<AssignHook>>>,
+    on_explain: Option<Rc<RefCell<ExplainHook>>>,
+    #[cfg(feature = "js")]
+    on_display: Option<Rc<RefCell<DisplayHook>>>,
+    check_precision: bool,
+    max_array_len: Option<usize>,
+    max_items: Option<usize>,
+    max_string_len: Option<usize>,
+    max_depth: Option<usize>,
+    max_steps: Option<u64>,
+    parse: ParseOptions,
+    interrupt: Option<Arc<AtomicBool>>,
+    permissions: Permissions,
+    backend: Backend,
+    robot: Option<Robot>,
+}
+
+/// How [`parse_with`] reads a program.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    pub dialect: Dialect,
+    /// Whether RETURN may end the program with a value. See
+    /// [`Engine::script_mode`].
+    pub script: bool,
+}
+
+/// Everything known about a run of a program, as returned by [`Engine::run`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RunOutcome {
+    /// What the program evaluated to in [`Engine::script_mode`], or the global
+    /// chosen with [`Engine::with_result`]. [`Value::Void`] if the program
+    /// failed.
+    pub value: Value,
+    /// Everything the program displayed, if [`Engine::capture_output`] was
+    /// used.
+    pub captured_output: String,
+    /// Parse errors. The program only runs when there are none.
+    pub diagnostics: Vec<Diagnostic<usize>>,
+    /// The runtime error that stopped the program.
+    pub exception: Option<Exception>,
+    /// Problems noticed while running, if checks such as
+    /// [`Engine::check_precision`] were enabled.
+    pub warnings: Vec<Warning>,
+    pub stats: Stats,
+    /// What the program drew with the turtle graphics builtins.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub turtle: Turtle,
+    /// Where the robot ended up, if there was a grid for it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub robot: Option<Robot>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    globals: HashMap<String, Value>,
+}
+
+impl RunOutcome {
+    /// The outcome of a program that did not run because of `diagnostics`.
+    pub fn failed(diagnostics: Vec<Diagnostic<usize>>) -> Self {
+        Self {
+            value: Value::Void,
+            captured_output: String::new(),
+            diagnostics,
+            exception: None,
+            warnings: Vec::new(),
+            stats: Stats::default(),
+            turtle: Turtle::default(),
+            robot: None,
+            globals: HashMap::new(),
+        }
+    }
+
+    /// The global variables and PROCEDUREs as they were when the program
+    /// stopped, including any seeded with [`Engine::with_global`]. Builtins
+    /// are left out.
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.globals
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// The parse errors, or the exception as diagnostics if the program failed
+    /// at runtime.
+    pub fn errors(&self, fid: usize) -> Vec<Diagnostic<usize>> {
+        match &self.exception {
+            Some(e) => e.to_diagnostics(fid),
+            None => self.diagnostics.clone(),
+        }
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines a global variable before the program runs, as if the program
+    /// began with `name <- value`.
+    pub fn with_global(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.globals.push((name.into(), value.into()));
+        self
+    }
+
+    /// Answers `INPUT` calls with these lines, in order, instead of prompting.
+    pub fn with_input(mut self, lines: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.input = Some(lines.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Answers `INPUT` calls with `provider`, such as a closure that takes the
+    /// prompt, instead of prompting. Takes precedence over
+    /// [`Engine::with_input`]. See [`VM::input`].
+    pub fn with_input_provider(mut self, provider: impl InputProvider + 'static) -> Self {
+        self.input_provider = Some(Rc::new(RefCell::new(provider)));
+        self
+    }
+
+    /// Collects the program's output into [`RunOutcome::captured_output`]
+    /// instead of printing it. Has no effect in the browser, where output goes
+    /// to the console.
+    pub fn capture_output(mut self) -> Self {
+        self.capture_output = true;
+        self
+    }
+
+    /// Writes the program's output to `writer` instead of stdout, alongside
+    /// any capture. Has no effect in the browser.
+    pub fn with_output(mut self, writer: impl Write + 'static) -> Self {
+        self.output = Some(Rc::new(RefCell::new(writer)));
+        self
+    }
+
+    /// Makes [`RunOutcome::value`] the final value of the global `name`, or
+    /// what it returns if it is a PROCEDURE without parameters.
+    pub fn with_result(mut self, name: impl Into<String>) -> Self {
+        self.result = Some(name.into());
+        self
+    }
+
+    /// Seeds the random number generator so that `RANDOM`, `SHUFFLE`, and the
+    /// like behave the same on every run. Has no effect in the browser.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Calls `hook` after each assignment to a variable, e.g. to trace how a
+    /// variable changes. Hooks added by calling this again run after the
+    /// earlier ones. See [`VM::on_assign`].
+    pub fn on_assign(mut self, hook: impl FnMut(&Assignment) + 'static) -> Self {
+        self.on_assign.push(Rc::new(RefCell::new(hook)));
+        self
+    }
+
+    /// Calls `hook` as each statement runs with a plain-English description of
+    /// what it did. See [`VM::on_explain`].
+    pub fn on_explain(mut self, hook: impl FnMut(&Explanation) + 'static) -> Self {
+        self.on_explain = Some(Rc::new(RefCell::new(hook)));
+        self
+    }
+
+    /// Calls `hook` with the text of each `DISPLAY` instead of logging it to
+    /// the browser console. See [`VM::on_display`].
+    #[cfg(feature = "js")]
+    pub fn on_display(mut self, hook: impl FnMut(&str) + 'static) -> Self {
+        self.on_display = Some(Rc::new(RefCell::new(hook)));
+        self
+    }
+
+    /// Warns about arithmetic results too large for every whole number near
+    /// them to be stored exactly, which can make counters skip values. See
+    /// [`RunOutcome::warnings`].
+    pub fn check_precision(mut self) -> Self {
+        self.check_precision = true;
+        self
+    }
+
+    /// Raises an exception when a list or set would hold more than `max`
+    /// items.
+    pub fn with_max_array_len(mut self, max: usize) -> Self {
+        self.max_array_len = Some(max);
+        self
+    }
+
+    /// Raises an exception when the program has added more than `max` items
+    /// to lists and sets in total, so that a runaway loop fails before it
+    /// exhausts memory. See [`VM::max_items`].
+    pub fn with_max_items(mut self, max: usize) -> Self {
+        self.max_items = Some(max);
+        self
+    }
+
+    /// Raises an exception when a builtin would build a string longer than
+    /// `max` characters. See [`VM::max_string_len`].
+    pub fn with_max_string_len(mut self, max: usize) -> Self {
+        self.max_string_len = Some(max);
+        self
+    }
+
+    /// Raises an exception when more than `max` PROCEDURE calls would be
+    /// running at once. See [`VM::max_depth`].
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Raises an exception once the program has evaluated `max` statements
+    /// and expressions, so that a loop that never ends cannot hang the host.
+    /// See [`VM::set_max_steps`].
+    pub fn with_max_steps(mut self, max: u64) -> Self {
+        self.max_steps = Some(max);
+        self
+    }
+
+    /// Stops the program with an exception once `interrupt` is set, such as
+    /// from a Ctrl-C handler. See [`VM::interrupt`].
+    pub fn with_interrupt(mut self, interrupt: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    /// Accepts programs written in `dialect` instead of the AP pseudocode.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.parse.dialect = dialect;
+        self
+    }
+
+    /// Makes the program's result its top-level RETURN value or, if it has
+    /// none, the value of its last statement when that is an expression. This
+    /// lets hosts such as graders check a result without parsing what the
+    /// program displays.
+    pub fn script_mode(mut self) -> Self {
+        self.parse.script = true;
+        self
+    }
+
+    /// Lets builtins reach outside the program as far as `permissions` allow.
+    /// See [`VM::permissions`].
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Puts the robot on `robot`'s grid before the program runs, for the
+    /// robot builtins such as `MOVE_FORWARD`.
+    pub fn with_robot(mut self, robot: Robot) -> Self {
+        self.robot = Some(robot);
+        self
+    }
+
+    /// Runs the program with `backend` instead of the tree-walker. See
+    /// [`Backend`].
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// How [`Engine::run`] parses programs, for hosts that parse them
+    /// separately and call [`Engine::run_parsed`].
+    pub fn parse_options(&self) -> ParseOptions {
+        self.parse
+    }
+
+    /// Parses and runs `source`. Diagnostics use `0` as the file id.
+    ///
+    /// Like [`Engine::run_parsed`], this runs on the calling thread.
+    pub fn run(&self, source: &str) -> RunOutcome {
+        match parse_with(source, self.parse) {
+            Ok(scope) => self.run_parsed(source, &scope),
+            Err(diagnostics) => RunOutcome::failed(diagnostics),
+        }
+    }
+
+    /// Runs a program that was already parsed from `source` with
+    /// [`parse_with`] and [`Engine::parse_options`], such as one loaded from a
+    /// cache.
+    ///
+    /// The program runs on the calling thread, which should have a stack of
+    /// [`STACK_SIZE`] for recursion to reach [`Engine::with_max_depth`]. On a
+    /// smaller stack, recursion ends sooner with the same "maximum recursion
+    /// depth exceeded" exception rather than overflowing; [`run_isolated`]
+    /// runs programs on a thread of the right size.
+    pub fn run_parsed(&self, source: &str, scope: &[Stmt]) -> RunOutcome {
+        let mut env = Env::new();
+        stdlib::inject(&mut env);
+        env.entries.extend(
+            self.globals
+                .iter()
+                .map(|(name, value)| (name.clone(), fresh(value))),
+        );
+
+        let env = Rc::new(RefCell::new(env));
+        let mut vm = VM::new(source).with_backend(self.backend);
+        if let Some(provider) = self.input_provider.clone() {
+            vm.input = Some(Box::new(move |prompt: &str| {
+                provider.borrow_mut().answer(prompt)
+            }));
+        } else if let Some(input) = &self.input {
+            vm.input = Some(Box::new(VecDeque::from(input.clone())));
+        }
+        vm.check_precision = self.check_precision;
+        vm.max_array_len = self.max_array_len;
+        vm.max_items = self.max_items;
+        vm.max_string_len = self.max_string_len;
+        if let Some(max) = self.max_depth {
+            vm.max_depth = max;
+        }
+        vm.set_max_steps(self.max_steps);
+        vm.interrupt = self.interrupt.clone();
+        vm.permissions = self.permissions.clone();
+        vm.robot = self.robot.clone();
+        if !self.on_assign.is_empty() {
+            let hooks = self.on_assign.clone();
+            vm.on_assign = Some(Box::new(move |assignment| {
+                for hook in &hooks {
+                    (hook.borrow_mut())(assignment);
+                }
+            }));
+        }
+        if let Some(hook) = self.on_explain.clone() {
+            vm.on_explain = Some(Box::new(move |explanation| {
+                (hook.borrow_mut())(explanation)
+            }));
+        }
+        #[cfg(feature = "js")]
+        if let Some(hook) = self.on_display.clone() {
+            vm.on_display = Some(Box::new(move |text| (hook.borrow_mut())(text)));
+        }
+        #[cfg(not(feature = "js"))]
+        if let Some(seed) = self.seed {
+            vm.rng = Some(rand::SeedableRng::seed_from_u64(seed));
+        }
+
+        let output = Output {
+            capture: self.capture_output.then(Default::default),
+            writer: self.output.clone(),
+        };
+        #[cfg(not(feature = "js"))]
+        if output.capture.is_some() || output.writer.is_some() {
+            vm.output = Box::new(output.clone());
+            vm.terminal = false;
+            vm.color = false;
+        }
+
+        let mut value = if self.parse.script {
+            vm.eval_scope_value(scope, env.clone())
+        } else {
+            vm.run(scope, env.clone())
+        };
+
+        if let (Some(name), Value::Void) = (&self.result, &value) {
+            let global = env.borrow().get(name);
+            value = match global {
+                Some(proc @ Value::Procedure(_)) => {
+                    vm.call(&proc, &[], &[], Span { start: 0, end: 0 }, env.clone())
+                }
+                global => global.unwrap_or(Value::Void),
+            };
+        }
+
+        let stats = vm.stats;
+        let turtle = std::mem::take(&mut vm.turtle);
+        let robot = vm.robot.take();
+        let warnings = std::mem::take(&mut vm.warnings);
+        drop(vm);
+
+        let exception = value.take_exception();
+
+        let globals = std::mem::take(&mut env.borrow_mut().entries)
+            .into_iter()
+            .filter(|(_, value)| !matches!(value, Value::Builtin(_)))
+            .collect();
+
+        let captured_output = output
+            .capture
+            .map(|capture| String::from_utf8_lossy(&capture.borrow()).into_owned())
+            .unwrap_or_default();
+
+        RunOutcome {
+            value,
+            captured_output,
+            diagnostics: Vec::new(),
+            exception,
+            warnings,
+            stats,
+            turtle,
+            robot,
+            globals,
+        }
+    }
+}
+
+/// Parses a whole program. Diagnostics use `0` as the file id.
+pub fn parse(source: &str) -> Result<Box<[Stmt]>, Vec<Diagnostic<usize>>> {
+    parse_with(source, ParseOptions::default())
+}
+
+/// Parses a whole program with the given options.
+pub fn parse_with(
+    source: &str,
+    options: ParseOptions,
+) -> Result<Box<[Stmt]>, Vec<Diagnostic<usize>>> {
+    crate::trace_span!("parse", bytes = source.len());
+
+    let mut parser = Parser::new(0, source.as_bytes());
+    parser.dialect = options.dialect;
+    parser.script = options.script;
+
+    let scope = parser.parse_program();
+
+    #[cfg(feature = "trace")]
+    tracing::debug!(diagnostics = parser.diagnostics.len(), "parsed");
+
+    match (scope, parser.diagnostics.is_empty()) {
+        (Ok(scope), true) => Ok(scope),
+        _ => Err(parser.diagnostics),
+    }
+}
+
+/// An interactive session, such as a REPL, where each fragment of source runs
+/// with the globals left behind by the ones before it.
+pub struct Session {
+    /// Every fragment entered so far. Fragments are parsed in place so that the
+    /// spans of PROCEDUREs from earlier fragments stay valid.
+    buffer: String,
+    /// The fragments that ran without errors, as ranges of `buffer`.
+    fragments: Vec<Range<usize>>,
+    env: Rc<RefCell<Env>>,
+    /// The turtle drawing, which carries over between fragments.
+    turtle: Turtle,
+    /// The robot's grid, which also carries over.
+    robot: Option<Robot>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let mut env = Env::new();
+        stdlib::inject(&mut env);
+
+        Self {
+            buffer: String::new(),
+            fragments: Vec::new(),
+            env: Rc::new(RefCell::new(env)),
+            turtle: Turtle::default(),
+            robot: None,
+        }
+    }
+
+    /// All source entered so far, which the spans in each [`RunOutcome`] point
+    /// into.
+    pub fn source(&self) -> &str {
+        &self.buffer
+    }
+
+    /// The names of every global, including builtins.
+    pub fn names(&self) -> Vec<String> {
+        self.env.borrow().entries.keys().cloned().collect()
+    }
+
+    /// The variables and PROCEDUREs defined so far, sorted by name, leaving out
+    /// builtins.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals = self
+            .env
+            .borrow()
+            .entries
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::Builtin(_)))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    /// Parses and runs `fragment`. If it ends with an expression, its value
+    /// becomes [`RunOutcome::value`].
+    pub fn eval(&mut self, fragment: &str) -> RunOutcome {
+        let start = self.buffer.len();
+        self.buffer.push_str(fragment);
+        self.buffer.push('\n');
+
+        let mut parser = Parser::new(0, self.buffer.as_bytes());
+        parser.lex.index = start;
+
+        let scope = parser.parse_program();
+
+        let (Ok(scope), true) = (scope, parser.diagnostics.is_empty()) else {
+            return RunOutcome::failed(parser.diagnostics);
+        };
+
+        let mut vm = VM::new(&self.buffer);
+        vm.turtle = std::mem::take(&mut self.turtle);
+        vm.robot = self.robot.take();
+        let mut value = vm.eval_scope_value(&scope, self.env.clone());
+        let stats = vm.stats;
+        self.turtle = std::mem::take(&mut vm.turtle);
+        self.robot = vm.robot.take();
+
+        let exception = value.take_exception();
+        if exception.is_none() {
+            self.fragments.push(start..self.buffer.len());
+        }
+
+        RunOutcome {
+            value,
+            captured_output: String::new(),
+            diagnostics: Vec::new(),
+            exception,
+            warnings: std::mem::take(&mut vm.warnings),
+            stats,
+            turtle: self.turtle.clone(),
+            robot: self.robot.clone(),
+            globals: HashMap::new(),
+        }
+    }
+
+    /// The fragments that ran without errors, in order, as a program that
+    /// recreates this session's definitions when run or loaded into another
+    /// session.
+    pub fn save(&self) -> String {
+        self.fragments
+            .iter()
+            .map(|range| &self.buffer[range.clone()])
+            .collect()
+    }
+}
+
+/// Sends the program's output to the capture buffer, the host's writer, or
+/// both.
+#[derive(Clone)]
+struct Output {
+    capture: Option<Rc<RefCell<Vec<u8>>>>,
+    writer: Option<Rc<RefCell<dyn Write>>>,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(capture) = &self.capture {
+            capture.borrow_mut().extend_from_slice(buf);
+        }
+        if let Some(writer) = &self.writer {
+            writer.borrow_mut().write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &self.writer {
+            Some(writer) => writer.borrow_mut().flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Copies lists and maps so that one run cannot change the values seen by the next.
+fn fresh(value: &Value) -> Value {
+    match value {
+        Value::Array(array) => Value::Array(Gc::new(GcCell::new(Array {
+            items: array.borrow().items.iter().map(fresh).collect(),
+        }))),
+        Value::Set(set) => Value::Set(Gc::new(GcCell::new(set.borrow().clone()))),
+        Value::Map(map) => {
+            let mut copy = Map::default();
+            for (key, value) in map.borrow().entries.iter() {
+                copy.insert(key.clone(), fresh(value));
+            }
+            Value::Map(Gc::new(GcCell::new(copy)))
+        }
+        value => value.clone(),
+    }
+}
+
+/// A copy of a [`Value`] that owns its data and so can be sent between
+/// threads. Serializes the same way as [`Value`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum OwnedValue {
+    Void,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<OwnedValue>),
+    Set(Vec<OwnedValue>),
+    /// Keys and their values, in order.
+    Map(Vec<(OwnedValue, OwnedValue)>),
+    /// A PROCEDURE or builtin, which cannot leave its VM, described by its
+    /// display text.
+    Function(String),
+}
+
+impl From<&Value> for OwnedValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Void | Value::Exception(_) => Self::Void,
+            &Value::Bool(b) => Self::Bool(b),
+            &Value::Number(n) => Self::Number(n),
+            Value::String(s) => Self::String(s.as_str().into()),
+            Value::Array(array) => {
+                Self::List(array.borrow().items.iter().map(Into::into).collect())
+            }
+            Value::Set(set) => Self::Set(set.borrow().items.iter().map(Into::into).collect()),
+            Value::Map(map) => Self::Map(
+                map.borrow()
+                    .entries
+                    .iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            Value::Builtin(_) | Value::Procedure(_) => Self::Function(value.to_string()),
+        }
+    }
+}
+
+/// Functions become [`Value::Void`] since they cannot be rebuilt from their
+/// description.
+impl From<OwnedValue> for Value {
+    fn from(value: OwnedValue) -> Self {
+        match value {
+            OwnedValue::Void | OwnedValue::Function(_) => Value::Void,
+            OwnedValue::Bool(b) => Value::Bool(b),
+            OwnedValue::Number(n) => Value::Number(n),
+            OwnedValue::String(s) => s.into(),
+            OwnedValue::List(items) => items.into(),
+            OwnedValue::Set(items) => {
+                let mut set = Set::default();
+                for item in items {
+                    set.insert(item.into());
+                }
+                Value::Set(Gc::new(GcCell::new(set)))
+            }
+            OwnedValue::Map(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    map.insert(key.into(), value.into());
+                }
+                Value::Map(Gc::new(GcCell::new(map)))
+            }
+        }
+    }
+}
+
+/// Runs `source` with its output captured, for hosts such as test harnesses
+/// that check what a program displayed. `options` is the [`Engine`] to run
+/// it with, e.g. `Engine::new().with_input(["5"])`.
+pub fn run_str(source: &str, options: Engine) -> RunOutcome {
+    options.capture_output().run(source)
+}
+
+/// The result of [`run_isolated`], holding only owned data.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IsolatedResult {
+    pub diagnostics: Vec<Diagnostic<usize>>,
+    pub output: String,
+    pub exception: Option<Exception>,
+    pub globals: HashMap<String, OwnedValue>,
+}
+
+/// Runs `source` on a dedicated thread and waits for it to finish, with its
+/// output captured. `setup` configures the [`Engine`] on that thread, e.g.
+/// `|engine| engine.with_global("n", OwnedValue::Number(5.))`.
+///
+/// [`Value`]s are tied to the thread that created them, so servers grading
+/// many programs at once should use this rather than sharing an [`Engine`]
+/// between threads.
+pub fn run_isolated(source: &str, setup: impl FnOnce(Engine) -> Engine + Send) -> IsolatedResult {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let outcome = setup(Engine::new().capture_output()).run(source);
+
+                IsolatedResult {
+                    globals: outcome
+                        .globals
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.into()))
+                        .collect(),
+                    diagnostics: outcome.diagnostics,
+                    output: outcome.captured_output,
+                    exception: outcome.exception,
+                }
+            })
+            .ok()
+            .and_then(|handle| handle.join().ok())
+            // The interpreter reports errors as exceptions, so this should
+            // never happen, but a grader must not go down with one program.
+            .unwrap_or_else(|| IsolatedResult {
+                diagnostics: Vec::new(),
+                output: String::new(),
+                exception: Some(Exception {
+                    message: "the interpreter crashed".into(),
+                    span: Span { start: 0, end: 0 },
+                    stack: Vec::new(),
+                    help: None,
+                    arg: None,
+                }),
+                globals: HashMap::new(),
+            })
+    })
+}
@@ -0,0 +1,514 @@
+//! Lays out a program the standard way, for `aps fmt`: a tab for each level of
+//! nesting, opening braces at the end of the line, and single spaces around
+//! `<-` and operators. Comments are not part of the AST, so they are found in
+//! the source and kept next to the statements they were written beside.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    ast::{BinaryOpKind, ElseIf, Expr, Node, Span, Stmt, UnaryOpKind, MAX_NESTING},
+    lexer::{Lexer, Token},
+};
+
+/// Formats `scope`, which was parsed from `source`.
+pub fn format(source: &str, scope: &[Stmt]) -> String {
+    let mut formatter = Formatter::new(source);
+    for stmt in scope {
+        formatter.stmt(stmt);
+    }
+
+    formatter.comments_before(source.len() as u32);
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n');
+    }
+    formatter.out
+}
+
+struct Comment {
+    /// From the `#` to the end of the line.
+    span: Span,
+    /// Whether nothing comes before the comment on its line.
+    own_line: bool,
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    out: String,
+    indent: usize,
+    comments: Vec<Comment>,
+    /// How many of `comments` have been written.
+    written: usize,
+    /// Where each `{` and `}` token starts, in order.
+    opens: Vec<u32>,
+    closes: Vec<u32>,
+    /// The end of the last part of the source that was written.
+    pos: u32,
+    /// Whether the last line written ends with a `{`.
+    opened: bool,
+    /// How many expressions enclose the one being written.
+    depth: u32,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut comments = Vec::new();
+        let mut opens = Vec::new();
+        let mut closes = Vec::new();
+
+        let mut lex = Lexer::new(source.as_bytes());
+        let mut end = 0;
+        loop {
+            lex.next();
+
+            // Only whitespace and comments are skipped between tokens.
+            let mut idx = end;
+            while let Some(start) = source[idx..lex.start].find('#').map(|i| idx + i) {
+                idx = source[start..lex.start]
+                    .find('\n')
+                    .map_or(lex.start, |i| start + i);
+                let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+                comments.push(Comment {
+                    span: Span {
+                        start: start as u32,
+                        end: (start + source[start..idx].trim_end().len()) as u32,
+                    },
+                    own_line: source[line_start..start].trim().is_empty(),
+                });
+            }
+
+            match lex.token {
+                Token::LeftBrace => opens.push(lex.start as u32),
+                Token::RightBrace => closes.push(lex.start as u32),
+                Token::EOF => break,
+                _ => {}
+            }
+            end = lex.index;
+        }
+
+        Self {
+            source,
+            out: String::new(),
+            indent: 0,
+            comments,
+            written: 0,
+            opens,
+            closes,
+            pos: 0,
+            opened: false,
+            depth: 0,
+        }
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        &self.source[span.start as usize..span.end as usize]
+    }
+
+    /// Starts a new line, after a blank one if `blank` and the line before
+    /// does not open a block.
+    fn newline(&mut self, blank: bool) {
+        if !self.out.is_empty() {
+            if blank && !self.opened {
+                self.out.push('\n');
+            }
+            self.out.push('\n');
+        }
+        self.opened = false;
+        for _ in 0..self.indent {
+            self.out.push('\t');
+        }
+    }
+
+    /// Whether the source has a blank line between what was last written and
+    /// `offset`. Several blank lines in a row are written as one.
+    fn blank_before(&self, offset: u32) -> bool {
+        if offset <= self.pos {
+            return false;
+        }
+
+        let gap = self.text(Span {
+            start: self.pos,
+            end: offset,
+        });
+        let mut lines = gap.split('\n');
+        lines.next();
+        lines.next_back();
+        lines.any(|line| line.trim().is_empty())
+    }
+
+    /// Writes the comments that start before `offset`. A comment that ended a
+    /// line of code stays at the end of the line being written.
+    fn comments_before(&mut self, offset: u32) {
+        while let Some(comment) = self
+            .comments
+            .get(self.written)
+            .filter(|comment| comment.span.start < offset)
+        {
+            let span = comment.span;
+            if comment.own_line || self.out.is_empty() {
+                self.newline(self.blank_before(span.start));
+            } else {
+                self.out.push(' ');
+            }
+            self.out.push_str(self.text(span));
+            self.pos = self.pos.max(span.end);
+            self.written += 1;
+        }
+    }
+
+    fn has_comment(&self, span: Span) -> bool {
+        self.comments
+            .iter()
+            .any(|comment| span.start <= comment.span.start && comment.span.start < span.end)
+    }
+
+    /// Writes ` {`, the statements of `scope` a level deeper, and `}` on a
+    /// line of its own, or ` {}` if the block is empty.
+    fn block(&mut self, scope: &[Stmt]) {
+        let open = self.opens[self.opens.partition_point(|&open| open < self.pos)];
+        // Deeper blocks never come from the parser, so they are not laid out.
+        if self.indent as u32 == MAX_NESTING {
+            let end = scope.last().map_or(open, |stmt| stmt.span().end);
+            let close = self.closes[self.closes.partition_point(|&close| close < end)];
+            self.out.push(' ');
+            self.out.push_str(self.text(Span {
+                start: open,
+                end: close + 1,
+            }));
+            self.pos = close + 1;
+            return;
+        }
+        self.out.push_str(" {");
+        self.opened = true;
+        self.pos = open + 1;
+
+        self.indent += 1;
+        for stmt in scope {
+            self.stmt(stmt);
+        }
+        let close = self.closes[self.closes.partition_point(|&close| close < self.pos)];
+        self.comments_before(close);
+        self.indent -= 1;
+
+        if !self.opened {
+            self.newline(false);
+        }
+        self.opened = false;
+        self.out.push('}');
+        self.pos = close + 1;
+    }
+
+    /// Writes `IF (cond) { ... }` and any ELSE IF and ELSE blocks after it.
+    fn if_blocks(
+        &mut self,
+        cond: &Expr,
+        scope: &[Stmt],
+        else_ifs: &[ElseIf],
+        els: Option<&[Stmt]>,
+    ) {
+        self.out.push_str("IF (");
+        self.expr(cond);
+        self.out.push(')');
+        self.pos = self.pos.max(cond.span().end);
+        self.block(scope);
+
+        for else_if in else_ifs {
+            self.out.push_str(" ELSE IF (");
+            self.expr(&else_if.cond);
+            self.out.push(')');
+            self.pos = self.pos.max(else_if.cond.span().end);
+            self.block(&else_if.scope);
+        }
+
+        if let Some(els) = els {
+            self.out.push_str(" ELSE");
+            self.block(els);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let start = stmt.span().start;
+        self.comments_before(start);
+        self.newline(self.blank_before(start));
+
+        match stmt {
+            Stmt::Return {
+                value: Expr::Void, ..
+            } => self.out.push_str("RETURN"),
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Expr::Paren { value, .. } => &**value,
+                    value => value,
+                };
+                self.out.push_str("RETURN(");
+                self.expr(value);
+                self.out.push(')');
+            }
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::VarAssign { name, value } => {
+                self.out.push_str(self.text(*name));
+                self.out.push_str(" <- ");
+                self.expr(value);
+            }
+            Stmt::IndexAssign { root, index, value } => {
+                self.expr(root);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push_str("] <- ");
+                self.expr(value);
+            }
+            Stmt::Destructure { names, value, .. } => {
+                self.out.push('[');
+                self.names(names);
+                self.out.push_str("] <- ");
+                self.expr(value);
+            }
+            Stmt::If {
+                cond,
+                scope,
+                else_ifs,
+                els,
+            } => self.if_blocks(cond, scope, else_ifs, els.as_deref()),
+            Stmt::Procedure(proc) => {
+                self.out.push_str("PROCEDURE ");
+                self.out.push_str(self.text(proc.name));
+                self.out.push('(');
+                self.names(&proc.params);
+                self.out.push(')');
+                self.pos = proc.name.end;
+                self.block(&proc.scope);
+            }
+            Stmt::RepeatN { n, scope } => {
+                self.out.push_str("REPEAT ");
+                self.expr(n);
+                self.out.push_str(" TIMES");
+                self.pos = self.pos.max(n.span().end);
+                self.block(scope);
+            }
+            Stmt::RepeatUntil { cond, scope } => {
+                self.out.push_str("REPEAT UNTIL (");
+                self.expr(cond);
+                self.out.push(')');
+                self.pos = self.pos.max(cond.span().end);
+                self.block(scope);
+            }
+            Stmt::For {
+                alias,
+                index,
+                array,
+                scope,
+            } => {
+                self.out.push_str("FOR EACH ");
+                self.out.push_str(self.text(*alias));
+                if let Some(index) = index {
+                    self.out.push_str(", ");
+                    self.out.push_str(self.text(*index));
+                }
+                self.out.push_str(" IN ");
+                self.expr(array);
+                self.pos = self.pos.max(array.span().end);
+                self.block(scope);
+            }
+            // The words of a custom statement are up to the embedder, so it
+            // is kept as written.
+            &Stmt::Custom { span, .. } => self.out.push_str(self.text(span)),
+            &Stmt::Import { span, .. } => {
+                // The path is kept as written, escapes and all.
+                let path = self.text(span)["IMPORT".len()..].trim_start();
+                self.out.push_str("IMPORT ");
+                self.out.push_str(path);
+            }
+        }
+
+        if !matches!(
+            stmt,
+            Stmt::If { .. }
+                | Stmt::Procedure(_)
+                | Stmt::RepeatN { .. }
+                | Stmt::RepeatUntil { .. }
+                | Stmt::For { .. }
+        ) {
+            self.pos = self.pos.max(stmt.span().end);
+        }
+    }
+
+    fn names(&mut self, names: &[Span]) {
+        for (i, &name) in names.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(self.text(name));
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    /// Whether the IF expressions in `expr` can all be written on one line,
+    /// like `IF (a > b) { a } ELSE { b }`, because each block is only its
+    /// value and there are no comments inside.
+    fn fits_on_line(&self, expr: &Expr) -> bool {
+        let mut fits = true;
+        expr.walk(&mut |expr| {
+            let Expr::If {
+                span,
+                scope,
+                else_ifs,
+                els,
+                ..
+            } = expr
+            else {
+                return;
+            };
+
+            let only_value = |block: &[Stmt]| match block {
+                [Stmt::Expr(value)] => self.fits_on_line(value),
+                _ => false,
+            };
+            fits &= !self.has_comment(*span)
+                && only_value(scope)
+                && else_ifs.iter().all(|else_if| only_value(&else_if.scope))
+                && only_value(els);
+        });
+        fits
+    }
+
+    /// Writes an IF expression block that is only its value, like `{ a }`.
+    fn inline_block(&mut self, scope: &[Stmt]) {
+        if let [Stmt::Expr(value)] = scope {
+            self.out.push_str(" { ");
+            self.expr(value);
+            self.out.push_str(" }");
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        // Deeper expressions never come from the parser, so they are written
+        // as they are.
+        if self.depth == MAX_NESTING {
+            self.out.push_str(self.text(expr.span()));
+            return;
+        }
+        self.depth += 1;
+        self.write_expr(expr);
+        self.depth -= 1;
+    }
+
+    fn write_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Void => {}
+            Expr::True { .. } => self.out.push_str("TRUE"),
+            Expr::False { .. } => self.out.push_str("FALSE"),
+            &Expr::Identifier { span }
+            | &Expr::StringLiteral { span, .. }
+            | &Expr::IntegerLiteral { span, .. }
+            | &Expr::FloatLiteral { span, .. }
+            | &Expr::BinaryLiteral { span, .. }
+            | &Expr::HexLiteral { span, .. } => self.out.push_str(self.text(span)),
+            Expr::ArrayLiteral { values, .. } => {
+                self.out.push('[');
+                self.exprs(values);
+                self.out.push(']');
+            }
+            Expr::MapLiteral { entries, .. } => {
+                self.out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key);
+                    self.out.push_str(": ");
+                    self.expr(value);
+                }
+                self.out.push('}');
+            }
+            Expr::Index { value, index, .. } => {
+                self.expr(value);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            Expr::FnCall { calle, args, .. } => {
+                self.expr(calle);
+                self.out.push('(');
+                self.exprs(args);
+                self.out.push(')');
+            }
+            Expr::UnaryOp { kind, value, .. } => {
+                self.out.push_str(match kind {
+                    UnaryOpKind::Pos => "+",
+                    UnaryOpKind::Neg => "-",
+                    UnaryOpKind::Not => "NOT ",
+                });
+                // `- -x` rather than `--x`.
+                if let Expr::UnaryOp {
+                    kind: UnaryOpKind::Pos | UnaryOpKind::Neg,
+                    ..
+                } = **value
+                {
+                    if !matches!(kind, UnaryOpKind::Not) {
+                        self.out.push(' ');
+                    }
+                }
+                self.expr(value);
+            }
+            Expr::BinaryOp { kind, lhs, rhs } => {
+                self.expr(lhs);
+                self.out.push_str(match kind {
+                    BinaryOpKind::Add => " + ",
+                    BinaryOpKind::Sub => " - ",
+                    BinaryOpKind::Mul => " * ",
+                    BinaryOpKind::Div => " / ",
+                    BinaryOpKind::Mod => " % ",
+                    BinaryOpKind::Equal => " = ",
+                    BinaryOpKind::NotEqual => " != ",
+                    BinaryOpKind::Less => " < ",
+                    BinaryOpKind::LessEqual => " <= ",
+                    BinaryOpKind::Greater => " > ",
+                    BinaryOpKind::GreaterEqual => " >= ",
+                    BinaryOpKind::And => " AND ",
+                    BinaryOpKind::Or => " OR ",
+                });
+                self.expr(rhs);
+            }
+            Expr::Paren { value, .. } => {
+                self.out.push('(');
+                self.expr(value);
+                self.out.push(')');
+            }
+            Expr::If {
+                cond,
+                scope,
+                else_ifs,
+                els,
+                ..
+            } if self.fits_on_line(expr) => {
+                self.out.push_str("IF (");
+                self.expr(cond);
+                self.out.push(')');
+                self.inline_block(scope);
+                for else_if in else_ifs.iter() {
+                    self.out.push_str(" ELSE IF (");
+                    self.expr(&else_if.cond);
+                    self.out.push(')');
+                    self.inline_block(&else_if.scope);
+                }
+                self.out.push_str(" ELSE");
+                self.inline_block(els);
+                self.pos = self.pos.max(expr.span().end);
+            }
+            Expr::If {
+                cond,
+                scope,
+                else_ifs,
+                els,
+                ..
+            } => self.if_blocks(cond, scope, else_ifs, Some(els)),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Loading the files that a program brings in with `IMPORT "file.aps"`.
+//!
+//! Spans are byte offsets into a single source, so each imported file is added
+//! to the end of the program's source and parsed there, the way
+//! [`Session`](crate::engine::Session) parses fragments. [`Program::relocate`]
+//! then points diagnostics back at the file they are in.
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use crate::{
+    ast::{Span, Stmt},
+    encoding,
+    engine::ParseOptions,
+    parser::Parser,
+    permissions::Permissions,
+    vm::Value,
+};
+
+/// A program along with every file it imports.
+pub struct Program {
+    /// The text of the program followed by that of each imported file.
+    pub source: String,
+    /// The program and then the files it imports, in the order their text is
+    /// in `source`.
+    pub files: Vec<SourceFile>,
+    /// The program's statements, with the statements of each IMPORT filled in.
+    pub scope: Box<[Stmt]>,
+    /// Why the program or a file it imports could not be loaded, with spans in
+    /// `source`.
+    pub diagnostics: Vec<Diagnostic<usize>>,
+}
+
+pub struct SourceFile {
+    pub name: String,
+    /// Where the file's text is in [`Program::source`].
+    pub range: Range<usize>,
+}
+
+impl Program {
+    /// The file that the byte `offset` of [`Program::source`] is in, along
+    /// with the file's index and the offset within the file.
+    pub fn locate(&self, offset: usize) -> (usize, &SourceFile, usize) {
+        let (fid, file) = self
+            .files
+            .iter()
+            .enumerate()
+            .find(|(_, file)| offset <= file.range.end)
+            .unwrap_or((0, &self.files[0]));
+        (fid, file, offset.saturating_sub(file.range.start))
+    }
+
+    /// Points the labels of `diagnostics`, which have spans in
+    /// [`Program::source`], at the file they are in. Each file's id is its
+    /// index in [`Program::files`].
+    pub fn relocate(&self, diagnostics: &mut [Diagnostic<usize>]) {
+        for label in diagnostics.iter_mut().flat_map(|d| d.labels.iter_mut()) {
+            (label.file_id, label.range) = self.relocate_range(label.range.clone());
+        }
+    }
+
+    /// The index in [`Program::files`] of the file that `range`, a range of
+    /// [`Program::source`], is in, along with the range within that file.
+    pub fn relocate_range(&self, range: Range<usize>) -> (usize, Range<usize>) {
+        let (fid, file, start) = self.locate(range.start);
+        let end = range.end.min(file.range.end) - file.range.start;
+        (fid, start..end.max(start))
+    }
+}
+
+/// Loads the files imported by a program, read from `path`, which has the text
+/// `source` and was parsed by [`parse_with`](crate::engine::parse_with) into
+/// `parsed`. Paths in IMPORTs are relative to the file they are written in.
+///
+/// Files in the program's directory, including its subdirectories, can always
+/// be imported. Others must be in one of the [`Permissions::read`]
+/// directories.
+pub fn load(
+    path: &Path,
+    source: &str,
+    parsed: Result<Box<[Stmt]>, Vec<Diagnostic<usize>>>,
+    options: ParseOptions,
+    permissions: &Permissions,
+) -> Program {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut permissions = permissions.clone();
+    permissions.read.push(match dir.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => dir.to_path_buf(),
+    });
+
+    let mut loader = Loader {
+        program: Program {
+            source: format!("{source}\n"),
+            files: vec![SourceFile {
+                name: path.display().to_string(),
+                range: 0..source.len(),
+            }],
+            scope: Box::new([]),
+            diagnostics: Vec::new(),
+        },
+        options,
+        permissions,
+        loading: path.canonicalize().into_iter().collect(),
+        loaded: HashMap::new(),
+    };
+
+    match parsed {
+        Ok(mut scope) => {
+            loader.resolve(&mut scope, dir);
+            loader.program.scope = scope;
+        }
+        Err(diagnostics) => loader.program.diagnostics = diagnostics,
+    }
+
+    loader.program
+}
+
+struct Loader {
+    program: Program,
+    options: ParseOptions,
+    /// What may be imported, which includes the program's directory.
+    permissions: Permissions,
+    /// The files being loaded, each imported by the one before it.
+    loading: Vec<PathBuf>,
+    /// The statements of each file loaded so far, so that a file imported
+    /// more than once is only read once.
+    loaded: HashMap<PathBuf, Box<[Stmt]>>,
+}
+
+impl Loader {
+    /// Loads each IMPORT in `scope`, whose paths are relative to `dir`.
+    fn resolve(&mut self, scope: &mut [Stmt], dir: &Path) {
+        for stmt in scope {
+            match stmt {
+                Stmt::Import { span, path, scope } => *scope = self.import(dir, path, *span),
+                Stmt::If {
+                    scope,
+                    else_ifs,
+                    els,
+                    ..
+                } => {
+                    self.resolve(scope, dir);
+                    for else_if in else_ifs.iter_mut() {
+                        self.resolve(&mut else_if.scope, dir);
+                    }
+                    if let Some(els) = els {
+                        self.resolve(els, dir);
+                    }
+                }
+                Stmt::RepeatN { scope, .. }
+                | Stmt::RepeatUntil { scope, .. }
+                | Stmt::For { scope, .. } => self.resolve(scope, dir),
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads and parses the file at `path`, written in the IMPORT at `span`,
+    /// along with the files it imports.
+    fn import(&mut self, dir: &Path, path: &str, span: Span) -> Option<Box<[Stmt]>> {
+        let file = dir.join(path);
+        let canonical = match file.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                self.error(format!("cannot read \"{path}\": {e}"), span, Vec::new());
+                return None;
+            }
+        };
+
+        if let Value::Exception(e) = &self.permissions.check_read(&canonical) {
+            self.error(
+                e.message.clone(),
+                span,
+                vec!["files outside the program's directory can only be imported with `aps run --allow-read`".into()],
+            );
+            return None;
+        }
+
+        if let Some(idx) = self.loading.iter().position(|file| *file == canonical) {
+            let cycle = self.loading[idx..]
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.file_name().unwrap_or_default().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" \u{2192} ");
+            self.error(
+                format!("\"{path}\" is already being imported"),
+                span,
+                vec![format!("the files import each other in a cycle: {cycle}")],
+            );
+            return None;
+        }
+
+        if let Some(scope) = self.loaded.get(&canonical) {
+            return Some(scope.clone());
+        }
+
+        let bytes = match std::fs::read(&file) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.error(format!("cannot read \"{path}\": {e}"), span, Vec::new());
+                return None;
+            }
+        };
+        let decoded = encoding::decode(&bytes);
+
+        let start = self.program.source.len();
+        self.program.source.push_str(&decoded.text);
+        let end = self.program.source.len();
+        self.program.files.push(SourceFile {
+            name: file.display().to_string(),
+            range: start..end,
+        });
+        self.program.source.push('\n');
+
+        // The file's own errors point into its text, which
+        // [`Program::relocate`] turns into spans in the file.
+        if !decoded.invalid.is_empty() {
+            let mut diagnostics = decoded.diagnostics(0);
+            for label in diagnostics.iter_mut().flat_map(|d| d.labels.iter_mut()) {
+                label.range = label.range.start + start..label.range.end + start;
+            }
+            self.program.diagnostics.extend(diagnostics);
+            return None;
+        }
+
+        // The end of the file is where it ends, not after the newline that
+        // separates it from the next.
+        let mut parser = Parser::new(0, &self.program.source.as_bytes()[..end]);
+        parser.dialect = self.options.dialect;
+        parser.lex.index = start;
+        let scope = parser.parse_program();
+        self.program.diagnostics.append(&mut parser.diagnostics);
+        let mut scope = scope.ok()?;
+
+        self.loading.push(canonical.clone());
+        self.resolve(&mut scope, file.parent().unwrap_or(Path::new("")));
+        self.loading.pop();
+
+        self.loaded.insert(canonical, scope.clone());
+        Some(scope)
+    }
+
+    fn error(&mut self, message: String, span: Span, notes: Vec<String>) {
+        self.program.diagnostics.push(
+            Diagnostic::error()
+                .with_message(message)
+                .with_labels(vec![Label::primary(0, span)])
+                .with_notes(notes),
+        );
+    }
+}
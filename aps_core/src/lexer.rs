@@ -1,3 +1,5 @@
+use alloc::string::String;
+
 use phf::phf_map;
 
 use crate::ast::Span;
@@ -21,6 +23,7 @@ pub enum Token {
     LeftBrace,
     RightBrace,
     Comma,
+    Colon,
     Add,
     Sub,
     Mul,
@@ -54,6 +57,7 @@ impl AsRef<str> for Token {
             Self::LeftBrace => "`{`",
             Self::RightBrace => "`}`",
             Self::Comma => "`,`",
+            Self::Colon => "`:`",
             Self::Add => "`+`",
             Self::Sub => "`-`",
             Self::Mul => "`*`",
@@ -81,6 +85,7 @@ impl AsRef<str> for Token {
             Self::Keyword(Keyword::Each) => "`EACH`",
             Self::Keyword(Keyword::In) => "`IN`",
             Self::Keyword(Keyword::Procedure) => "`PROCEDURE`",
+            Self::Keyword(Keyword::Import) => "`IMPORT`",
         }
     }
 }
@@ -119,6 +124,7 @@ pub enum Keyword {
     Each,
     In,
     Procedure,
+    Import,
 }
 
 pub static KEYWORDS: phf::Map<&'static str, Token> = phf_map! {
@@ -137,6 +143,7 @@ pub static KEYWORDS: phf::Map<&'static str, Token> = phf_map! {
     "EACH" => Token::Keyword(Keyword::Each),
     "IN" => Token::Keyword(Keyword::In),
     "PROCEDURE" => Token::Keyword(Keyword::Procedure),
+    "IMPORT" => Token::Keyword(Keyword::Import),
 };
 
 #[derive(Debug)]
@@ -185,8 +192,8 @@ impl<'a> Lexer<'a> {
         loop {
             self.index += 1;
             let Some(b'0'..=b'9') = self.buffer.get(self.index) else {
-				break;
-			};
+                break;
+            };
         }
         Token::FloatLiteral
     }
@@ -202,8 +209,8 @@ impl<'a> Lexer<'a> {
                     loop {
                         self.index += 1;
                         let Some(b' ' | b'\t' | b'\r' | b'\n') = self.buffer.get(self.index) else {
-							break
-						};
+                            break;
+                        };
                     }
                     continue 'main;
                 }
@@ -222,8 +229,8 @@ impl<'a> Lexer<'a> {
                     loop {
                         self.index += 1;
                         let Some(b' ' | b'\t' | b'\r' | b'\n') = self.buffer.get(self.index) else {
-							break
-						};
+                            break;
+                        };
                     }
 
                     continue 'main;
@@ -268,6 +275,10 @@ impl<'a> Lexer<'a> {
                     self.index += 1;
                     self.token = Token::Comma;
                 }
+                Some(b':') => {
+                    self.index += 1;
+                    self.token = Token::Colon;
+                }
                 Some(b'+') => {
                     self.index += 1;
                     self.token = Token::Add;
@@ -327,16 +338,18 @@ impl<'a> Lexer<'a> {
                 Some(b'a'..=b'z' | b'A'..=b'Z' | b'_') => {
                     loop {
                         self.index += 1;
-                        let Some(b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_') = self.buffer.get(self.index) else {
-							break;
-						};
+                        let Some(b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_') =
+                            self.buffer.get(self.index)
+                        else {
+                            break;
+                        };
                     }
 
-                    self.token = *KEYWORDS
-                        .get(unsafe {
-                            std::str::from_utf8_unchecked(&self.buffer[self.start..self.index])
-                        })
-                        .unwrap_or(&Token::Identifier);
+                    self.token = core::str::from_utf8(&self.buffer[self.start..self.index])
+                        .ok()
+                        .and_then(|word| KEYWORDS.get(word))
+                        .copied()
+                        .unwrap_or(Token::Identifier);
                 }
                 Some(b'0') => {
                     self.index += 1;
@@ -344,9 +357,11 @@ impl<'a> Lexer<'a> {
                         Some(b'x' | b'X') => {
                             loop {
                                 self.index += 1;
-                                let Some(b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') = self.buffer.get(self.index) else {
-									break;
-								};
+                                let Some(b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') =
+                                    self.buffer.get(self.index)
+                                else {
+                                    break;
+                                };
                             }
                             Token::HexLiteral
                         }
@@ -354,8 +369,8 @@ impl<'a> Lexer<'a> {
                             loop {
                                 self.index += 1;
                                 let Some(b'0' | b'1') = self.buffer.get(self.index) else {
-									break;
-								};
+                                    break;
+                                };
                             }
                             Token::BinaryLiteral
                         }
@@ -364,28 +379,34 @@ impl<'a> Lexer<'a> {
                         _ => Token::IntegerLiteral,
                     };
                 }
-                Some(b'"') => {
-                    loop {
-                        self.index += 1;
-                        match self.buffer.get(self.index) {
-                            Some(b'"') => {
-                                self.index += 1;
-                                break;
-                            }
-                            None => {
-                                self.token = Token::InvalidStringLiteral;
-                                break;
-                            }
-                            _ => {}
+                Some(b'"') => loop {
+                    self.index += 1;
+                    match self.buffer.get(self.index) {
+                        Some(b'"') => {
+                            self.index += 1;
+                            self.token = Token::StringLiteral;
+                            break;
+                        }
+                        None => {
+                            self.token = Token::InvalidStringLiteral;
+                            break;
                         }
+                        // Skips the escaped character, so `\"` does not end
+                        // the string.
+                        Some(b'\\') if self.index + 1 < self.buffer.len() => self.index += 1,
+                        _ => {}
                     }
-                    self.token = Token::StringLiteral;
-                }
+                },
                 Some(b'1'..=b'9') => self.token = self.integer_continue(),
                 None => self.token = Token::EOF,
                 _ => {
                     self.token = Token::Unknown;
                     self.index += 1;
+
+                    // Keep multi-byte characters whole so spans stay on UTF-8 boundaries.
+                    while let Some(0x80..=0xBF) = self.buffer.get(self.index) {
+                        self.index += 1;
+                    }
                 }
             }
 
@@ -393,3 +414,49 @@ impl<'a> Lexer<'a> {
         }
     }
 }
+
+/// The text of a string literal with its escape sequences (`\n`, `\t`, `\\`,
+/// `\"`, and `\u{...}` with a character's code in hex) replaced by the
+/// characters they stand for. `raw` is the text between the quotes. Fails with
+/// the byte range in `raw` of the first invalid escape.
+pub fn unescape(raw: &str) -> Result<String, core::ops::Range<usize>> {
+    let mut out = String::with_capacity(raw.len());
+    let mut pos = 0;
+
+    while let Some(found) = raw[pos..].find('\\') {
+        let start = pos + found;
+        out.push_str(&raw[pos..start]);
+
+        let after = &raw[start + 1..];
+        let (c, len) = match after.chars().next() {
+            Some('n') => ('\n', 1),
+            Some('t') => ('\t', 1),
+            Some('\\') => ('\\', 1),
+            Some('"') => ('"', 1),
+            Some('u') => {
+                let hex = after[1..]
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(hex, _)| hex);
+                let len = hex.map_or(1, |hex| hex.len() + 3);
+                let c = hex
+                    .filter(|hex| (1..=6).contains(&hex.len()))
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32);
+                match c {
+                    Some(c) => (c, len),
+                    None => return Err(start..start + 1 + len),
+                }
+            }
+            Some(c) => return Err(start..start + 1 + c.len_utf8()),
+            None => return Err(start..start + 1),
+        };
+
+        out.push(c);
+        pos = start + 1 + len;
+    }
+
+    out.push_str(&raw[pos..]);
+    Ok(out)
+}
@@ -1,9 +1,41 @@
+// `gc_derive` predates the `non_local_definitions` lint and the parser reports
+// errors through `diagnostics` rather than the `Err` payload.
+#![allow(non_local_definitions, clippy::result_unit_err)]
+// Without `std`, only the lexer, AST, and parser are built, using `alloc`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
 pub mod ast;
+#[cfg(feature = "std")]
+pub mod bytecode;
+pub mod diagnostic;
+pub mod doc;
+pub mod edit;
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod engine;
+pub mod fmt;
+#[cfg(feature = "std")]
+pub mod imports;
 pub mod lexer;
+#[cfg(feature = "std")]
+pub mod lint;
+pub mod locale;
+pub mod metrics;
 pub mod parser;
+#[cfg(feature = "std")]
+pub mod permissions;
+#[cfg(feature = "std")]
 pub mod stdlib;
+#[cfg(feature = "std")]
 pub mod vm;
 
+#[cfg(feature = "std")]
+pub use engine::{run_str, Engine, RunOutcome};
+
 #[cfg(feature = "js")]
 mod wasm;
 
@@ -22,20 +54,52 @@ macro_rules! tee {
     }};
 }
 
+/// Enters a `tracing` span at the debug level until the end of the enclosing
+/// block when the `trace` feature is enabled, and does nothing otherwise.
+#[cfg(feature = "std")]
+macro_rules! trace_span {
+    ($($arg: tt)*) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+#[cfg(feature = "std")]
+pub(crate) use trace_span;
+
+/// Returns an exception with the message, where it happened (or `BUILTIN` in
+/// builtins, whose call site is filled in by the VM, or `ARG n` for the
+/// builtin's argument at index `n`), and optionally a hint for fixing it.
 #[macro_export]
 macro_rules! fail {
-    ($msg: expr, BUILTIN) => {{
-        return Value::Exception(Box::new(crate::vm::Exception {
+    ($msg: expr, ARG $arg: expr) => {
+        $crate::fail!($msg, ARG $arg, None)
+    };
+    ($msg: expr, ARG $arg: expr, $help: expr) => {{
+        return Value::Exception(Box::new($crate::vm::Exception {
             message: $msg.into(),
-            span: crate::ast::Span { start: 0, end: 0 },
+            span: $crate::ast::Span { start: 0, end: 0 },
             stack: Vec::new(),
+            help: $help,
+            arg: Some($arg),
         }));
     }};
-    ($msg: expr, $span: expr) => {{
-        return Value::Exception(Box::new(crate::vm::Exception {
+    ($msg: expr, BUILTIN) => {
+        $crate::fail!($msg, $crate::ast::Span { start: 0, end: 0 })
+    };
+    ($msg: expr, BUILTIN, $help: expr) => {
+        $crate::fail!($msg, $crate::ast::Span { start: 0, end: 0 }, $help)
+    };
+    ($msg: expr, $span: expr) => {
+        $crate::fail!($msg, $span, None)
+    };
+    ($msg: expr, $span: expr, $help: expr) => {{
+        return Value::Exception(Box::new($crate::vm::Exception {
             message: $msg.into(),
             span: $span,
             stack: Vec::new(),
+            help: $help,
+            arg: None,
         }));
     }};
 }
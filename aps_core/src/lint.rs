@@ -0,0 +1,451 @@
+//! Style checks that flag legal but discouraged code. Each [`Lint`] carries a
+//! rule ID so tools can report and filter them individually.
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use crate::{
+    ast::{BinaryOpKind, Expr, Node, Span, Stmt},
+    edit::{Edit, Fix},
+    lexer::{Lexer, Token, KEYWORDS},
+    stdlib::DEFAULT_MODULES,
+};
+
+/// Keywords should be written in uppercase (only checked in strict mode).
+pub const KEYWORD_CASE: &str = "keyword-case";
+/// A `REPEAT UNTIL` loop that only counts iterations.
+pub const PREFER_REPEAT_TIMES: &str = "prefer-repeat-times";
+/// A variable, parameter, or procedure named like a builtin.
+pub const BUILTIN_SHADOW: &str = "builtin-shadow";
+/// Parentheses that do not change how an expression is grouped.
+pub const REDUNDANT_PARENS: &str = "redundant-parens";
+/// A `REPEAT UNTIL` loop whose condition can never become true.
+pub const INFINITE_LOOP: &str = "infinite-loop";
+/// A statement like `x = 1`, which compares instead of assigning. Reported by
+/// the parser rather than the linter.
+pub use crate::parser::ASSIGN_WITH_EQUAL;
+/// A comparison like `1 < x < 10`. Reported by the parser rather than the
+/// linter.
+pub use crate::parser::CHAINED_COMPARISON;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Enables rules that only matter to stricter style guides.
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub rule: &'static str,
+    pub span: Span,
+    pub message: String,
+    /// How to resolve the lint, if it can be fixed mechanically.
+    pub fix: Option<Fix>,
+}
+
+impl Lint {
+    pub fn to_diagnostic(&self, file_id: usize) -> Diagnostic<usize> {
+        Diagnostic::warning()
+            .with_message(&self.message)
+            .with_code(self.rule)
+            .with_labels(vec![Label::primary(file_id, self.span)])
+    }
+}
+
+/// Checks `source` for style issues. `program` is the parsed form of `source`
+/// and may be omitted when it failed to parse, in which case only the
+/// token-based rules run.
+pub fn lint(source: &str, program: Option<&[Stmt]>, options: &Options) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    if options.strict {
+        keyword_case(source, &mut lints);
+    }
+
+    if let Some(program) = program {
+        let mut linter = Linter {
+            source,
+            lints: &mut lints,
+        };
+        for stmt in program {
+            stmt.walk(&mut |stmt| linter.stmt(stmt));
+        }
+    }
+
+    lints.sort_by_key(|lint| lint.span.start);
+    lints
+}
+
+fn keyword_case(source: &str, lints: &mut Vec<Lint>) {
+    let mut lex = Lexer::new(source.as_bytes());
+    lex.next();
+
+    while lex.token != Token::EOF {
+        if lex.token == Token::Identifier {
+            let text = &source[lex.start..lex.index];
+            let upper = text.to_ascii_uppercase();
+            if KEYWORDS.contains_key(upper.as_str()) {
+                lints.push(Lint {
+                    rule: KEYWORD_CASE,
+                    span: lex.span(),
+                    message: format!("keyword `{upper}` should be written in uppercase"),
+                    fix: Some(Fix {
+                        title: format!("Change to `{upper}`"),
+                        edits: vec![Edit::replace(lex.span(), upper)],
+                    }),
+                });
+            }
+        }
+        lex.next();
+    }
+}
+
+/// Builtins that never modify their arguments.
+const READS_ONLY: &[&str] = &[
+    "DISPLAY",
+    "LENGTH",
+    "SUBSTRING",
+    "CONCAT",
+    "PEEK",
+    "SET_HAS",
+    "KEYS",
+    "VALUES",
+    "CONTAINS_KEY",
+    "ROWS",
+    "COLS",
+    "GET_CELL",
+];
+
+fn is_builtin(name: &str) -> bool {
+    DEFAULT_MODULES
+        .iter()
+        .flat_map(|module| module.builtins)
+        .any(|(builtin, _)| *builtin == name)
+}
+
+fn is_atom(expr: &Expr) -> bool {
+    !matches!(expr, Expr::UnaryOp { .. } | Expr::BinaryOp { .. })
+}
+
+struct Linter<'a> {
+    source: &'a str,
+    lints: &'a mut Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn text(&self, span: Span) -> &str {
+        &self.source[span.start as usize..span.end as usize]
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let exprs = match stmt {
+            // `RETURN(x)` is the conventional spelling, so its parentheses stay.
+            Stmt::Return {
+                value: Expr::Paren { value, .. },
+                ..
+            } => vec![value.as_ref()],
+            _ => stmt.exprs(),
+        };
+        for expr in exprs {
+            self.redundant_parens(expr);
+            expr.walk(&mut |expr| self.nested_parens(expr));
+        }
+
+        match stmt {
+            Stmt::VarAssign { name, .. } => self.builtin_shadow(*name, "variable"),
+            Stmt::Destructure { names, .. } => {
+                for &name in names.iter() {
+                    self.builtin_shadow(name, "variable");
+                }
+            }
+            Stmt::Procedure(proc) => {
+                self.builtin_shadow(proc.name, "procedure");
+                for &param in proc.params.iter() {
+                    self.builtin_shadow(param, "parameter");
+                }
+            }
+            Stmt::For { alias, index, .. } => {
+                self.builtin_shadow(*alias, "variable");
+                if let Some(index) = index {
+                    self.builtin_shadow(*index, "variable");
+                }
+            }
+            Stmt::RepeatUntil { cond, scope } => {
+                self.repeat_until(cond, scope);
+                self.infinite_loop(cond, scope);
+            }
+            _ => {}
+        }
+    }
+
+    /// Flags parentheses in positions that are already delimited, such as
+    /// call arguments, list items, and indices.
+    fn nested_parens(&mut self, expr: &Expr) {
+        match expr {
+            Expr::FnCall { args: exprs, .. } | Expr::ArrayLiteral { values: exprs, .. } => {
+                exprs.iter().for_each(|expr| self.redundant_parens(expr))
+            }
+            Expr::Index { index: expr, .. } => self.redundant_parens(expr),
+            Expr::Paren { value, .. } if matches!(**value, Expr::Paren { .. }) => {
+                self.redundant_parens(value)
+            }
+            Expr::Paren { span, value } if is_atom(value) => self.push_redundant_parens(*span),
+            _ => {}
+        }
+    }
+
+    fn redundant_parens(&mut self, expr: &Expr) {
+        if let &Expr::Paren { span, .. } = expr {
+            self.push_redundant_parens(span);
+        }
+    }
+
+    fn push_redundant_parens(&mut self, span: Span) {
+        if self
+            .lints
+            .iter()
+            .any(|lint| lint.rule == REDUNDANT_PARENS && lint.span == span)
+        {
+            return;
+        }
+
+        // Keep a space where removing a parenthesis would join two words.
+        let is_word = |idx: u32| {
+            self.source
+                .as_bytes()
+                .get(idx as usize)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+        };
+        let open = match span.start > 0 && is_word(span.start - 1) && is_word(span.start + 1) {
+            true => " ",
+            false => "",
+        };
+        let close = match is_word(span.end - 2) && is_word(span.end) {
+            true => " ",
+            false => "",
+        };
+
+        self.lints.push(Lint {
+            rule: REDUNDANT_PARENS,
+            span,
+            message: "unnecessary parentheses".into(),
+            fix: Some(Fix {
+                title: "Remove parentheses".into(),
+                edits: vec![
+                    Edit::replace(
+                        Span {
+                            start: span.start,
+                            end: span.start + 1,
+                        },
+                        open,
+                    ),
+                    Edit::replace(
+                        Span {
+                            start: span.end - 1,
+                            end: span.end,
+                        },
+                        close,
+                    ),
+                ],
+            }),
+        });
+    }
+
+    fn mentions(&self, expr: &Expr, names: &[&str]) -> bool {
+        let mut found = false;
+        expr.walk(&mut |expr| {
+            if let &Expr::Identifier { span } = expr {
+                found |= names.contains(&self.text(span));
+            }
+        });
+        found
+    }
+
+    /// Flags a `REPEAT UNTIL` loop that nothing can stop: its condition is
+    /// `FALSE` or nothing in its body changes the variables the condition
+    /// reads, and the body never RETURNs. Calls to PROCEDUREs, which could
+    /// change globals, and builtins that may modify one of the variables
+    /// count as changes.
+    fn infinite_loop(&mut self, cond: &Expr, scope: &[Stmt]) {
+        let mut names = Vec::new();
+        let mut calls = false;
+        cond.walk(&mut |expr| match expr {
+            Expr::FnCall { .. } => calls = true,
+            &Expr::Identifier { span } if !names.contains(&self.text(span)) => {
+                names.push(self.text(span))
+            }
+            _ => {}
+        });
+
+        // Calls like `INPUT()` in the condition can make it true by themselves.
+        if calls || (names.is_empty() && !matches!(cond, Expr::False { .. })) {
+            return;
+        }
+
+        let mut stops = false;
+        for stmt in scope {
+            stmt.walk(&mut |stmt| {
+                stops |= match stmt {
+                    Stmt::Return { .. } | Stmt::Custom { .. } => true,
+                    Stmt::VarAssign { name, .. } => names.contains(&self.text(*name)),
+                    Stmt::Destructure {
+                        names: assigned, ..
+                    } => assigned
+                        .iter()
+                        .any(|&name| names.contains(&self.text(name))),
+                    Stmt::For { alias, index, .. } => [*alias]
+                        .into_iter()
+                        .chain(*index)
+                        .any(|name| names.contains(&self.text(name))),
+                    Stmt::IndexAssign { root, .. } => self.mentions(root, &names),
+                    _ => false,
+                };
+
+                for expr in stmt.exprs() {
+                    expr.walk(&mut |expr| {
+                        let Expr::FnCall { calle, args, .. } = expr else {
+                            return;
+                        };
+                        let builtin = match **calle {
+                            Expr::Identifier { span } => is_builtin(self.text(span)),
+                            _ => false,
+                        };
+                        let reads_only = match **calle {
+                            Expr::Identifier { span } => READS_ONLY.contains(&self.text(span)),
+                            _ => false,
+                        };
+                        stops |= !builtin
+                            || (!reads_only && args.iter().any(|arg| self.mentions(arg, &names)));
+                    });
+                }
+            });
+        }
+
+        if stops {
+            return;
+        }
+
+        let message = match names.as_slice() {
+            [] => "this loop never ends because its condition is always false".into(),
+            names => format!(
+                "this loop never ends because nothing in it changes {}",
+                names
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ),
+        };
+        self.lints.push(Lint {
+            rule: INFINITE_LOOP,
+            span: cond.span(),
+            message,
+            fix: None,
+        });
+    }
+
+    fn builtin_shadow(&mut self, span: Span, what: &str) {
+        let name = self.text(span);
+        let builtin = DEFAULT_MODULES
+            .iter()
+            .flat_map(|module| module.builtins)
+            .find(|(builtin, _)| builtin.eq_ignore_ascii_case(name));
+
+        if let Some((builtin, _)) = builtin {
+            self.lints.push(Lint {
+                rule: BUILTIN_SHADOW,
+                span,
+                message: format!("{what} `{name}` shadows the builtin `{builtin}`"),
+                fix: None,
+            });
+        }
+    }
+
+    /// Flags `REPEAT UNTIL (i = n)` loops whose counter is only ever used to
+    /// count, i.e. the body's one reference to it is `i <- i + 1`.
+    fn repeat_until(&mut self, cond: &Expr, scope: &[Stmt]) {
+        let mut cond = cond;
+        while let Expr::Paren { value, .. } = cond {
+            cond = value;
+        }
+
+        let Expr::BinaryOp {
+            kind:
+                BinaryOpKind::Equal
+                | BinaryOpKind::Greater
+                | BinaryOpKind::GreaterEqual
+                | BinaryOpKind::Less
+                | BinaryOpKind::LessEqual,
+            lhs,
+            rhs,
+        } = cond
+        else {
+            return;
+        };
+
+        let Some(counter) = [lhs, rhs].into_iter().find_map(|side| match **side {
+            Expr::Identifier { span } => Some(self.text(span)),
+            _ => None,
+        }) else {
+            return;
+        };
+
+        let mut increments = 0;
+        let mut other_uses = 0;
+        for stmt in scope {
+            stmt.walk(&mut |stmt| {
+                if self.is_increment(stmt, counter) {
+                    increments += 1;
+                    return;
+                }
+
+                if let Stmt::VarAssign { name, .. } = stmt {
+                    if self.text(*name) == counter {
+                        other_uses += 1;
+                    }
+                }
+
+                for expr in stmt.exprs() {
+                    expr.walk(&mut |expr| {
+                        if let Expr::Identifier { span } = expr {
+                            if self.text(*span) == counter {
+                                other_uses += 1;
+                            }
+                        }
+                    });
+                }
+            });
+        }
+
+        if increments == 1 && other_uses == 0 {
+            self.lints.push(Lint {
+                rule: PREFER_REPEAT_TIMES,
+                span: cond.span(),
+                message: format!("`{counter}` only counts iterations; prefer `REPEAT n TIMES`"),
+                fix: None,
+            });
+        }
+    }
+
+    fn is_increment(&self, stmt: &Stmt, counter: &str) -> bool {
+        let Stmt::VarAssign {
+            name,
+            value:
+                Expr::BinaryOp {
+                    kind: BinaryOpKind::Add,
+                    lhs,
+                    rhs,
+                },
+        } = stmt
+        else {
+            return false;
+        };
+
+        let is_counter =
+            |expr: &Expr| matches!(expr, &Expr::Identifier { span } if self.text(span) == counter);
+        let is_one =
+            |expr: &Expr| matches!(expr, &Expr::IntegerLiteral { value, .. } if value == 1.);
+
+        self.text(*name) == counter
+            && ((is_counter(lhs) && is_one(rhs)) || (is_one(lhs) && is_counter(rhs)))
+    }
+}
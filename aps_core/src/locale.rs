@@ -0,0 +1,704 @@
+//! Translations of diagnostic and exception messages.
+//!
+//! Messages are written in English where they are raised. [`translate`] looks
+//! a message up in the catalog of a language by matching it against English
+//! templates, where `{}` stands for text such as a name or a value. That text
+//! is carried over into the translation, itself translated if it is one of a
+//! few common words like a token name.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::diagnostic::Diagnostic;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+}
+
+impl Lang {
+    /// Parses a language tag like `es`, `es-MX`, or a locale like
+    /// `es_MX.UTF-8`. Returns `None` for languages without a catalog.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let code = tag.split(['-', '_', '.']).next()?;
+        if code.eq_ignore_ascii_case("en") || code == "C" || code == "POSIX" {
+            Some(Self::En)
+        } else if code.eq_ignore_ascii_case("es") {
+            Some(Self::Es)
+        } else {
+            None
+        }
+    }
+
+    /// Pairs of English templates and their translations, most specific
+    /// first.
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => &[],
+            Self::Es => ES,
+        }
+    }
+
+    /// Translations of words that fill in templates.
+    fn terms(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => &[],
+            Self::Es => ES_TERMS,
+        }
+    }
+}
+
+/// Matches `message` against `template`, returning the text in place of each
+/// `{}`. Placeholders never match empty text.
+fn captures<'m>(template: &str, message: &'m str) -> Option<Vec<&'m str>> {
+    let mut parts = template.split("{}");
+    let mut rest = message.strip_prefix(parts.next()?)?;
+    let parts = parts.collect::<Vec<_>>();
+    let mut args = Vec::with_capacity(parts.len());
+
+    for (idx, part) in parts.iter().enumerate() {
+        let end = if idx + 1 == parts.len() {
+            rest.strip_suffix(part)?.len()
+        } else {
+            rest.find(part)?
+        };
+        if end == 0 {
+            return None;
+        }
+        args.push(&rest[..end]);
+        rest = &rest[end + part.len()..];
+    }
+
+    rest.is_empty().then_some(args)
+}
+
+/// Translates `message` into `lang`, or returns it as it is if the catalog
+/// has no translation for it.
+pub fn translate(lang: Lang, message: &str) -> String {
+    for (template, translation) in lang.catalog() {
+        let Some(args) = captures(template, message) else {
+            continue;
+        };
+
+        let mut out = String::new();
+        let mut pieces = translation.split("{}");
+        out.push_str(pieces.next().unwrap_or_default());
+        for (arg, piece) in args.into_iter().zip(pieces) {
+            match lang.terms().iter().find(|(term, _)| *term == arg) {
+                Some((_, term)) => out.push_str(term),
+                None => out.push_str(arg),
+            }
+            out.push_str(piece);
+        }
+        return out;
+    }
+
+    message.into()
+}
+
+/// Translates the messages, label messages, and notes of `diagnostics`.
+pub fn localize<FileId>(lang: Lang, diagnostics: &mut [Diagnostic<FileId>]) {
+    if lang == Lang::En {
+        return;
+    }
+
+    for diagnostic in diagnostics {
+        diagnostic.message = translate(lang, &diagnostic.message);
+        for label in &mut diagnostic.labels {
+            label.message = translate(lang, &label.message);
+        }
+        for note in &mut diagnostic.notes {
+            *note = translate(lang, note);
+        }
+    }
+}
+
+const ES: &[(&str, &str)] = &[
+    // Parser
+    (
+        "expected expression, found {}",
+        "se esperaba una expresión, se encontró {}",
+    ),
+    ("expected expression", "se esperaba una expresión"),
+    (
+        "expected an operator, found {}",
+        "se esperaba un operador, se encontró {}",
+    ),
+    ("expected an operator", "se esperaba un operador"),
+    (
+        "expected new line after statement, found {}",
+        "se esperaba un salto de línea después de la instrucción, se encontró {}",
+    ),
+    (
+        "expected new line here",
+        "se esperaba un salto de línea aquí",
+    ),
+    ("main statement here", "instrucción principal aquí"),
+    (
+        "expected end of expression, found {}",
+        "se esperaba el final de la expresión, se encontró {}",
+    ),
+    (
+        "expected `{}`, found {}",
+        "se esperaba `{}`, se encontró {}",
+    ),
+    (
+        "expected identifier, found {}",
+        "se esperaba un identificador, se encontró {}",
+    ),
+    ("expected `{}`", "se esperaba `{}`"),
+    ("expected identifier", "se esperaba un identificador"),
+    (
+        "keywords must be uppercase",
+        "las palabras clave deben estar en mayúsculas",
+    ),
+    ("write this as {}", "escríbelo como {}"),
+    (
+        "{} conditions must be wrapped in parentheses",
+        "las condiciones de {} deben ir entre paréntesis",
+    ),
+    (
+        "add parentheses around this condition",
+        "agrega paréntesis alrededor de esta condición",
+    ),
+    (
+        "`=` compares values; use `<-` to assign",
+        "`=` compara valores; usa `<-` para asignar",
+    ),
+    (
+        "this compares instead of assigning",
+        "esto compara en lugar de asignar",
+    ),
+    (
+        "to assign, write `{} <- {}`",
+        "para asignar, escribe `{} <- {}`",
+    ),
+    (
+        "comparisons cannot be chained",
+        "las comparaciones no se pueden encadenar",
+    ),
+    (
+        "this compares the TRUE or FALSE of one comparison with a number",
+        "esto compara el TRUE o FALSE de una comparación con un número",
+    ),
+    (
+        "to check both, write `{}`",
+        "para comprobar ambas, escribe `{}`",
+    ),
+    ("unclosed `{`", "`{` sin cerrar"),
+    ("this `{` is never closed", "este `{` nunca se cierra"),
+    (
+        "expected `}` before the end of the file",
+        "se esperaba `}` antes del final del archivo",
+    ),
+    (
+        "expression nested too deeply",
+        "expresión anidada a demasiada profundidad",
+    ),
+    ("block nested too deeply", "bloque anidado a demasiada profundidad"),
+    (
+        "this is nested more than {} levels deep",
+        "esto está anidado a más de {} niveles de profundidad",
+    ),
+    (
+        "store parts of it in variables or move them into a PROCEDURE",
+        "guarda partes en variables o muévelas a un PROCEDURE",
+    ),
+    (
+        "PROCEDUREs cannot be outside of the global scope",
+        "los PROCEDURE no pueden estar fuera del ámbito global",
+    ),
+    (
+        "PROCEDURE not in the global scope",
+        "PROCEDURE fuera del ámbito global",
+    ),
+    (
+        "RETURN statements cannot be outside of function scopes",
+        "las instrucciones RETURN no pueden estar fuera de un procedimiento",
+    ),
+    (
+        "RETURN not in function scope",
+        "RETURN fuera de un procedimiento",
+    ),
+    (
+        "RETURN cannot be used inside an IF expression",
+        "RETURN no se puede usar dentro de una expresión IF",
+    ),
+    (
+        "RETURN inside an IF expression",
+        "RETURN dentro de una expresión IF",
+    ),
+    (
+        "the blocks of an IF expression must end with a value",
+        "los bloques de una expresión IF deben terminar con un valor",
+    ),
+    (
+        "expected a value on the last line of this block",
+        "se esperaba un valor en la última línea de este bloque",
+    ),
+    (
+        "IF expressions must have an ELSE block",
+        "las expresiones IF deben tener un bloque ELSE",
+    ),
+    (
+        "this IF has no value when its conditions are FALSE",
+        "este IF no tiene valor cuando sus condiciones son FALSE",
+    ),
+    // Encoding
+    ("file is not valid UTF-8", "el archivo no es UTF-8 válido"),
+    ("invalid UTF-8 at byte {}", "UTF-8 no válido en el byte {}"),
+    (
+        "save the file with UTF-8 encoding",
+        "guarda el archivo con la codificación UTF-8",
+    ),
+    // Lints
+    (
+        "keyword `{}` should be written in uppercase",
+        "la palabra clave `{}` debe escribirse en mayúsculas",
+    ),
+    ("unnecessary parentheses", "paréntesis innecesarios"),
+    (
+        "this loop never ends because its condition is always false",
+        "este bucle nunca termina porque su condición siempre es falsa",
+    ),
+    (
+        "this loop never ends because nothing in it changes {}",
+        "este bucle nunca termina porque nada en él cambia {}",
+    ),
+    (
+        "{} `{}` shadows the builtin `{}`",
+        "{} `{}` oculta la función integrada `{}`",
+    ),
+    (
+        "`{}` only counts iterations; prefer `REPEAT n TIMES`",
+        "`{}` solo cuenta las iteraciones; usa mejor `REPEAT n TIMES`",
+    ),
+    // Runtime
+    ("in PROCEDURE {}, called here", "en el PROCEDURE {}, llamado aquí"),
+    (
+        "in PROCEDURE {}, called here {} times",
+        "en el PROCEDURE {}, llamado aquí {} veces",
+    ),
+    ("maximum recursion depth exceeded", "se superó la profundidad máxima de recursión"),
+    (
+        "expected {} arguments, found {}",
+        "se esperaban {} argumentos, se encontraron {}",
+    ),
+    ("'{}' is not defined", "'{}' no está definido"),
+    (
+        "the program was interrupted",
+        "el programa fue interrumpido",
+    ),
+    (
+        "execution limit exceeded",
+        "se superó el límite de ejecución",
+    ),
+    ("index {} is not an integer", "el índice {} no es un número entero"),
+    ("{} is not a boolean", "{} no es un booleano"),
+    ("{} is not a valid number", "{} no es un número válido"),
+    ("{} is not a finite number", "{} no es un número finito"),
+    ("{} is not a number", "{} no es un número"),
+    ("{} is not an array", "{} no es una lista"),
+    ("{} is not a function", "{} no es una función"),
+    (
+        "{} is not an integer from 0 to 255",
+        "{} no es un número entero del 0 al 255",
+    ),
+    ("{} is not an integer", "{} no es un número entero"),
+    (
+        "{} is not a non-negative integer",
+        "{} no es un número entero no negativo",
+    ),
+    (
+        "{} is not a positive integer",
+        "{} no es un número entero positivo",
+    ),
+    ("{} is not positive", "{} no es positivo"),
+    ("{} is not a color", "{} no es un color"),
+    (
+        "index is out of bounds: the length is {} but the index is {}",
+        "el índice está fuera de rango: la longitud es {} pero el índice es {}",
+    ),
+    (
+        "index {} is out of array range (length: {})",
+        "el índice {} está fuera del rango de la lista (longitud: {})",
+    ),
+    (
+        "cannot assign {} items to {} variables",
+        "no se pueden asignar {} elementos a {} variables",
+    ),
+    (
+        "only variables can be assigned in `[a, b] <- list`",
+        "solo se pueden asignar variables en `[a, b] <- lista`",
+    ),
+    ("expected a variable name", "se esperaba el nombre de una variable"),
+    (
+        "index {} is out of string range (length: {})",
+        "el índice {} está fuera del rango del texto (longitud: {})",
+    ),
+    (
+        "start {} is out of string range (length: {})",
+        "el inicio {} está fuera del rango del texto (longitud: {})",
+    ),
+    (
+        "end {} is out of string range (length: {})",
+        "el final {} está fuera del rango del texto (longitud: {})",
+    ),
+    ("end {} is before start {}", "el final {} está antes del inicio {}"),
+    ("cannot join a string with {}", "no se puede unir un texto con {}"),
+    ("cannot join a list with {}", "no se puede unir una lista con {}"),
+    (
+        "array index is not an integer",
+        "el índice de la lista no es un número entero",
+    ),
+    (
+        "array index out of range",
+        "el índice de la lista está fuera de rango",
+    ),
+    ("unsupported operator", "operador no admitido"),
+    (
+        "expected the file to import in quotes, found {}",
+        "se esperaba el archivo a importar entre comillas, se encontró {}",
+    ),
+    ("expected a string like \"file.aps\"", "se esperaba una cadena como \"file.aps\""),
+    ("IMPORT cannot be inside a PROCEDURE", "IMPORT no puede estar dentro de un PROCEDURE"),
+    ("IMPORT inside a PROCEDURE", "IMPORT dentro de un PROCEDURE"),
+    ("\"{}\" was not loaded", "\"{}\" no se cargó"),
+    ("cannot read \"{}\": {}", "no se puede leer \"{}\": {}"),
+    ("cannot write \"{}\": {}", "no se puede escribir \"{}\": {}"),
+    ("expected a path for the first argument", "se esperaba una ruta como primer argumento"),
+    ("\"{}\" is already being imported", "\"{}\" ya se está importando"),
+    (
+        "files outside the program's directory can only be imported with `aps run --allow-read`",
+        "los archivos fuera de la carpeta del programa solo se pueden importar con `aps run --allow-read`",
+    ),
+    (
+        "the files import each other in a cycle: {}",
+        "los archivos se importan entre sí en un ciclo: {}",
+    ),
+    ("invalid escape sequence `{}`", "secuencia de escape no válida `{}`"),
+    (
+        "the escapes are `\\n`, `\\t`, `\\\\`, `\\\"`, and `\\u{...}` with a character's code in hex",
+        "los escapes son `\\n`, `\\t`, `\\\\`, `\\\"` y `\\u{...}` con el código de un carácter en hexadecimal",
+    ),
+    ("string is missing its closing `\"`", "a la cadena le falta su `\"` de cierre"),
+    ("this string never ends", "esta cadena nunca termina"),
+    (
+        "{} is too large to be stored exactly",
+        "{} es demasiado grande para guardarse con exactitud",
+    ),
+    (
+        "whole numbers are only exact up to {}",
+        "los números enteros solo son exactos hasta {}",
+    ),
+    (
+        "INPUT was called but there are no responses left",
+        "se llamó a INPUT pero no quedan respuestas",
+    ),
+    (
+        "cannot POP from an empty list",
+        "no se puede hacer POP de una lista vacía",
+    ),
+    (
+        "cannot PEEK into an empty list",
+        "no se puede hacer PEEK de una lista vacía",
+    ),
+    (
+        "cannot DEQUEUE from an empty list",
+        "no se puede hacer DEQUEUE de una lista vacía",
+    ),
+    (
+        "cannot CHOICE from an empty list",
+        "no se puede hacer CHOICE de una lista vacía",
+    ),
+    (
+        "cannot take the {} of an empty list",
+        "no se puede calcular {} de una lista vacía",
+    ),
+    (
+        "cannot SAMPLE {} values from a list of {}",
+        "no se pueden tomar {} valores con SAMPLE de una lista de {}",
+    ),
+    (
+        "the range start {} is greater than its end {}",
+        "el inicio del rango {} es mayor que su final {}",
+    ),
+    (
+        "expected a list for the first argument",
+        "se esperaba una lista como primer argumento",
+    ),
+    (
+        "expected array for the first argument",
+        "se esperaba una lista como primer argumento",
+    ),
+    (
+        "expected the first argument to be an array",
+        "se esperaba una lista como primer argumento",
+    ),
+    (
+        "expected the first argument to be an array or a string",
+        "se esperaba una lista o un texto como primer argumento",
+    ),
+    (
+        "expected a string or a list for the first argument",
+        "se esperaba un texto o una lista como primer argumento",
+    ),
+    (
+        "expected a direction for the first argument",
+        "se esperaba una dirección como primer argumento",
+    ),
+    (
+        "expected a layout for the first argument",
+        "se esperaba una cuadrícula como primer argumento",
+    ),
+    (
+        "expected a string for the first argument",
+        "se esperaba una cadena como primer argumento",
+    ),
+    (
+        "expected string for the first argument",
+        "se esperaba una cadena como primer argumento",
+    ),
+    (
+        "expected a string for the second argument",
+        "se esperaba una cadena como segundo argumento",
+    ),
+    (
+        "expected a string for the third argument",
+        "se esperaba una cadena como tercer argumento",
+    ),
+    (
+        "expected string for the third argument",
+        "se esperaba una cadena como tercer argumento",
+    ),
+    (
+        "expected a number for the first argument",
+        "se esperaba un número como primer argumento",
+    ),
+    (
+        "expected number for the first argument",
+        "se esperaba un número como primer argumento",
+    ),
+    (
+        "expected a number for the second argument",
+        "se esperaba un número como segundo argumento",
+    ),
+    (
+        "expected number for the second argument",
+        "se esperaba un número como segundo argumento",
+    ),
+    (
+        "expected a number for the third argument",
+        "se esperaba un número como tercer argumento",
+    ),
+    (
+        "expected number for argument {}",
+        "se esperaba un número como argumento {}",
+    ),
+    (
+        "expected index for the second argument",
+        "se esperaba un índice como segundo argumento",
+    ),
+    (
+        "expected value for the second argument",
+        "se esperaba un valor como segundo argumento",
+    ),
+    (
+        "expected value for the third argument",
+        "se esperaba un valor como tercer argumento",
+    ),
+    (
+        "expected a value for the first argument",
+        "se esperaba un valor como primer argumento",
+    ),
+    (
+        "expected a value for the second argument",
+        "se esperaba un valor como segundo argumento",
+    ),
+    (
+        "expected set for the first argument",
+        "se esperaba un conjunto como primer argumento",
+    ),
+    ("expected two numbers", "se esperaban dos números"),
+    ("the string would be too long", "el texto sería demasiado largo"),
+    (
+        "the least common multiple of {} and {} is too large",
+        "el mínimo común múltiplo de {} y {} es demasiado grande",
+    ),
+    ("expected two sets", "se esperaban dos conjuntos"),
+    (
+        "expected a list of numbers, found {} at index {}",
+        "se esperaba una lista de números, se encontró {} en el índice {}",
+    ),
+    (
+        "{} cannot be stored in a set (only numbers, strings, and booleans can)",
+        "{} no se puede guardar en un conjunto (solo números, cadenas y booleanos)",
+    ),
+    (
+        "expected map for the first argument",
+        "se esperaba un mapa como primer argumento",
+    ),
+    (
+        "expected key for the second argument",
+        "se esperaba una clave como segundo argumento",
+    ),
+    (
+        "{} cannot be a map key (only numbers, strings, and booleans can)",
+        "{} no puede ser una clave de un mapa (solo números, cadenas y booleanos)",
+    ),
+    ("key {} is not in the map", "la clave {} no está en el mapa"),
+    ("invalid pattern: {}", "patrón no válido: {}"),
+    (
+        "{} may have been rounded; whole numbers are only exact below {}",
+        "{} puede haberse redondeado; los números enteros solo son exactos por debajo de {}",
+    ),
+    (
+        "a list cannot hold more than {} items",
+        "una lista no puede tener más de {} elementos",
+    ),
+    (
+        "a string cannot be longer than {} characters",
+        "un texto no puede tener más de {} caracteres",
+    ),
+    (
+        "a grid cannot hold more than {} cells",
+        "una cuadrícula no puede tener más de {} celdas",
+    ),
+    (
+        "the program is not allowed to read {}",
+        "el programa no tiene permiso para leer {}",
+    ),
+    (
+        "the program is not allowed to write {}",
+        "el programa no tiene permiso para escribir {}",
+    ),
+    (
+        "expected a parameter after `,`",
+        "se esperaba un parámetro después de `,`",
+    ),
+    (
+        "expected `,` between parameters",
+        "se esperaba `,` entre los parámetros",
+    ),
+    (
+        "cannot compare {} with {}",
+        "no se puede comparar {} con {}",
+    ),
+    (
+        "the program cannot add more than {} items to lists",
+        "el programa no puede agregar más de {} elementos a listas",
+    ),
+    ("there is no robot grid", "no hay una cuadrícula para el robot"),
+    (
+        "the robot cannot move off the grid",
+        "el robot no puede salirse de la cuadrícula",
+    ),
+    (
+        "the robot cannot move into a blocked square",
+        "el robot no puede moverse a una casilla bloqueada",
+    ),
+    ("{} is not a direction", "{} no es una dirección"),
+    (
+        "{} is not a square; use '.', '#', or one of '^', '>', 'v', and '<' for the robot",
+        "{} no es una casilla; usa '.', '#' o uno de '^', '>', 'v' y '<' para el robot",
+    ),
+    ("the grid has no robot", "la cuadrícula no tiene robot"),
+    (
+        "the grid has more than one robot",
+        "la cuadrícula tiene más de un robot",
+    ),
+    (
+        "row {} has {} squares, but the first row has {}",
+        "la fila {} tiene {} casillas, pero la primera fila tiene {}",
+    ),
+    ("{} is not a row of the grid", "{} no es una fila de la cuadrícula"),
+    // Hints
+    (
+        "help: procedures are called with the value as an argument, like `{}({})`",
+        "ayuda: los procedimientos se llaman con el valor como argumento, como `{}({})`",
+    ),
+    (
+        "help: lists start at index 1, so the first item is `{}[1]`",
+        "ayuda: las listas empiezan en el índice 1, así que el primer elemento es `{}[1]`",
+    ),
+    (
+        "help: lists start at index 1, so the first item is at index 1",
+        "ayuda: las listas empiezan en el índice 1, así que el primer elemento está en el índice 1",
+    ),
+    (
+        "help: the program ran for too long; check for a loop whose condition never changes",
+        "ayuda: el programa se ejecutó durante demasiado tiempo; busca un bucle cuya condición nunca cambie",
+    ),
+    (
+        "help: IMPORT only works in programs run from a file, such as with `aps run`",
+        "ayuda: IMPORT solo funciona en programas ejecutados desde un archivo, como con `aps run`",
+    ),
+    (
+        "help: PROCEDUREs can only call each other {} deep; check that a recursive PROCEDURE stops calling itself",
+        "ayuda: los PROCEDUREs solo pueden llamarse entre sí hasta {} niveles; comprueba que un PROCEDURE recursivo deje de llamarse a sí mismo",
+    ),
+    (
+        "help: the interpreter ran out of stack after PROCEDUREs called each other {} deep; check that a recursive PROCEDURE stops calling itself",
+        "ayuda: el intérprete se quedó sin pila después de que los PROCEDUREs se llamaran entre sí {} niveles; comprueba que un PROCEDURE recursivo deje de llamarse a sí mismo",
+    ),
+    (
+        "help: strings start at position 1, like lists",
+        "ayuda: los textos empiezan en la posición 1, como las listas",
+    ),
+    (
+        "help: both arguments must be strings or both must be lists",
+        "ayuda: los dos argumentos deben ser textos o los dos deben ser listas",
+    ),
+    (
+        "help: this is text, not a number; convert it with `NUMBER({})` first",
+        "ayuda: esto es texto, no un número; conviértelo primero con `NUMBER({})`",
+    ),
+    (
+        "help: text cannot be used as a number; if it came from INPUT, the answer was not a number",
+        "ayuda: el texto no se puede usar como número; si vino de INPUT, la respuesta no era un número",
+    ),
+    (
+        "help: convert the text to a number with `NUMBER({})` first",
+        "ayuda: convierte primero el texto en un número con `NUMBER({})`",
+    ),
+    (
+        "help: text can only be compared with text, and numbers with numbers",
+        "ayuda: el texto solo se puede comparar con texto, y los números con números",
+    ),
+    (
+        "help: set one up with `ROBOT_GRID(layout)` first",
+        "ayuda: prepara una primero con `ROBOT_GRID(layout)`",
+    ),
+    (
+        "help: check `CAN_MOVE(\"forward\")` before moving",
+        "ayuda: comprueba `CAN_MOVE(\"forward\")` antes de moverte",
+    ),
+    (
+        "help: check with `CONTAINS_KEY({}, {})` first",
+        "ayuda: compruébalo antes con `CONTAINS_KEY({}, {})`",
+    ),
+    (
+        "help: check with `CONTAINS_KEY(map, key)` first",
+        "ayuda: compruébalo antes con `CONTAINS_KEY(map, key)`",
+    ),
+    (
+        "help: use \"forward\", \"backward\", \"left\", or \"right\"",
+        "ayuda: usa \"forward\", \"backward\", \"left\" o \"right\"",
+    ),
+];
+
+const ES_TERMS: &[(&str, &str)] = &[
+    ("end of file", "fin del archivo"),
+    ("identifier", "identificador"),
+    ("integer", "entero"),
+    ("float", "decimal"),
+    ("string", "cadena"),
+    ("invalid string", "cadena no válida"),
+    ("binary literal", "literal binario"),
+    ("hex literal", "literal hexadecimal"),
+    ("unknown character", "carácter desconocido"),
+    ("variable", "la variable"),
+    ("procedure", "el procedimiento"),
+    ("parameter", "el parámetro"),
+];
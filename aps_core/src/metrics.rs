@@ -0,0 +1,105 @@
+//! Size and complexity measurements of a program, for `aps stats`.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::ast::{BinaryOpKind, Expr, Span, Stmt};
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Metrics {
+    /// Every statement in the program, not counting PROCEDURE definitions
+    /// themselves.
+    pub statements: usize,
+    /// How deeply blocks are nested anywhere in the program. Statements at
+    /// the top level or directly in a PROCEDURE are at depth 0.
+    pub max_depth: usize,
+    /// The PROCEDUREs defined at the top level, in order.
+    pub procedures: Vec<ProcedureMetrics>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProcedureMetrics {
+    pub name: String,
+    pub statements: usize,
+    pub max_depth: usize,
+    /// The number of paths through the PROCEDURE: one, plus one for every IF,
+    /// ELSE IF, loop, AND, and OR.
+    pub complexity: usize,
+}
+
+impl Metrics {
+    pub fn new(source: &str, program: &[Stmt]) -> Self {
+        let text = |span: Span| &source[span.start as usize..span.end as usize];
+
+        let mut metrics = Self::default();
+        for stmt in program {
+            let Stmt::Procedure(proc) = stmt else {
+                metrics.statements += count(core::slice::from_ref(stmt));
+                metrics.max_depth = metrics.max_depth.max(depth(stmt));
+                continue;
+            };
+
+            let procedure = ProcedureMetrics {
+                name: text(proc.name).into(),
+                statements: count(&proc.scope),
+                max_depth: proc.scope.iter().map(depth).max().unwrap_or(0),
+                complexity: 1 + proc.scope.iter().map(decisions).sum::<usize>(),
+            };
+            metrics.statements += procedure.statements;
+            metrics.max_depth = metrics.max_depth.max(procedure.max_depth);
+            metrics.procedures.push(procedure);
+        }
+
+        metrics
+    }
+}
+
+/// The statements in `scope` and its blocks, except PROCEDURE definitions.
+fn count(scope: &[Stmt]) -> usize {
+    let mut count = 0;
+    for stmt in scope {
+        stmt.walk(&mut |stmt| {
+            if !matches!(stmt, Stmt::Procedure(_)) {
+                count += 1;
+            }
+        });
+    }
+    count
+}
+
+/// How many blocks deep the statements in `stmt`'s blocks go.
+fn depth(stmt: &Stmt) -> usize {
+    stmt.scopes()
+        .into_iter()
+        .map(|scope| 1 + scope.iter().map(depth).max().unwrap_or(0))
+        .max()
+        .unwrap_or(0)
+}
+
+/// The branches and loops in `stmt` and its blocks, each of which adds a path
+/// through the program.
+fn decisions(stmt: &Stmt) -> usize {
+    let mut decisions = 0;
+    stmt.walk(&mut |stmt| {
+        decisions += match stmt {
+            Stmt::If { else_ifs, .. } => 1 + else_ifs.len(),
+            Stmt::RepeatN { .. } | Stmt::RepeatUntil { .. } | Stmt::For { .. } => 1,
+            _ => 0,
+        };
+
+        for expr in stmt.exprs() {
+            expr.walk(&mut |expr| {
+                decisions += match expr {
+                    Expr::BinaryOp {
+                        kind: BinaryOpKind::And | BinaryOpKind::Or,
+                        ..
+                    } => 1,
+                    Expr::If { else_ifs, .. } => 1 + else_ifs.len(),
+                    _ => 0,
+                };
+            });
+        }
+    });
+    decisions
+}
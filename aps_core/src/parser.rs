@@ -1,18 +1,99 @@
-use codespan_reporting::diagnostic::{Diagnostic, Label};
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+use core::mem;
 
 use crate::{
-    ast::{ElseIf, Expr, Node, Procedure, Span, Stmt, UnaryOpKind},
-    lexer::{Keyword, Lexer, Token},
+    ast::{BinaryOpKind, ElseIf, Expr, Node, Procedure, Span, Stmt, UnaryOpKind, MAX_NESTING},
+    diagnostic::{Diagnostic, Label, Severity},
+    edit::{Edit, Fix},
+    lexer::{unescape, Keyword, Lexer, Token, KEYWORDS},
 };
 
+/// The code of the diagnostic for a statement like `x = 1`, which compares
+/// instead of assigning. It is a warning if [`Parser::tolerant`] is set.
+pub const ASSIGN_WITH_EQUAL: &str = "assign-with-equal";
+
+/// The code of the diagnostic for a comparison like `1 < x < 10`. It is a
+/// warning if [`Parser::tolerant`] is set.
+pub const CHAINED_COMPARISON: &str = "chained-comparison";
+
+/// The language accepted by the parser.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    /// The pseudocode on the AP exam reference sheet.
+    #[default]
+    Ap,
+    /// Adds features for more advanced lessons, such as PROCEDUREs defined
+    /// inside other PROCEDUREs.
+    Extended,
+}
+
+/// One piece of a [`StatementForm`].
+#[derive(Debug, Clone)]
+pub enum FormPart {
+    /// A word that must appear literally, e.g. `SECONDS`.
+    Word(String),
+    /// An expression whose value is passed to the handler.
+    Expr,
+}
+
+/// A statement form added by an embedder, such as `WAIT n SECONDS`. Matching
+/// statements call the procedure or builtin named `handler` with the values of
+/// the form's expressions.
+#[derive(Debug, Clone)]
+pub struct StatementForm {
+    pub keyword: String,
+    pub parts: Vec<FormPart>,
+    pub handler: String,
+}
+
+impl StatementForm {
+    pub fn new(keyword: impl Into<String>, handler: impl Into<String>) -> Self {
+        Self {
+            keyword: keyword.into(),
+            parts: Vec::new(),
+            handler: handler.into(),
+        }
+    }
+
+    pub fn word(mut self, word: impl Into<String>) -> Self {
+        self.parts.push(FormPart::Word(word.into()));
+        self
+    }
+
+    pub fn expr(mut self) -> Self {
+        self.parts.push(FormPart::Expr);
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct Parser<'a, T: Copy> {
     pub lex: Lexer<'a>,
     pub fid: T,
     pub diagnostics: Vec<Diagnostic<T>>,
+    pub forms: Vec<StatementForm>,
+    /// Suggested fixes, each paired with the index of the diagnostic in
+    /// `diagnostics` that it resolves.
+    pub fixes: Vec<(usize, Fix)>,
+    /// Reports likely mistakes that still parse, such as `x = 5` as a
+    /// statement, as warnings instead of errors. Editor tooling sets this so
+    /// that such programs can still be analyzed.
+    pub tolerant: bool,
+    pub dialect: Dialect,
+    /// Allows RETURN outside of PROCEDUREs, which ends the program with that
+    /// value, for hosts that run programs to get a result.
+    pub script: bool,
+    /// How many IF expression blocks are being parsed, which may end on the
+    /// same line as their value.
+    value_blocks: u32,
+    /// How many expressions and blocks enclose the one being parsed.
+    depth: u32,
+    /// How deep the deepest part of the expression being parsed is, counting
+    /// the operators that wrap the expression to their left.
+    reach: u32,
 }
 
-pub type Result<T> = std::result::Result<T, ()>;
+pub type Result<T> = core::result::Result<T, ()>;
 
 impl<'a, T: Copy> Parser<'a, T> {
     pub fn new(fid: T, buffer: &'a [u8]) -> Self {
@@ -20,10 +101,296 @@ impl<'a, T: Copy> Parser<'a, T> {
             lex: Lexer::new(buffer),
             fid,
             diagnostics: Vec::new(),
+            forms: Vec::new(),
+            fixes: Vec::new(),
+            tolerant: false,
+            dialect: Dialect::Ap,
+            script: false,
+            value_blocks: 0,
+            depth: 0,
+            reach: 0,
+        }
+    }
+
+    /// Whether any of the diagnostics so far are errors rather than warnings.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity >= Severity::Error)
+    }
+
+    /// Teaches the parser a custom statement. The form's keyword is matched
+    /// against identifiers at the start of a statement.
+    pub fn register_statement(&mut self, form: StatementForm) {
+        self.forms.push(form);
+    }
+
+    fn text(&self, span: Span) -> &[u8] {
+        &self.lex.buffer[span.start as usize..span.end as usize]
+    }
+
+    /// Advice for a `.` between two names, as in `list.LENGTH`, where the
+    /// current token is the `.`. AP pseudocode has no methods, so the value is
+    /// passed as an argument instead.
+    fn dot_call_notes(&self) -> Vec<String> {
+        let span = self.lex.span();
+        if self.lex.token != Token::Unknown || self.text(span) != b"." {
+            return vec![];
+        }
+
+        let is_name = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+        let before = &self.lex.buffer[..span.start as usize];
+        let after = &self.lex.buffer[span.end as usize..];
+        let value =
+            &before[before.len() - before.iter().rev().take_while(|b| is_name(b)).count()..];
+        let procedure = &after[..after.iter().take_while(|b| is_name(b)).count()];
+        if value.is_empty() || procedure.is_empty() {
+            return vec![];
+        }
+
+        vec![format!(
+            "help: procedures are called with the value as an argument, like `{}({})`",
+            String::from_utf8_lossy(procedure),
+            String::from_utf8_lossy(value)
+        )]
+    }
+
+    /// The value of the number literal at `span`.
+    fn number(&mut self, span: Span) -> Result<f64> {
+        let text = String::from_utf8_lossy(self.text(span));
+        let Ok(value) = text.parse() else {
+            self.diagnostics.push(
+                Diagnostic::error()
+                    .with_message(format!("{text} is not a valid number"))
+                    .with_labels(vec![Label::primary(self.fid, span)]),
+            );
+            return Err(());
+        };
+        Ok(value)
+    }
+
+    /// The value of the `0x` or `0b` literal at `span`, whose digits are in
+    /// base `radix`. Values past 2^53 are rejected, since numbers that large
+    /// cannot all be stored exactly.
+    fn radix_number(&mut self, span: Span, radix: u32) -> Result<f64> {
+        const MAX_EXACT: u64 = 1 << 53;
+
+        let text = String::from_utf8_lossy(self.text(span));
+        let digits = &text[2..];
+        if digits.is_empty() {
+            self.diagnostics.push(
+                Diagnostic::error()
+                    .with_message(format!("{text} is not a valid number"))
+                    .with_labels(vec![Label::primary(self.fid, span)]),
+            );
+            return Err(());
+        }
+
+        match u64::from_str_radix(digits, radix) {
+            Ok(value) if value <= MAX_EXACT => Ok(value as f64),
+            _ => {
+                self.diagnostics.push(
+                    Diagnostic::error()
+                        .with_message(format!("{text} is too large to be stored exactly"))
+                        .with_labels(vec![Label::primary(self.fid, span)])
+                        .with_notes(vec![format!(
+                            "whole numbers are only exact up to {MAX_EXACT}"
+                        )]),
+                );
+                Err(())
+            }
+        }
+    }
+
+    fn parse_form(&mut self, form: usize) -> Result<Stmt> {
+        let start = self.lex.start as u32;
+        self.lex.next();
+
+        let mut args = Vec::new();
+        let mut end = self.lex.index as u32;
+
+        for part in 0..self.forms[form].parts.len() {
+            match &self.forms[form].parts[part] {
+                FormPart::Word(word) => {
+                    if self.lex.token != Token::Identifier
+                        || self.text(self.lex.span()) != word.as_bytes()
+                    {
+                        let word = format!("`{word}`");
+                        self.diagnostics.push(
+                            Diagnostic::error()
+                                .with_message(format!(
+                                    "expected {word}, found {}",
+                                    self.lex.token.as_ref()
+                                ))
+                                .with_labels(vec![Label::primary(self.fid, self.lex.span())
+                                    .with_message(format!("expected {word}"))]),
+                        );
+                        return Err(());
+                    }
+                    end = self.lex.index as u32;
+                    self.lex.next();
+                }
+                FormPart::Expr => {
+                    let value = self.parse_expr(0)?;
+                    end = value.span().end;
+                    args.push(value);
+                }
+            }
+        }
+
+        let stmt = Stmt::Custom {
+            span: Span { start, end },
+            handler: self.forms[form].handler.clone(),
+            args: args.into_boxed_slice(),
+        };
+        self.expect_stmt_end(&stmt);
+        Ok(stmt)
+    }
+
+    /// Reports `diagnostic` along with a fix for it.
+    fn suggest(&mut self, diagnostic: Diagnostic<T>, title: impl Into<String>, edits: Vec<Edit>) {
+        self.diagnostics.push(diagnostic);
+        self.fixes.push((
+            self.diagnostics.len() - 1,
+            Fix {
+                title: title.into(),
+                edits,
+            },
+        ));
+    }
+
+    /// If the current identifier is `keyword` written in the wrong case,
+    /// reports it and treats it as the keyword so parsing can continue.
+    fn keyword_case(&mut self, keyword: Token) {
+        if self.lex.token != Token::Identifier {
+            return;
+        }
+
+        let span = self.lex.span();
+        let upper = String::from_utf8_lossy(self.text(span)).to_ascii_uppercase();
+        if KEYWORDS.get(upper.as_str()) != Some(&keyword) {
+            return;
+        }
+
+        self.suggest(
+            Diagnostic::error()
+                .with_message("keywords must be uppercase")
+                .with_labels(vec![Label::primary(self.fid, span)
+                    .with_message(format!("write this as {}", keyword.as_ref()))]),
+            format!("Change to `{upper}`"),
+            vec![Edit::replace(span, upper)],
+        );
+        self.lex.token = keyword;
+    }
+
+    /// Like [`Self::keyword_case`], for a statement keyword like `if` at the
+    /// start of a statement. A name followed by `<-` is left alone since it is
+    /// an ordinary assignment.
+    fn statement_keyword_case(&mut self) {
+        if self.lex.token != Token::Identifier {
+            return;
+        }
+
+        let span = self.lex.span();
+        let upper = String::from_utf8_lossy(self.text(span)).to_ascii_uppercase();
+        let Some(
+            &keyword @ Token::Keyword(
+                Keyword::If | Keyword::Repeat | Keyword::For | Keyword::Procedure | Keyword::Return,
+            ),
+        ) = KEYWORDS.get(upper.as_str())
+        else {
+            return;
+        };
+
+        let has_newline_before = self.lex.has_newline_before;
+        self.lex.next();
+        let next = self.lex.token;
+        self.lex.index = span.start as usize;
+        self.lex.next();
+        self.lex.has_newline_before = has_newline_before;
+
+        if !matches!(next, Token::ThinArrow | Token::LeftBrack) {
+            self.keyword_case(keyword);
+        }
+    }
+
+    /// Parses the parenthesized condition of an IF or REPEAT UNTIL, suggesting
+    /// parentheses when they are missing.
+    fn parse_cond(&mut self, keyword: &str) -> Result<Expr> {
+        let cond = self.parse_expr(0)?;
+        if let Expr::Paren { value, .. } = cond {
+            return Ok(*value);
+        }
+
+        let span = cond.span();
+        self.suggest(
+            Diagnostic::error()
+                .with_message(format!(
+                    "{keyword} conditions must be wrapped in parentheses"
+                ))
+                .with_labels(vec![Label::primary(self.fid, span)
+                    .with_message("add parentheses around this condition")]),
+            "Wrap condition in parentheses",
+            vec![Edit::insert(span.start, "("), Edit::insert(span.end, ")")],
+        );
+        Ok(cond)
+    }
+
+    /// Reports a statement like `x = 5`, which compares `x` with `5` rather
+    /// than assigning to it.
+    fn assign_with_equal(&mut self, stmt: &Expr) {
+        let Expr::BinaryOp {
+            kind: BinaryOpKind::Equal,
+            lhs,
+            rhs,
+        } = stmt
+        else {
+            return;
+        };
+        if !matches!(**lhs, Expr::Identifier { .. } | Expr::Index { .. }) {
+            return;
         }
+
+        let (start, end) = (lhs.span().end, rhs.span().start);
+        let Some(offset) = self
+            .text(Span { start, end })
+            .iter()
+            .position(|&b| b == b'=')
+        else {
+            return;
+        };
+        let op = Span {
+            start: start + offset as u32,
+            end: start + offset as u32 + 1,
+        };
+
+        let severity = match self.tolerant {
+            true => Severity::Warning,
+            false => Severity::Error,
+        };
+        let note = format!(
+            "to assign, write `{} <- {}`",
+            String::from_utf8_lossy(self.text(lhs.span())),
+            String::from_utf8_lossy(self.text(rhs.span())),
+        );
+        self.suggest(
+            Diagnostic::new(severity)
+                .with_message("`=` compares values; use `<-` to assign")
+                .with_code(ASSIGN_WITH_EQUAL)
+                .with_labels(vec![
+                    Label::primary(self.fid, op).with_message("this compares instead of assigning")
+                ])
+                .with_notes(vec![note]),
+            "Replace `=` with `<-`",
+            vec![Edit::replace(op, "<-")],
+        );
     }
 
     fn eat(&mut self, tok: Token) -> Result<Span> {
+        if let Token::Keyword(_) = tok {
+            self.keyword_case(tok);
+        }
+
         if self.lex.token != tok {
             self.diagnostics.push(
                 Diagnostic::error()
@@ -42,14 +409,125 @@ impl<'a, T: Copy> Parser<'a, T> {
         Ok(span)
     }
 
+    /// Eats the `}` that closes the block opened by the `{` at `open`.
+    fn close_brace(&mut self, open: Span) -> Result<Span> {
+        if self.lex.token == Token::EOF {
+            self.diagnostics.push(
+                Diagnostic::error()
+                    .with_message("unclosed `{`")
+                    .with_labels(vec![
+                        Label::primary(self.fid, open).with_message("this `{` is never closed"),
+                        Label::secondary(self.fid, self.lex.span())
+                            .with_message("expected `}` before the end of the file"),
+                    ]),
+            );
+            return Err(());
+        }
+
+        self.eat(Token::RightBrace)
+    }
+
+    /// Parses a `{ ... }` block.
+    fn parse_block(&mut self, is_global_scope: bool) -> Result<Box<[Stmt]>> {
+        let open = self.eat(Token::LeftBrace)?;
+        let scope = self.parse_scope(is_global_scope);
+        self.close_brace(open)?;
+        scope
+    }
+
+    /// Parses a block of an IF expression, which must end with its value.
+    fn parse_value_block(&mut self) -> Result<(Box<[Stmt]>, Span)> {
+        let open = self.eat(Token::LeftBrace)?;
+        self.value_blocks += 1;
+        let scope = self.parse_scope(false);
+        self.value_blocks -= 1;
+        let span = Span {
+            start: open.start,
+            end: self.close_brace(open)?.end,
+        };
+        let scope = scope?;
+
+        if let Some(ret) = find_return(&scope) {
+            self.diagnostics.push(
+                Diagnostic::error()
+                    .with_message("RETURN cannot be used inside an IF expression")
+                    .with_labels(vec![Label::primary(self.fid, ret.span())
+                        .with_message("RETURN inside an IF expression")]),
+            );
+        }
+
+        if !matches!(scope.last(), Some(Stmt::Expr(_))) {
+            self.diagnostics.push(
+                Diagnostic::error()
+                    .with_message("the blocks of an IF expression must end with a value")
+                    .with_labels(vec![Label::primary(self.fid, span)
+                        .with_message("expected a value on the last line of this block")]),
+            );
+        }
+
+        Ok((scope, span))
+    }
+
+    /// Parses `IF (cond) { ... } ELSE { ... }` as a value.
+    fn parse_if_expr(&mut self) -> Result<Expr> {
+        let start = self.lex.start as u32;
+        self.lex.next();
+
+        let cond = self.parse_cond("IF")?;
+        let (scope, mut last) = self.parse_value_block()?;
+        let mut else_ifs = Vec::new();
+
+        let els = loop {
+            self.keyword_case(Token::Keyword(Keyword::Else));
+            if self.lex.token != Token::Keyword(Keyword::Else) {
+                let span = Span {
+                    start,
+                    end: last.end,
+                };
+                self.diagnostics.push(
+                    Diagnostic::error()
+                        .with_message("IF expressions must have an ELSE block")
+                        .with_labels(vec![Label::primary(self.fid, span)
+                            .with_message("this IF has no value when its conditions are FALSE")]),
+                );
+                return Err(());
+            }
+            self.lex.next();
+
+            if self.lex.token == Token::LeftBrace {
+                let (els, span) = self.parse_value_block()?;
+                last = span;
+                break els;
+            }
+
+            self.eat(Token::Keyword(Keyword::If))?;
+            let cond = self.parse_cond("ELSE IF")?;
+            let (scope, span) = self.parse_value_block()?;
+            last = span;
+            else_ifs.push(ElseIf { cond, scope });
+        };
+
+        Ok(Expr::If {
+            span: Span {
+                start,
+                end: last.end,
+            },
+            cond: Box::new(cond),
+            scope,
+            else_ifs: else_ifs.into_boxed_slice(),
+            els,
+        })
+    }
+
     fn parse_simple_expr(&mut self) -> Result<Expr> {
         Ok(match self.lex.token {
+            Token::Keyword(Keyword::If) if self.dialect == Dialect::Extended => {
+                self.parse_if_expr()?
+            }
             Token::Keyword(Keyword::True) => {
                 let start = self.lex.start as u32;
                 self.lex.next();
-                Expr::True {
-                    start: start as u32,
-                }
+                Expr::True { start }
             }
             Token::Keyword(Keyword::False) => {
                 let start = self.lex.start as u32;
@@ -63,15 +541,47 @@ impl<'a, T: Copy> Parser<'a, T> {
             }
             Token::StringLiteral => {
                 let span = self.lex.span();
+                let raw = String::from_utf8_lossy(self.text(Span {
+                    start: span.start + 1,
+                    end: span.end - 1,
+                }));
+                let value = match unescape(&raw) {
+                    Ok(value) => value,
+                    Err(escape) => {
+                        let escape = Span {
+                            start: span.start + 1 + escape.start as u32,
+                            end: span.start + 1 + escape.end as u32,
+                        };
+                        let text = String::from_utf8_lossy(self.text(escape));
+                        self.diagnostics.push(
+                            Diagnostic::error()
+                                .with_message(format!("invalid escape sequence `{text}`"))
+                                .with_labels(vec![Label::primary(self.fid, escape)])
+                                .with_notes(vec![
+                                    "the escapes are `\\n`, `\\t`, `\\\\`, `\\\"`, and `\\u{...}` with a character's code in hex"
+                                        .into(),
+                                ]),
+                        );
+                        return Err(());
+                    }
+                };
                 self.lex.next();
-                Expr::StringLiteral { span }
+                Expr::StringLiteral { span, value }
+            }
+            Token::InvalidStringLiteral => {
+                self.diagnostics.push(
+                    Diagnostic::error()
+                        .with_message("string is missing its closing `\"`")
+                        .with_labels(vec![Label::primary(self.fid, self.lex.span())
+                            .with_message("this string never ends")]),
+                );
+                return Err(());
             }
             Token::LeftParen => {
                 let start = self.lex.start as u32;
                 self.lex.next();
                 let inner = self.parse_expr(0)?;
-                let end = self.lex.start as u32;
-                self.eat(Token::RightParen)?;
+                let end = self.eat(Token::RightParen)?.end;
                 Expr::Paren {
                     span: Span { start, end },
                     value: Box::new(inner),
@@ -102,6 +612,33 @@ impl<'a, T: Copy> Parser<'a, T> {
                     values: values.into_boxed_slice(),
                 }
             }
+            Token::LeftBrace => {
+                let start = self.lex.start as u32;
+                self.lex.next();
+                let mut entries = Vec::new();
+
+                loop {
+                    if self.lex.token == Token::RightBrace {
+                        break;
+                    }
+
+                    let key = self.parse_expr(0)?;
+                    self.eat(Token::Colon)?;
+                    entries.push((key, self.parse_expr(0)?));
+
+                    if self.lex.token == Token::Comma {
+                        self.lex.next();
+                    }
+                }
+
+                let end = self.lex.index as u32;
+                self.eat(Token::RightBrace)?;
+
+                Expr::MapLiteral {
+                    span: Span { start, end },
+                    entries: entries.into_boxed_slice(),
+                }
+            }
             Token::Keyword(Keyword::Not) => {
                 let start = self.lex.start as u32;
                 self.lex.next();
@@ -143,30 +680,35 @@ impl<'a, T: Copy> Parser<'a, T> {
             }
             Token::IntegerLiteral => {
                 let span = self.lex.span();
+                let value = self.number(span)?;
                 self.lex.next();
-                Expr::IntegerLiteral { span }
+                Expr::IntegerLiteral { span, value }
             }
             Token::FloatLiteral => {
                 let span = self.lex.span();
+                let value = self.number(span)?;
                 self.lex.next();
-                Expr::FloatLiteral { span }
+                Expr::FloatLiteral { span, value }
             }
             Token::HexLiteral => {
                 let span = self.lex.span();
+                let value = self.radix_number(span, 16)?;
                 self.lex.next();
-                Expr::HexLiteral { span }
+                Expr::HexLiteral { span, value }
             }
             Token::BinaryLiteral => {
                 let span = self.lex.span();
+                let value = self.radix_number(span, 2)?;
                 self.lex.next();
-                Expr::BinaryLiteral { span }
+                Expr::BinaryLiteral { span, value }
             }
             tok => {
                 self.diagnostics.push(
                     Diagnostic::error()
                         .with_message(format!("expected expression, found {}", tok.as_ref()))
                         .with_labels(vec![Label::primary(self.fid, self.lex.span())
-                            .with_message("expected expression")]),
+                            .with_message("expected expression")])
+                        .with_notes(self.dot_call_notes()),
                 );
                 return Err(());
             }
@@ -174,6 +716,44 @@ impl<'a, T: Copy> Parser<'a, T> {
     }
 
     pub fn parse_expr(&mut self, lbp: u8) -> Result<Expr> {
+        if self.depth >= MAX_NESTING {
+            self.nested_too_deeply("expression", self.lex.span());
+            return Err(());
+        }
+        self.depth += 1;
+        let outer = mem::replace(&mut self.reach, self.depth);
+        let expr = self.parse_operators(lbp);
+        self.depth -= 1;
+        self.reach = self.reach.max(outer);
+        expr
+    }
+
+    /// Reports an expression or block nested more than [`MAX_NESTING`] deep,
+    /// which could overflow the stack while parsing, checking, or running it.
+    fn nested_too_deeply(&mut self, what: &str, span: Span) {
+        self.diagnostics.push(
+            Diagnostic::error()
+                .with_message(format!("{what} nested too deeply"))
+                .with_labels(vec![Label::primary(self.fid, span).with_message(format!(
+                    "this is nested more than {MAX_NESTING} levels deep"
+                ))])
+                .with_notes(vec![String::from(
+                    "store parts of it in variables or move them into a PROCEDURE",
+                )]),
+        );
+    }
+
+    /// Moves the expression to the left of an operator one level deeper.
+    fn wrap(&mut self, lhs: &Expr) -> Result<()> {
+        self.reach += 1;
+        if self.reach > MAX_NESTING {
+            self.nested_too_deeply("expression", lhs.span());
+            return Err(());
+        }
+        Ok(())
+    }
+
+    fn parse_operators(&mut self, lbp: u8) -> Result<Expr> {
         let mut lhs = self.parse_simple_expr()?;
 
         loop {
@@ -183,7 +763,14 @@ impl<'a, T: Copy> Parser<'a, T> {
                 break;
             }
 
+            // A `[` on a new line starts a statement like `[a, b] <- pair`
+            // rather than indexing the line before.
+            if self.lex.token == Token::LeftBrack && self.lex.has_newline_before {
+                break;
+            }
+
             if self.lex.token == Token::LeftBrack {
+                self.wrap(&lhs)?;
                 self.lex.next();
                 let index = self.parse_expr(0)?;
                 let end = self.lex.index as u32;
@@ -200,6 +787,7 @@ impl<'a, T: Copy> Parser<'a, T> {
             }
 
             if self.lex.token == Token::LeftParen {
+                self.wrap(&lhs)?;
                 self.lex.next();
 
                 let mut args = Vec::new();
@@ -230,7 +818,20 @@ impl<'a, T: Copy> Parser<'a, T> {
                 continue;
             }
 
-            let kind = self.lex.token.into();
+            let Ok(kind) = BinaryOpKind::try_from(self.lex.token) else {
+                self.diagnostics.push(
+                    Diagnostic::error()
+                        .with_message(format!(
+                            "expected an operator, found {}",
+                            self.lex.token.as_ref()
+                        ))
+                        .with_labels(vec![Label::primary(self.fid, self.lex.span())
+                            .with_message("expected an operator")])
+                        .with_notes(self.dot_call_notes()),
+                );
+                return Err(());
+            };
+            self.wrap(&lhs)?;
             self.lex.next();
 
             lhs = Expr::BinaryOp {
@@ -238,13 +839,72 @@ impl<'a, T: Copy> Parser<'a, T> {
                 lhs: Box::new(lhs),
                 rhs: Box::new(self.parse_expr(prec)?),
             };
+            self.chained_comparison(&lhs);
         }
 
         Ok(lhs)
     }
 
+    /// Reports an expression like `1 < x < 10`, which compares the TRUE or
+    /// FALSE from `1 < x` with `10` rather than checking that `x` is between
+    /// them.
+    fn chained_comparison(&mut self, expr: &Expr) {
+        fn is_ordering(kind: &BinaryOpKind) -> bool {
+            matches!(
+                kind,
+                BinaryOpKind::Less
+                    | BinaryOpKind::LessEqual
+                    | BinaryOpKind::Greater
+                    | BinaryOpKind::GreaterEqual
+            )
+        }
+
+        let Expr::BinaryOp { kind, lhs, rhs } = expr else {
+            return;
+        };
+        if !is_ordering(kind) {
+            return;
+        }
+        // The operand written between the two operators.
+        let middle = match (&**lhs, &**rhs) {
+            (Expr::BinaryOp { kind, rhs, .. }, _) if is_ordering(kind) => rhs,
+            (_, Expr::BinaryOp { kind, lhs, .. }) if is_ordering(kind) => lhs,
+            _ => return,
+        };
+
+        let span = expr.span();
+        let text =
+            |start, end| String::from_utf8_lossy(self.text(Span { start, end })).into_owned();
+        let middle_text = text(middle.span().start, middle.span().end);
+        let first = text(span.start, middle.span().end);
+        let rest = text(middle.span().end, span.end);
+        let severity = match self.tolerant {
+            true => Severity::Warning,
+            false => Severity::Error,
+        };
+        self.suggest(
+            Diagnostic::new(severity)
+                .with_message("comparisons cannot be chained")
+                .with_code(CHAINED_COMPARISON)
+                .with_labels(vec![Label::primary(self.fid, span).with_message(
+                    "this compares the TRUE or FALSE of one comparison with a number",
+                )])
+                .with_notes(vec![format!(
+                    "to check both, write `{first} AND {middle_text}{rest}`"
+                )]),
+            "Split into two comparisons joined by AND",
+            vec![Edit::insert(
+                middle.span().end,
+                format!(" AND {middle_text}"),
+            )],
+        );
+    }
+
     fn expect_stmt_end(&mut self, node: &impl Node) {
-        if !self.lex.has_newline_before && self.lex.token != Token::EOF {
+        if !self.lex.has_newline_before
+            && self.lex.token != Token::EOF
+            && !(self.value_blocks > 0 && self.lex.token == Token::RightBrace)
+        {
             self.diagnostics.push(
                 Diagnostic::error()
                     .with_message(format!(
@@ -255,242 +915,464 @@ impl<'a, T: Copy> Parser<'a, T> {
                         Label::primary(self.fid, self.lex.span())
                             .with_message("expected new line here"),
                         Label::secondary(self.fid, node.span()).with_message("main statement here"),
-                    ]),
+                    ])
+                    .with_notes(self.dot_call_notes()),
             );
         }
     }
 
     pub fn parse_scope(&mut self, is_global_scope: bool) -> Result<Box<[Stmt]>> {
+        if self.depth >= MAX_NESTING {
+            self.nested_too_deeply("block", self.lex.span());
+            return Err(());
+        }
+        self.depth += 1;
+        let scope = self.parse_stmts(is_global_scope);
+        self.depth -= 1;
+        scope
+    }
+
+    fn parse_stmts(&mut self, is_global_scope: bool) -> Result<Box<[Stmt]>> {
         let mut nodes = Vec::new();
+        let mut failed = false;
 
+        loop {
+            let start = self.lex.start;
+            match self.parse_stmt(&mut nodes, is_global_scope) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(()) => {
+                    failed = true;
+                    self.synchronize(start);
+                }
+            }
+        }
+
+        match failed {
+            true => Err(()),
+            false => Ok(nodes.into_boxed_slice()),
+        }
+    }
+
+    /// Parses a whole program, starting from the lexer's position. Anything
+    /// left over that cannot start a statement, such as a stray `)`, is
+    /// reported rather than ignored, so a typo never runs half of a program.
+    pub fn parse_program(&mut self) -> Result<Box<[Stmt]>> {
+        self.lex.next();
+        let mut scope = self.parse_scope(true);
+
+        while self.lex.token != Token::EOF {
+            self.diagnostics.push(
+                Diagnostic::error()
+                    .with_message(format!(
+                        "expected statement, found {}",
+                        self.lex.token.as_ref()
+                    ))
+                    .with_labels(vec![Label::primary(self.fid, self.lex.span())
+                        .with_message("expected statement")]),
+            );
+
+            // Skips to the next line to check the rest of the program.
+            let start = self.lex.start;
+            self.lex.next();
+            self.synchronize(start);
+            _ = self.parse_scope(true);
+            scope = Err(());
+        }
+
+        scope
+    }
+
+    /// Skips the rest of a statement that began at `start` and failed to
+    /// parse, stopping at the next line, the `}` that closes the block, or the
+    /// end of the file, so the statements after it are still checked.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = 0u32;
         loop {
             match self.lex.token {
-                Token::Identifier => {
-                    let name = self.lex.span();
-                    self.lex.next();
+                Token::EOF => return,
+                Token::RightBrace if depth == 0 => return,
+                _ if depth == 0 && self.lex.has_newline_before && self.lex.start > start => return,
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.lex.next();
+        }
+    }
 
-                    match self.lex.token {
-                        Token::ThinArrow => {
-                            self.lex.next();
-                            let value = self.parse_expr(0)?;
-                            let stmt = Stmt::VarAssign { name, value };
-                            self.expect_stmt_end(&stmt);
-                            nodes.push(stmt);
-                        }
-                        _ => 'blk: {
-                            self.lex.index = name.start as usize;
+    /// Parses a statement into `nodes`, returning whether there was one.
+    fn parse_stmt(&mut self, nodes: &mut Vec<Stmt>, is_global_scope: bool) -> Result<bool> {
+        if self.lex.token == Token::Identifier {
+            let word = self.text(self.lex.span());
+            if let Some(form) = self
+                .forms
+                .iter()
+                .position(|form| form.keyword.as_bytes() == word)
+            {
+                let stmt = self.parse_form(form)?;
+                nodes.push(stmt);
+                return Ok(true);
+            }
+
+            self.statement_keyword_case();
+        }
+
+        match self.lex.token {
+            Token::Identifier => {
+                let name = self.lex.span();
+                self.lex.next();
+
+                match self.lex.token {
+                    Token::ThinArrow => {
+                        self.lex.next();
+                        let value = self.parse_expr(0)?;
+                        let stmt = Stmt::VarAssign { name, value };
+                        self.expect_stmt_end(&stmt);
+                        nodes.push(stmt);
+                    }
+                    _ => 'blk: {
+                        self.lex.index = name.start as usize;
+                        self.lex.next();
+                        let value = self.parse_expr(0)?;
+
+                        if self.lex.token == Token::ThinArrow {
                             self.lex.next();
-                            let value = self.parse_expr(0)?;
+                            let Expr::Index {
+                                value: root, index, ..
+                            } = value
+                            else {
+                                self.expect_stmt_end(&value);
+                                return Err(());
+                            };
 
-                            if self.lex.token == Token::ThinArrow {
-                                self.lex.next();
-                                let Expr::Index { value: root, index, .. } = value else {
-									self.expect_stmt_end(&value);
-									return Err(());
-								};
-
-                                let value = self.parse_expr(0)?;
-                                let node = Stmt::IndexAssign { root, index, value };
-                                self.expect_stmt_end(&node);
-                                nodes.push(node);
-                                break 'blk;
-                            }
-                            self.expect_stmt_end(&value);
-                            nodes.push(Stmt::Expr(value));
+                            let value = self.parse_expr(0)?;
+                            let node = Stmt::IndexAssign { root, index, value };
+                            self.expect_stmt_end(&node);
+                            nodes.push(node);
+                            break 'blk;
                         }
+                        self.expect_stmt_end(&value);
+                        self.assign_with_equal(&value);
+                        nodes.push(Stmt::Expr(value));
                     }
                 }
-                Token::Add
-                | Token::Sub
-                | Token::IntegerLiteral
-                | Token::LeftBrack
-                | Token::LeftParen => 'blk: {
-                    let value = self.parse_expr(0)?;
+            }
+            Token::Add
+            | Token::Sub
+            | Token::IntegerLiteral
+            | Token::LeftBrack
+            | Token::LeftParen => 'blk: {
+                let value = self.parse_expr(0)?;
 
-                    if self.lex.token == Token::ThinArrow {
-                        println!("HERE!");
-                        self.lex.next();
-                        let Expr::Index { value: root, index, .. } = value else {
-							self.expect_stmt_end(&value);
-							nodes.push(Stmt::Expr(value));
-							break 'blk;
-						};
+                if self.lex.token == Token::ThinArrow {
+                    self.lex.next();
 
-                        let value = self.parse_expr(0)?;
-                        let node = Stmt::IndexAssign { root, index, value };
+                    if let Expr::ArrayLiteral { span, values } = &value {
+                        let mut names = Vec::with_capacity(values.len());
+                        for value in values.iter() {
+                            let &Expr::Identifier { span } = value else {
+                                self.diagnostics.push(
+                                    Diagnostic::error()
+                                        .with_message(
+                                            "only variables can be assigned in `[a, b] <- list`",
+                                        )
+                                        .with_labels(vec![Label::primary(self.fid, value.span())
+                                            .with_message("expected a variable name")]),
+                                );
+                                return Err(());
+                            };
+                            names.push(span);
+                        }
+
+                        let node = Stmt::Destructure {
+                            span: *span,
+                            names: names.into_boxed_slice(),
+                            value: self.parse_expr(0)?,
+                        };
                         self.expect_stmt_end(&node);
                         nodes.push(node);
                         break 'blk;
                     }
 
-                    self.expect_stmt_end(&value);
-                    nodes.push(Stmt::Expr(value));
+                    let Expr::Index {
+                        value: root, index, ..
+                    } = value
+                    else {
+                        self.expect_stmt_end(&value);
+                        nodes.push(Stmt::Expr(value));
+                        break 'blk;
+                    };
+
+                    let value = self.parse_expr(0)?;
+                    let node = Stmt::IndexAssign { root, index, value };
+                    self.expect_stmt_end(&node);
+                    nodes.push(node);
+                    break 'blk;
                 }
-                Token::Keyword(Keyword::If) => {
-                    self.lex.next();
 
-                    self.eat(Token::LeftParen)?;
-                    let cond = self.parse_expr(0)?;
-                    self.eat(Token::RightParen)?;
-                    self.eat(Token::LeftBrace)?;
-                    let scope = self.parse_scope(is_global_scope)?;
-                    self.eat(Token::RightBrace)?;
+                self.expect_stmt_end(&value);
+                self.assign_with_equal(&value);
+                nodes.push(Stmt::Expr(value));
+            }
+            Token::Keyword(Keyword::If) => {
+                self.lex.next();
 
-                    let mut else_ifs = Vec::new();
-                    let mut els = None;
+                let cond = self.parse_cond("IF")?;
+                let scope = self.parse_block(is_global_scope)?;
 
-                    loop {
-                        if self.lex.token != Token::Keyword(Keyword::Else) {
-                            break;
-                        }
-                        self.lex.next();
+                let mut else_ifs = Vec::new();
+                let mut els = None;
 
-                        if self.lex.token == Token::LeftBrace {
-                            self.lex.next();
-                            els = Some(self.parse_scope(is_global_scope)?);
-                            self.eat(Token::RightBrace)?;
-                            break;
-                        }
+                loop {
+                    self.keyword_case(Token::Keyword(Keyword::Else));
+                    if self.lex.token != Token::Keyword(Keyword::Else) {
+                        break;
+                    }
+                    self.lex.next();
 
-                        self.eat(Token::Keyword(Keyword::If))?;
-                        self.eat(Token::LeftParen)?;
-                        let cond = self.parse_expr(0)?;
-                        self.eat(Token::RightParen)?;
-                        self.eat(Token::LeftBrace)?;
-                        let scope = self.parse_scope(is_global_scope)?;
-                        self.eat(Token::RightBrace)?;
-                        else_ifs.push(ElseIf { cond, scope });
+                    if self.lex.token == Token::LeftBrace {
+                        els = Some(self.parse_block(is_global_scope)?);
+                        break;
                     }
 
-                    nodes.push(Stmt::If {
-                        cond: Box::new(cond),
-                        scope,
-                        else_ifs: else_ifs.into_boxed_slice(),
-                        els,
-                    });
+                    self.eat(Token::Keyword(Keyword::If))?;
+                    let cond = self.parse_cond("ELSE IF")?;
+                    let scope = self.parse_block(is_global_scope)?;
+                    else_ifs.push(ElseIf { cond, scope });
                 }
-                Token::Keyword(Keyword::Procedure) => {
-                    let start = self.lex.start as u32;
-                    self.lex.next();
-                    let name = self.eat(Token::Identifier)?;
-                    self.eat(Token::LeftParen)?;
-                    let mut params = Vec::new();
-
-                    loop {
-                        if self.lex.token == Token::RightParen {
-                            break;
-                        }
 
-                        let param = self.eat(Token::Identifier)?;
-                        params.push(param);
+                nodes.push(Stmt::If {
+                    cond: Box::new(cond),
+                    scope,
+                    else_ifs: else_ifs.into_boxed_slice(),
+                    els,
+                });
+            }
+            Token::Keyword(Keyword::Procedure) => {
+                let start = self.lex.start as u32;
+                self.lex.next();
+                let name = self.eat(Token::Identifier)?;
+                self.eat(Token::LeftParen)?;
+                let mut params = Vec::new();
 
-                        match self.lex.token {
-                            Token::Comma => self.lex.next(),
-                            _ => break,
-                        }
+                loop {
+                    if self.lex.token == Token::RightParen {
+                        break;
                     }
 
-                    self.eat(Token::RightParen)?;
-
-                    self.eat(Token::LeftBrace)?;
-                    let scope = self.parse_scope(false)?;
-                    let end = self.lex.index as u32;
-                    self.eat(Token::RightBrace)?;
+                    let param = self.eat(Token::Identifier)?;
+                    params.push(param);
 
-                    if !is_global_scope {
-                        self.diagnostics.push(
-                            Diagnostic::error()
-                                .with_message(format!(
-                                    "PROCEDUREs cannot be outside of the global scope",
-                                ))
-                                .with_labels(vec![Label::primary(self.fid, Span { start, end })
-                                    .with_message(format!("PROCEDURE not in the global scope"))]),
-                        );
-                    }
+                    match self.lex.token {
+                        Token::Comma => {
+                            let comma = self.lex.span();
+                            self.lex.next();
 
-                    nodes.push(Stmt::Procedure(Procedure {
-                        name,
-                        params: params.into_boxed_slice(),
-                        scope,
-                    }));
-                }
-                Token::Keyword(Keyword::Return) => {
-                    let start = self.lex.start as u32;
-                    self.lex.next();
-                    let ret_stmt = if self.lex.has_newline_before || self.lex.token == Token::EOF {
-                        Stmt::Return {
-                            start,
-                            value: Expr::Void,
+                            if self.lex.token == Token::RightParen {
+                                self.suggest(
+                                    Diagnostic::error()
+                                        .with_message("expected a parameter after `,`")
+                                        .with_labels(vec![Label::primary(self.fid, comma)
+                                            .with_message("remove this `,`")]),
+                                    "Remove the `,`",
+                                    vec![Edit::delete(comma)],
+                                );
+                            }
                         }
-                    } else {
-                        Stmt::Return {
-                            start,
-                            value: self.parse_expr(0)?,
+                        // Two names in a row are most likely missing the
+                        // comma between them, so the rest of the
+                        // parameters are still read.
+                        Token::Identifier => {
+                            self.suggest(
+                                Diagnostic::error()
+                                    .with_message("expected `,` between parameters")
+                                    .with_labels(vec![Label::primary(self.fid, self.lex.span())
+                                        .with_message("add a `,` before this parameter")]),
+                                "Add a `,`",
+                                vec![Edit::insert(param.end, ",")],
+                            );
                         }
-                    };
-
-                    if is_global_scope {
-                        self.diagnostics.push(
-                            Diagnostic::error()
-                                .with_message(format!(
-                                    "RETURN statements cannot be outside of function scopes",
-                                ))
-                                .with_labels(vec![Label::primary(self.fid, ret_stmt.span())
-                                    .with_message(format!("RETURN not in function scope"))]),
-                        );
+                        _ => break,
                     }
+                }
+
+                self.eat(Token::RightParen)?;
 
-                    self.expect_stmt_end(&ret_stmt);
+                let open = self.eat(Token::LeftBrace)?;
+                let scope = self.parse_scope(false);
+                let end = self.lex.index as u32;
+                self.close_brace(open)?;
+                let mut scope = scope?;
+                mark_tail_returns(&mut scope);
 
-                    nodes.push(ret_stmt);
+                // In the extended dialect, a PROCEDURE inside another one
+                // is defined in that call's variables.
+                if !is_global_scope && self.dialect != Dialect::Extended {
+                    self.diagnostics.push(
+                        Diagnostic::error()
+                            .with_message("PROCEDUREs cannot be outside of the global scope")
+                            .with_labels(vec![Label::primary(self.fid, Span { start, end })
+                                .with_message("PROCEDURE not in the global scope")]),
+                    );
                 }
-                Token::Keyword(Keyword::Repeat) => 'blk: {
-                    self.lex.next();
 
-                    if let Token::Keyword(Keyword::Until) = self.lex.token {
-                        self.lex.next();
-                        self.eat(Token::LeftParen)?;
-                        let cond = self.parse_expr(0)?;
-                        self.eat(Token::RightParen)?;
-                        self.eat(Token::LeftBrace)?;
-                        let scope = self.parse_scope(is_global_scope)?;
-                        self.eat(Token::RightBrace)?;
-                        nodes.push(Stmt::RepeatUntil {
-                            cond: Box::new(cond),
-                            scope,
-                        });
-                        break 'blk;
+                nodes.push(Stmt::Procedure(Procedure {
+                    span: Span { start, end },
+                    name,
+                    params: params.into_boxed_slice(),
+                    scope,
+                }));
+            }
+            Token::Keyword(Keyword::Import) => {
+                let start = self.lex.start as u32;
+                self.lex.next();
+                if self.lex.token != Token::StringLiteral {
+                    self.diagnostics.push(
+                        Diagnostic::error()
+                            .with_message(format!(
+                                "expected the file to import in quotes, found {}",
+                                self.lex.token.as_ref()
+                            ))
+                            .with_labels(vec![Label::primary(self.fid, self.lex.span())
+                                .with_message("expected a string like \"file.aps\"")]),
+                    );
+                    return Err(());
+                }
+                let Expr::StringLiteral { span, value: path } = self.parse_simple_expr()? else {
+                    unreachable!();
+                };
+                let span = Span {
+                    start,
+                    end: span.end,
+                };
+
+                if !is_global_scope {
+                    self.diagnostics.push(
+                        Diagnostic::error()
+                            .with_message("IMPORT cannot be inside a PROCEDURE")
+                            .with_labels(vec![Label::primary(self.fid, span)
+                                .with_message("IMPORT inside a PROCEDURE")]),
+                    );
+                }
+
+                let stmt = Stmt::Import {
+                    span,
+                    path,
+                    scope: None,
+                };
+                self.expect_stmt_end(&stmt);
+                nodes.push(stmt);
+            }
+            Token::Keyword(Keyword::Return) => {
+                let start = self.lex.start as u32;
+                self.lex.next();
+                let ret_stmt = if self.lex.has_newline_before || self.lex.token == Token::EOF {
+                    Stmt::Return {
+                        start,
+                        value: Expr::Void,
+                        tail: false,
+                    }
+                } else {
+                    Stmt::Return {
+                        start,
+                        value: self.parse_expr(0)?,
+                        tail: false,
                     }
+                };
 
-                    let n = self.parse_expr(0)?;
-                    self.eat(Token::Keyword(Keyword::Times))?;
-                    self.eat(Token::LeftBrace)?;
-                    let scope = self.parse_scope(is_global_scope)?;
-                    self.eat(Token::RightBrace)?;
-                    nodes.push(Stmt::RepeatN {
-                        n: Box::new(n),
-                        scope,
-                    });
+                if is_global_scope && !self.script {
+                    self.diagnostics.push(
+                        Diagnostic::error()
+                            .with_message("RETURN statements cannot be outside of function scopes")
+                            .with_labels(vec![Label::primary(self.fid, ret_stmt.span())
+                                .with_message("RETURN not in function scope")]),
+                    );
                 }
-                Token::Keyword(Keyword::For) => {
+
+                self.expect_stmt_end(&ret_stmt);
+
+                nodes.push(ret_stmt);
+            }
+            Token::Keyword(Keyword::Repeat) => 'blk: {
+                self.lex.next();
+
+                self.keyword_case(Token::Keyword(Keyword::Until));
+                if let Token::Keyword(Keyword::Until) = self.lex.token {
                     self.lex.next();
-                    self.eat(Token::Keyword(Keyword::Each))?;
-                    let alias = self.eat(Token::Identifier)?;
-                    self.eat(Token::Keyword(Keyword::In))?;
-                    let array = self.parse_expr(0)?;
-                    self.eat(Token::LeftBrace)?;
-                    let scope = self.parse_scope(is_global_scope)?;
-                    self.eat(Token::RightBrace)?;
-                    nodes.push(Stmt::For {
-                        alias,
-                        array: Box::new(array),
+                    let cond = self.parse_cond("REPEAT UNTIL")?;
+                    let scope = self.parse_block(is_global_scope)?;
+                    nodes.push(Stmt::RepeatUntil {
+                        cond: Box::new(cond),
                         scope,
                     });
+                    break 'blk;
                 }
-                _ => break,
+
+                let n = self.parse_expr(0)?;
+                self.eat(Token::Keyword(Keyword::Times))?;
+                let scope = self.parse_block(is_global_scope)?;
+                nodes.push(Stmt::RepeatN {
+                    n: Box::new(n),
+                    scope,
+                });
+            }
+            Token::Keyword(Keyword::For) => {
+                self.lex.next();
+                self.eat(Token::Keyword(Keyword::Each))?;
+                let alias = self.eat(Token::Identifier)?;
+                let index = if self.lex.token == Token::Comma {
+                    self.lex.next();
+                    Some(self.eat(Token::Identifier)?)
+                } else {
+                    None
+                };
+                self.eat(Token::Keyword(Keyword::In))?;
+                let array = self.parse_expr(0)?;
+                let scope = self.parse_block(is_global_scope)?;
+                nodes.push(Stmt::For {
+                    alias,
+                    index,
+                    array: Box::new(array),
+                    scope,
+                });
             }
+            _ => return Ok(false),
         }
 
-        Ok(nodes.into_boxed_slice())
+        Ok(true)
     }
 }
+
+/// Marks the RETURNs that are the last statement of `scope`, a PROCEDURE's
+/// body, looking into the blocks of an IF that ends it.
+fn mark_tail_returns(scope: &mut [Stmt]) {
+    match scope.last_mut() {
+        Some(Stmt::Return { tail, .. }) => *tail = true,
+        Some(Stmt::If {
+            scope,
+            else_ifs,
+            els,
+            ..
+        }) => {
+            mark_tail_returns(scope);
+            for else_if in else_ifs.iter_mut() {
+                mark_tail_returns(&mut else_if.scope);
+            }
+            if let Some(els) = els {
+                mark_tail_returns(els);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The first RETURN in `scope` or its blocks, not counting those in nested
+/// PROCEDUREs.
+fn find_return(scope: &[Stmt]) -> Option<&Stmt> {
+    scope.iter().find_map(|stmt| match stmt {
+        Stmt::Return { .. } => Some(stmt),
+        Stmt::Procedure(_) => None,
+        stmt => stmt.scopes().into_iter().find_map(find_return),
+    })
+}
@@ -0,0 +1,97 @@
+//! What builtins that reach outside the program, such as to files, are
+//! allowed to do.
+//!
+//! Everything is denied unless the host allows it, so untrusted programs can
+//! be run with only the builtins that cannot affect the machine. Builtins with
+//! such side effects check [`VM::permissions`](crate::vm::VM::permissions)
+//! before acting and return the exception it gives back when denied.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::{fail, vm::Value};
+
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    /// Directories whose files, including those in subdirectories, may be
+    /// read.
+    pub read: Vec<PathBuf>,
+    /// Directories whose files, including those in subdirectories, may be
+    /// created or written.
+    pub write: Vec<PathBuf>,
+}
+
+impl Permissions {
+    /// Permissions that allow everything, for running trusted programs.
+    pub fn allow_all() -> Self {
+        Self {
+            read: vec![PathBuf::from("/")],
+            write: vec![PathBuf::from("/")],
+        }
+    }
+
+    /// Fails without a location, like a builtin, unless `path` is in one of
+    /// the [`Permissions::read`] directories.
+    pub fn check_read(&self, path: &Path) -> Value {
+        if !within(&self.read, path) {
+            fail!(
+                format!("the program is not allowed to read {}", path.display()),
+                BUILTIN
+            );
+        }
+
+        Value::Void
+    }
+
+    /// Fails without a location, like a builtin, unless `path` is in one of
+    /// the [`Permissions::write`] directories.
+    pub fn check_write(&self, path: &Path) -> Value {
+        if !within(&self.write, path) {
+            fail!(
+                format!("the program is not allowed to write {}", path.display()),
+                BUILTIN
+            );
+        }
+
+        Value::Void
+    }
+}
+
+/// Whether `path` is inside one of `roots` once both are made absolute and
+/// `..` is resolved, so `root/../secret` does not count as inside `root`.
+/// Symbolic links are followed where the path exists.
+fn within(roots: &[PathBuf], path: &Path) -> bool {
+    let Some(path) = resolve(path) else {
+        return false;
+    };
+
+    roots
+        .iter()
+        .filter_map(|root| resolve(root))
+        .any(|root| path.starts_with(root))
+}
+
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+
+    // A file that does not exist yet, such as one about to be written, is
+    // resolved through its closest existing parent. That parent is resolved
+    // first, since a `..` after a symbolic link leaves the link's target
+    // rather than the link. Only the part that does not exist, and so has no
+    // links, is resolved by hand.
+    let path = std::path::absolute(path).ok()?;
+    let existing = path.ancestors().find(|parent| parent.exists())?;
+    let mut resolved = existing.canonicalize().ok()?;
+    for component in path.strip_prefix(existing).ok()?.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+
+    Some(resolved)
+}
@@ -0,0 +1,98 @@
+use wasm_bindgen::prelude::*;
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Drawing on the web playground's canvas. The page provides the drawing
+/// functions as `globalThis.apsCanvas`.
+pub const MODULE: Module = Module {
+    name: "canvas",
+    builtins: &[
+        ("DRAW_RECT", draw_rect as BuiltinPtr),
+        ("DRAW_CIRCLE", draw_circle),
+        ("DRAW_LINE", draw_line),
+        ("CLEAR_CANVAS", clear_canvas),
+    ],
+};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_namespace = apsCanvas, js_name = drawRect)]
+    fn js_draw_rect(x: f64, y: f64, width: f64, height: f64, color: &str) -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = apsCanvas, js_name = drawCircle)]
+    fn js_draw_circle(x: f64, y: f64, radius: f64, color: &str) -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = apsCanvas, js_name = drawLine)]
+    fn js_draw_line(x1: f64, y1: f64, x2: f64, y2: f64, color: &str) -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = apsCanvas, js_name = clear)]
+    fn js_clear() -> Result<(), JsValue>;
+}
+
+/// Reads the numbers at the start of `args` into `out` and the color after
+/// them, which defaults to black.
+fn read_args<const N: usize>(args: &[Value], out: &mut [f64; N], color: &mut String) -> Value {
+    for (idx, n) in out.iter_mut().enumerate() {
+        let Some(&Value::Number(arg)) = args.get(idx) else {
+            fail!(format!("expected number for argument {}", idx + 1), ARG idx);
+        };
+        *n = arg;
+    }
+
+    *color = match args.get(N) {
+        Some(Value::String(s)) => s.as_str().to_string(),
+        Some(_) => fail!(
+            format!("expected color string for argument {}", N + 1),
+            ARG N
+        ),
+        None => "black".into(),
+    };
+
+    Value::Void
+}
+
+fn drawn(result: Result<(), JsValue>) -> Value {
+    match result {
+        Ok(()) => Value::Void,
+        Err(_) => fail!("this page has no canvas to draw on", BUILTIN),
+    }
+}
+
+/// `DRAW_RECT(x, y, width, height, color)` fills a rectangle whose top left
+/// corner is at `(x, y)`.
+fn draw_rect(_: &mut VM, args: &[Value]) -> Value {
+    let (mut n, mut color) = ([0.; 4], String::new());
+    tee!(read_args(args, &mut n, &mut color));
+
+    drawn(js_draw_rect(n[0], n[1], n[2], n[3], &color))
+}
+
+/// `DRAW_CIRCLE(x, y, radius, color)` fills a circle centered at `(x, y)`.
+fn draw_circle(_: &mut VM, args: &[Value]) -> Value {
+    let (mut n, mut color) = ([0.; 3], String::new());
+    tee!(read_args(args, &mut n, &mut color));
+
+    if n[2] < 0. {
+        fail!(format!("{} is not a valid radius", n[2]), BUILTIN);
+    }
+
+    drawn(js_draw_circle(n[0], n[1], n[2], &color))
+}
+
+/// `DRAW_LINE(x1, y1, x2, y2, color)` draws a line from `(x1, y1)` to
+/// `(x2, y2)`.
+fn draw_line(_: &mut VM, args: &[Value]) -> Value {
+    let (mut n, mut color) = ([0.; 4], String::new());
+    tee!(read_args(args, &mut n, &mut color));
+
+    drawn(js_draw_line(n[0], n[1], n[2], n[3], &color))
+}
+
+/// Erases everything drawn on the canvas.
+fn clear_canvas(_: &mut VM, _: &[Value]) -> Value {
+    drawn(js_clear())
+}
@@ -0,0 +1,76 @@
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Stack and queue operations over lists.
+pub const MODULE: Module = Module {
+    name: "collections",
+    builtins: &[
+        ("PUSH", push as BuiltinPtr),
+        ("POP", pop),
+        ("PEEK", peek),
+        ("ENQUEUE", push),
+        ("DEQUEUE", dequeue),
+    ],
+};
+
+/// Adds a value to the end of the list. O(1).
+fn push(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(val) = args.get(1) else {
+        fail!("expected value for the second argument", ARG 1);
+    };
+
+    let items = &mut array.borrow_mut().items;
+    tee!(vm.add_items(items.len() + 1, 1));
+    items.push(val.clone());
+
+    Value::Void
+}
+
+/// Removes and returns the last value of the list. O(1).
+fn pop(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(val) = array.borrow_mut().items.pop() else {
+        fail!("cannot POP from an empty list", BUILTIN);
+    };
+
+    val
+}
+
+/// Returns the last value of the list without removing it. O(1).
+fn peek(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(val) = array.borrow().items.last().cloned() else {
+        fail!("cannot PEEK into an empty list", BUILTIN);
+    };
+
+    val
+}
+
+/// Removes and returns the first value of the list. O(n) because the
+/// remaining values shift left.
+fn dequeue(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let items = &mut array.borrow_mut().items;
+
+    if items.is_empty() {
+        fail!("cannot DEQUEUE from an empty list", BUILTIN);
+    }
+
+    items.remove(0)
+}
@@ -0,0 +1,388 @@
+#[cfg(feature = "js")]
+use std::fmt::Write as WriteFmt;
+#[cfg(not(feature = "js"))]
+use std::io::Write;
+
+use codespan_reporting::term::termcolor::Color;
+#[cfg(not(feature = "js"))]
+use codespan_reporting::term::termcolor::{Ansi, ColorSpec, WriteColor};
+use gc::{Gc, GcCell};
+
+use super::{validate_index, Module};
+use crate::{
+    fail, tee,
+    vm::{Array, BuiltinPtr, Str, Value, VM},
+};
+
+/// Console I/O and the list and text procedures from the AP reference sheet.
+pub const MODULE: Module = Module {
+    name: "core",
+    builtins: &[
+        ("DISPLAY", display as BuiltinPtr),
+        ("DISPLAY_COLOR", display_color),
+        ("INPUT", input),
+        ("APPEND", append),
+        ("INSERT", insert),
+        ("REMOVE", remove),
+        ("LENGTH", length),
+        ("SUBSTRING", substring),
+        ("CONCAT", concat),
+        ("NUMBER", number),
+    ],
+};
+
+#[cfg(not(feature = "js"))]
+fn display_helper(out: &mut dyn Write, args: &[Value]) -> Value {
+    let mut iter = args.iter();
+    if let Some(arg0) = iter.next() {
+        let Ok(_) = write!(out, "{}", arg0) else {
+            fail!("failed to write output", BUILTIN);
+        };
+        for arg in iter {
+            let Ok(_) = write!(out, " {}", arg) else {
+                fail!("failed to write output", BUILTIN);
+            };
+        }
+    }
+    Value::Void
+}
+
+#[cfg(feature = "js")]
+fn display_helper(out: &mut String, args: &[Value]) -> Value {
+    let mut iter = args.iter();
+    if let Some(arg0) = iter.next() {
+        write!(out, "{}", arg0).unwrap();
+        for arg in iter {
+            write!(out, " {}", arg).unwrap();
+        }
+    }
+    Value::Void
+}
+
+#[cfg(not(feature = "js"))]
+fn display(vm: &mut VM, args: &[Value]) -> Value {
+    let out = &mut vm.output;
+    _ = tee!(display_helper(out, args));
+    let Ok(_) = writeln!(out) else {
+        fail!("failed to write output", BUILTIN);
+    };
+    let Ok(_) = out.flush() else {
+        fail!("failed to flush output", BUILTIN);
+    };
+    Value::Void
+}
+
+#[cfg(feature = "js")]
+fn display(vm: &mut VM, args: &[Value]) -> Value {
+    let mut out = String::new();
+    _ = tee!(display_helper(&mut out, args));
+    match &mut vm.on_display {
+        Some(on_display) => on_display(&out),
+        None => web_sys::console::log_1(&out.as_str().into()),
+    }
+    Value::Void
+}
+
+/// Reads the color named by the first argument.
+fn color_arg(args: &[Value], out: &mut Color) -> Value {
+    let Some(Value::String(name)) = args.first() else {
+        fail!("expected color name for the first argument", ARG 0);
+    };
+
+    let Ok(color) = name.as_str().parse() else {
+        fail!(
+            format!(
+                "{:?} is not a color; expected black, red, green, yellow, blue, magenta, cyan, or white",
+                name.as_str()
+            ),
+            BUILTIN
+        );
+    };
+
+    *out = color;
+    Value::Void
+}
+
+/// Like `DISPLAY`, but in the color named by the first argument. Color is
+/// left out when the output is not a terminal.
+#[cfg(not(feature = "js"))]
+fn display_color(vm: &mut VM, args: &[Value]) -> Value {
+    let mut color = Color::White;
+    tee!(color_arg(args, &mut color));
+
+    if vm.color {
+        let mut out = Ansi::new(&mut vm.output);
+        let Ok(_) = out.set_color(ColorSpec::new().set_fg(Some(color))) else {
+            fail!("failed to write output", BUILTIN);
+        };
+        tee!(display_helper(&mut out, &args[1..]));
+        let Ok(_) = out.reset() else {
+            fail!("failed to write output", BUILTIN);
+        };
+    } else {
+        tee!(display_helper(&mut vm.output, &args[1..]));
+    }
+
+    // Ends the line.
+    display(vm, &[])
+}
+
+#[cfg(feature = "js")]
+fn display_color(vm: &mut VM, args: &[Value]) -> Value {
+    let mut color = Color::White;
+    tee!(color_arg(args, &mut color));
+
+    display(vm, &args[1..])
+}
+
+#[cfg(not(feature = "js"))]
+fn input(vm: &mut VM, args: &[Value]) -> Value {
+    let mut prompt = Vec::new();
+    _ = tee!(display_helper(&mut prompt, args));
+    let prompt = String::from_utf8_lossy(&prompt);
+
+    let stdout = &mut vm.output;
+    let shown = if args.is_empty() { "Input:" } else { &prompt };
+    let Ok(_) = write!(stdout, "{shown} ") else {
+        fail!("failed to write output", BUILTIN);
+    };
+
+    let out = if let Some(provider) = &mut vm.input {
+        let Some(answer) = provider.answer(&prompt) else {
+            fail!("INPUT was called but there are no responses left", BUILTIN);
+        };
+
+        // Echo the scripted answer so the transcript reads like a terminal session.
+        let Ok(_) = writeln!(stdout, "{answer}") else {
+            fail!("failed to write output", BUILTIN);
+        };
+        answer
+    } else {
+        let Ok(_) = stdout.flush() else {
+            fail!("failed to flush output", BUILTIN);
+        };
+
+        let mut out = String::new();
+        let Ok(_) = std::io::stdin().read_line(&mut out) else {
+            fail!("failed to read line from stdout", BUILTIN);
+        };
+        out
+    };
+
+    let outs = out.trim();
+
+    if let Ok(f) = outs.parse() {
+        Value::Number(f)
+    } else {
+        Value::String(Gc::new(Str::new(outs)))
+    }
+}
+
+#[cfg(feature = "js")]
+fn input(vm: &mut VM, args: &[Value]) -> Value {
+    use gc::Gc;
+
+    let mut out = String::new();
+    _ = tee!(display_helper(&mut out, args));
+
+    let msg = if let Some(provider) = &mut vm.input {
+        let Some(answer) = provider.answer(&out) else {
+            fail!("INPUT was called but there are no responses left", BUILTIN);
+        };
+        answer
+    } else {
+        let Some(window) = web_sys::window() else {
+            fail!("failed to prompt user", BUILTIN);
+        };
+
+        let Ok(Some(msg)) = window.prompt_with_message(&out) else {
+            fail!("failed to prompt user", BUILTIN);
+        };
+        msg
+    };
+
+    let outs = msg.trim();
+
+    if let Ok(f) = outs.parse() {
+        Value::Number(f)
+    } else {
+        Value::String(Gc::new(Str::new(outs)))
+    }
+}
+
+fn append(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(val) = args.get(1) else {
+        fail!("expected value for the second argument", ARG 1);
+    };
+
+    let items = &mut array.borrow_mut().items;
+    tee!(vm.add_items(items.len() + 1, 1));
+    items.push(val.clone());
+
+    Value::Void
+}
+
+fn insert(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(Value::Number(idx)) = args.get(1) else {
+        fail!("expected index for the second argument", ARG 1);
+    };
+
+    let Some(val) = args.get(2) else {
+        fail!("expected value for the third argument", ARG 2);
+    };
+
+    let mut correct_idx = 0;
+    _ = tee!(validate_index(*idx, 1, &mut correct_idx));
+
+    let items = &mut array.borrow_mut().items;
+
+    if correct_idx > items.len() {
+        fail!("array index out of range", ARG 1);
+    }
+
+    tee!(vm.add_items(items.len() + 1, 1));
+    items.insert(correct_idx - 1, val.clone());
+
+    Value::Void
+}
+
+fn remove(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(Value::Number(idx)) = args.get(1) else {
+        fail!("expected number for the second argument", ARG 1);
+    };
+
+    let mut correct_idx = 0;
+    _ = tee!(validate_index(*idx, 1, &mut correct_idx));
+
+    let items = &mut array.borrow_mut().items;
+
+    if correct_idx > items.len() {
+        fail!("array index out of range", ARG 1);
+    }
+
+    items.remove(correct_idx - 1);
+
+    Value::Void
+}
+
+/// The number of items in a list or set, of keys in a map, or of characters in
+/// a string.
+fn length(_: &mut VM, args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::Array(array)) => Value::Number(array.borrow().items.len() as f64),
+        Some(Value::Set(set)) => Value::Number(set.borrow().items.len() as f64),
+        Some(Value::Map(map)) => Value::Number(map.borrow().entries.len() as f64),
+        Some(Value::String(s)) => Value::Number(s.char_len() as f64),
+        _ => fail!("expected the first argument to be an array or a string", ARG 0),
+    }
+}
+
+/// The characters of `args[0]` from position `args[1]` through `args[2]`,
+/// counting from 1 like list indices. An end right before the start gives an
+/// empty string.
+fn substring(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::String(s)) = args.first() else {
+        fail!("expected a string for the first argument", ARG 0);
+    };
+
+    let Some(&Value::Number(start)) = args.get(1) else {
+        fail!("expected a number for the second argument", ARG 1);
+    };
+
+    let Some(&Value::Number(end)) = args.get(2) else {
+        fail!("expected a number for the third argument", ARG 2);
+    };
+
+    for (n, arg) in [(start, 1), (end, 2)] {
+        if n.floor() != n {
+            fail!(format!("{n} is not an integer"), ARG arg);
+        }
+    }
+
+    let len = s.char_len();
+    if start < 1. || start > len as f64 + 1. {
+        fail!(
+            format!("start {start} is out of string range (length: {len})"),
+            BUILTIN,
+            (start == 0.).then(|| "strings start at position 1, like lists".into())
+        );
+    }
+    if end > len as f64 {
+        fail!(
+            format!("end {end} is out of string range (length: {len})"),
+            BUILTIN
+        );
+    }
+    if end < start - 1. {
+        fail!(format!("end {end} is before start {start}"), BUILTIN);
+    }
+
+    let (start, end) = (start as usize, end as usize);
+    s.slice(start - 1, end).unwrap_or_default().into()
+}
+
+/// Converts text like `"42"` to a number.
+fn number(_: &mut VM, args: &[Value]) -> Value {
+    match args.first() {
+        Some(Value::Number(n)) => Value::Number(*n),
+        Some(Value::String(s)) => match s.as_str().trim().parse() {
+            Ok(n) => Value::Number(n),
+            Err(_) => fail!(format!("{:?} is not a number", s.as_str()), ARG 0),
+        },
+        Some(value) => fail!(format!("{value:?} is not a number"), ARG 0),
+        None => fail!("expected a value for the first argument", ARG 0),
+    }
+}
+
+/// Joins two strings, or two lists into a new list, leaving both arguments
+/// as they were.
+fn concat(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(first) = args.first() else {
+        fail!("expected a string or a list for the first argument", ARG 0);
+    };
+
+    let Some(second) = args.get(1) else {
+        fail!("expected a value for the second argument", ARG 1);
+    };
+
+    match (first, second) {
+        (Value::String(a), Value::String(b)) => {
+            _ = tee!(vm.check_string_len(a.char_len() + b.char_len()));
+            Value::String(Gc::new(Str::new(a.as_str().to_string() + b.as_str())))
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let items = a
+                .borrow()
+                .items
+                .iter()
+                .chain(b.borrow().items.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            tee!(vm.add_items(items.len(), items.len()));
+            Value::Array(Gc::new(GcCell::new(Array { items })))
+        }
+        (Value::String(_), _) => fail!(
+            format!("cannot join a string with {second:?}"),
+            ARG 1,
+            Some("both arguments must be strings or both must be lists".into())
+        ),
+        (Value::Array(_), _) => fail!(
+            format!("cannot join a list with {second:?}"),
+            ARG 1,
+            Some("both arguments must be strings or both must be lists".into())
+        ),
+        _ => fail!("expected a string or a list for the first argument", ARG 0),
+    }
+}
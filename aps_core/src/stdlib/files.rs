@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use gc::Gc;
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Str, Value, VM},
+};
+
+/// Reading and writing text files, as far as
+/// [`VM::permissions`](crate::vm::VM::permissions) allows. Nothing is allowed
+/// by default.
+pub const MODULE: Module = Module {
+    name: "files",
+    builtins: &[
+        ("READ_FILE", read_file as BuiltinPtr),
+        ("WRITE_FILE", write_file),
+        ("APPEND_FILE", append_file),
+        ("FILE_EXISTS", file_exists),
+    ],
+};
+
+/// Reads the path in the first argument into `out`.
+fn path_arg<'a>(args: &'a [Value], out: &mut &'a Path) -> Value {
+    let Some(Value::String(path)) = args.first() else {
+        fail!("expected a path for the first argument", ARG 0);
+    };
+
+    *out = Path::new(path.as_str());
+    Value::Void
+}
+
+/// Reads the text in the second argument into `out`. Values other than strings
+/// are written as DISPLAY shows them.
+fn text_arg(args: &[Value], out: &mut String) -> Value {
+    *out = match args.get(1) {
+        Some(Value::String(s)) => s.as_str().to_string(),
+        Some(Value::Exception(_)) | None => {
+            fail!("expected a value for the second argument", ARG 1)
+        }
+        Some(value) => value.to_string(),
+    };
+
+    Value::Void
+}
+
+/// `READ_FILE(path)` returns the text of the file at `path`.
+fn read_file(vm: &mut VM, args: &[Value]) -> Value {
+    let mut path = Path::new("");
+    _ = tee!(path_arg(args, &mut path));
+    _ = tee!(vm.permissions.check_read(path));
+
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => fail!(format!("cannot read \"{}\": {e}", path.display()), ARG 0),
+    };
+
+    // The file is read a piece at a time so that one too long to be a string
+    // fails before all of it is in memory.
+    let mut bytes = Vec::new();
+    let mut chars = 0;
+    let mut chunk = [0; 8192];
+    loop {
+        let read = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => fail!(format!("cannot read \"{}\": {e}", path.display()), ARG 0),
+        };
+        // Every character starts with a byte that is not `0b10xxxxxx`.
+        chars += chunk[..read]
+            .iter()
+            .filter(|&&byte| byte & 0xC0 != 0x80)
+            .count();
+        _ = tee!(vm.check_string_len(chars));
+        bytes.extend_from_slice(&chunk[..read]);
+    }
+
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => fail!(format!("cannot read \"{}\": it is not UTF-8 text", path.display()), ARG 0),
+    };
+
+    Value::String(Gc::new(Str::new(text)))
+}
+
+/// `WRITE_FILE(path, text)` replaces the contents of the file at `path`,
+/// creating it if it does not exist.
+fn write_file(vm: &mut VM, args: &[Value]) -> Value {
+    let mut path = Path::new("");
+    _ = tee!(path_arg(args, &mut path));
+    let mut text = String::new();
+    _ = tee!(text_arg(args, &mut text));
+    _ = tee!(vm.permissions.check_write(path));
+
+    if let Err(e) = fs::write(path, text) {
+        fail!(format!("cannot write \"{}\": {e}", path.display()), ARG 0);
+    }
+
+    Value::Void
+}
+
+/// `APPEND_FILE(path, text)` adds to the end of the file at `path`, creating
+/// it if it does not exist.
+fn append_file(vm: &mut VM, args: &[Value]) -> Value {
+    let mut path = Path::new("");
+    _ = tee!(path_arg(args, &mut path));
+    let mut text = String::new();
+    _ = tee!(text_arg(args, &mut text));
+    _ = tee!(vm.permissions.check_write(path));
+
+    let appended = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(text.as_bytes()));
+    if let Err(e) = appended {
+        fail!(format!("cannot write \"{}\": {e}", path.display()), ARG 0);
+    }
+
+    Value::Void
+}
+
+/// `FILE_EXISTS(path)` returns whether there is a file at `path`.
+fn file_exists(vm: &mut VM, args: &[Value]) -> Value {
+    let mut path = Path::new("");
+    _ = tee!(path_arg(args, &mut path));
+    _ = tee!(vm.permissions.check_read(path));
+
+    Value::Bool(path.is_file())
+}
@@ -0,0 +1,168 @@
+use gc::{Gc, GcCell};
+
+use super::{arg_error, validate_index, Module};
+use crate::{
+    fail, tee,
+    vm::{Array, BuiltinPtr, Value, MAX_LEN, VM},
+};
+
+/// Helpers for 2D grids stored as lists of row lists.
+pub const MODULE: Module = Module {
+    name: "grid",
+    builtins: &[
+        ("MAKE_GRID", make_grid as BuiltinPtr),
+        ("ROWS", rows),
+        ("COLS", cols),
+        ("GET_CELL", get_cell),
+        ("SET_CELL", set_cell),
+    ],
+};
+
+fn validate_size(n: f64, arg: usize, what: &str) -> Value {
+    if n.floor() != n || n < 0. {
+        fail!(format!("{what} must be a whole number, found {n}"), ARG arg);
+    }
+
+    Value::Void
+}
+
+/// Resolves `grid[row]` after bounds checking.
+fn get_row(grid: &Value, row: &Value) -> Result<Gc<GcCell<Array>>, Value> {
+    let Value::Array(grid) = grid else {
+        return Err(arg_error("expected a grid for the first argument", 0));
+    };
+
+    let Value::Number(row) = row else {
+        return Err(arg_error("expected a number for the row", 1));
+    };
+
+    let mut idx = 0;
+    if let e @ Value::Exception(_) = validate_index(*row, 1, &mut idx) {
+        return Err(e);
+    }
+
+    let grid = grid.borrow();
+    let Some(item) = grid.items.get(idx - 1) else {
+        return Err(arg_error(
+            format!("row {row} is out of range (rows: {})", grid.items.len()),
+            1,
+        ));
+    };
+
+    let Value::Array(item) = item else {
+        return Err(arg_error(format!("row {row} is not a list"), 0));
+    };
+
+    Ok(item.clone())
+}
+
+fn validate_column(row: &Array, col: &Value, out: &mut usize) -> Value {
+    let Value::Number(col) = col else {
+        fail!("expected a number for the column", ARG 2);
+    };
+
+    _ = tee!(validate_index(*col, 2, out));
+
+    if *out > row.items.len() {
+        fail!(
+            format!(
+                "column {col} is out of range (columns: {})",
+                row.items.len()
+            ),
+            ARG 2
+        );
+    }
+
+    Value::Void
+}
+
+fn make_grid(vm: &mut VM, args: &[Value]) -> Value {
+    let (Some(Value::Number(rows)), Some(Value::Number(cols))) = (args.first(), args.get(1)) else {
+        fail!("expected the number of rows and columns", BUILTIN);
+    };
+
+    let Some(fill) = args.get(2) else {
+        fail!("expected a fill value for the third argument", ARG 2);
+    };
+
+    _ = tee!(validate_size(*rows, 0, "the number of rows"));
+    _ = tee!(validate_size(*cols, 1, "the number of columns"));
+
+    // Checked before building the grid, which could otherwise exhaust memory.
+    let (rows, cols) = (*rows as usize, *cols as usize);
+    if rows.saturating_mul(cols) > MAX_LEN {
+        fail!(
+            format!("a grid cannot hold more than {MAX_LEN} cells"),
+            BUILTIN
+        );
+    }
+    tee!(vm.add_items(rows, rows));
+    tee!(vm.add_items(cols, rows.saturating_mul(cols)));
+
+    let items = (0..rows)
+        .map(|_| {
+            Value::Array(Gc::new(GcCell::new(Array {
+                items: vec![fill.clone(); cols],
+            })))
+        })
+        .collect();
+
+    Value::Array(Gc::new(GcCell::new(Array { items })))
+}
+
+fn rows(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(grid)) = args.first() else {
+        fail!("expected a grid for the first argument", ARG 0);
+    };
+
+    Value::Number(grid.borrow().items.len() as f64)
+}
+
+fn cols(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(grid)) = args.first() else {
+        fail!("expected a grid for the first argument", ARG 0);
+    };
+
+    match grid.borrow().items.first() {
+        Some(Value::Array(row)) => Value::Number(row.borrow().items.len() as f64),
+        Some(_) => fail!("row 1 is not a list", ARG 0),
+        None => Value::Number(0.),
+    }
+}
+
+fn get_cell(_: &mut VM, args: &[Value]) -> Value {
+    let (Some(grid), Some(row), Some(col)) = (args.first(), args.get(1), args.get(2)) else {
+        fail!("expected a grid, a row, and a column", BUILTIN);
+    };
+
+    let row = match get_row(grid, row) {
+        Ok(row) => row,
+        Err(e) => return e,
+    };
+    let row = row.borrow();
+
+    let mut idx = 0;
+    _ = tee!(validate_column(&row, col, &mut idx));
+
+    row.items[idx - 1].clone()
+}
+
+fn set_cell(_: &mut VM, args: &[Value]) -> Value {
+    let (Some(grid), Some(row), Some(col), Some(value)) =
+        (args.first(), args.get(1), args.get(2), args.get(3))
+    else {
+        fail!("expected a grid, a row, a column, and a value", BUILTIN);
+    };
+
+    let row = match get_row(grid, row) {
+        Ok(row) => row,
+        Err(e) => return e,
+    };
+    let mut row = row.borrow_mut();
+
+    let mut idx = 0;
+    _ = tee!(validate_column(&row, col, &mut idx));
+
+    row.items[idx - 1] = value.clone();
+    Value::Void
+}
@@ -0,0 +1,81 @@
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Maps from numbers, strings, and booleans to values, written `{key: value}`.
+pub const MODULE: Module = Module {
+    name: "maps",
+    builtins: &[
+        ("KEYS", keys as BuiltinPtr),
+        ("VALUES", values),
+        ("CONTAINS_KEY", contains_key),
+        ("REMOVE_KEY", remove_key),
+    ],
+};
+
+/// A new list of the map's keys, in the order they were added.
+fn keys(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Map(map)) = args.first() else {
+        fail!("expected map for the first argument", ARG 0);
+    };
+
+    let keys: Vec<_> = map
+        .borrow()
+        .entries
+        .iter()
+        .map(|(key, _)| key.clone())
+        .collect();
+    tee!(vm.add_items(keys.len(), keys.len()));
+    keys.into()
+}
+
+/// A new list of the map's values, in the order their keys were added.
+fn values(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Map(map)) = args.first() else {
+        fail!("expected map for the first argument", ARG 0);
+    };
+
+    let values: Vec<_> = map
+        .borrow()
+        .entries
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect();
+    tee!(vm.add_items(values.len(), values.len()));
+    values.into()
+}
+
+fn contains_key(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Map(map)) = args.first() else {
+        fail!("expected map for the first argument", ARG 0);
+    };
+
+    let Some(key) = args.get(1) else {
+        fail!("expected key for the second argument", ARG 1);
+    };
+
+    Value::Bool(map.borrow().contains_key(key))
+}
+
+/// Removes the key and its value from the map.
+fn remove_key(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Map(map)) = args.first() else {
+        fail!("expected map for the first argument", ARG 0);
+    };
+
+    let Some(key) = args.get(1) else {
+        fail!("expected key for the second argument", ARG 1);
+    };
+
+    if map.borrow_mut().remove(key).is_none() {
+        fail!(
+            format!("key {key:?} is not in the map"),
+            ARG 1,
+            Some("check with `CONTAINS_KEY(map, key)` first".into())
+        );
+    }
+
+    Value::Void
+}
@@ -0,0 +1,182 @@
+use gc::Gc;
+#[cfg(not(feature = "js"))]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Str, Value, VM},
+};
+
+/// Numeric procedures.
+pub const MODULE: Module = Module {
+    name: "math",
+    builtins: &[
+        ("RANDOM", random as BuiltinPtr),
+        ("TO_BINARY", to_binary),
+        ("TO_HEX", to_hex),
+        ("FROM_BINARY", from_binary),
+        ("FROM_HEX", from_hex),
+        ("GCD", gcd),
+        ("LCM", lcm),
+    ],
+};
+
+#[cfg(not(feature = "js"))]
+fn random(vm: &mut VM, args: &[Value]) -> Value {
+    let rng = vm.rng.get_or_insert_with(StdRng::from_entropy);
+
+    match (args.first(), args.get(1)) {
+        (None, _) => Value::Number(rng.gen::<f64>()),
+        (Some(Value::Number(n1)), Some(Value::Number(n2))) => {
+            let (start, end) = (n1.round() as i64, n2.round() as i64);
+            if start > end {
+                fail!(
+                    format!("the range start {n1} is greater than its end {n2}"),
+                    BUILTIN
+                );
+            }
+            Value::Number(rng.gen_range(start..=end) as f64)
+        }
+        _ => fail!("expected valid range start and end numbers", BUILTIN),
+    }
+}
+
+#[cfg(feature = "js")]
+fn random(_: &mut VM, args: &[Value]) -> Value {
+    let float = js_sys::Math::random();
+
+    match (args.first(), args.get(1)) {
+        (None, _) => Value::Number(float),
+        (Some(Value::Number(n1)), Some(Value::Number(n2))) => {
+            Value::Number((float * (n2 - n1 + 1.)).floor() + n1)
+        }
+        (n1, _) => {
+            let Some(Value::Number(_)) = n1 else {
+                fail!("expected a number for the first argument", ARG 0);
+            };
+
+            fail!("expected a number for the second argument", ARG 1);
+        }
+    }
+}
+
+fn to_base(args: &[Value], radix: u32) -> Value {
+    let Some(Value::Number(n)) = args.first() else {
+        fail!("expected a number for the first argument", ARG 0);
+    };
+
+    if n.floor() != *n {
+        fail!(format!("{n} is not an integer"), ARG 0);
+    }
+
+    let n = *n as i64;
+    let digits = match radix {
+        2 => format!("{:b}", n.unsigned_abs()),
+        _ => format!("{:X}", n.unsigned_abs()),
+    };
+
+    Value::String(Gc::new(Str::new(if n < 0 {
+        format!("-{digits}")
+    } else {
+        digits
+    })))
+}
+
+/// Parses `args[0]` in the given radix, allowing a sign and a `0b`/`0x`
+/// prefix.
+fn from_base(args: &[Value], radix: u32, prefix: &str, name: &str) -> Value {
+    let Some(Value::String(s)) = args.first() else {
+        fail!("expected a string for the first argument", ARG 0);
+    };
+
+    let text = s.as_str().trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let digits = digits
+        .strip_prefix(prefix)
+        .or_else(|| digits.strip_prefix(&prefix.to_uppercase()))
+        .unwrap_or(digits);
+
+    // `from_str_radix` would accept a second sign after the prefix.
+    if digits.starts_with(['+', '-']) {
+        fail!(format!("{:?} is not a {name} number", s.as_str()), ARG 0);
+    }
+
+    let Ok(n) = i64::from_str_radix(digits, radix) else {
+        fail!(format!("{:?} is not a {name} number", s.as_str()), ARG 0);
+    };
+
+    Value::Number(if negative { -n } else { n } as f64)
+}
+
+fn to_binary(_: &mut VM, args: &[Value]) -> Value {
+    to_base(args, 2)
+}
+
+fn to_hex(_: &mut VM, args: &[Value]) -> Value {
+    to_base(args, 16)
+}
+
+fn from_binary(_: &mut VM, args: &[Value]) -> Value {
+    from_base(args, 2, "0b", "binary")
+}
+
+fn from_hex(_: &mut VM, args: &[Value]) -> Value {
+    from_base(args, 16, "0x", "hex")
+}
+
+/// Reads the integers in the first two arguments.
+fn integer_pair(args: &[Value], out: &mut (u64, u64)) -> Value {
+    let (Some(&Value::Number(a)), Some(&Value::Number(b))) = (args.first(), args.get(1)) else {
+        fail!("expected two numbers", BUILTIN);
+    };
+
+    for (idx, n) in [a, b].into_iter().enumerate() {
+        if n.floor() != n {
+            fail!(format!("{n} is not an integer"), ARG idx);
+        }
+    }
+
+    *out = ((a as i64).unsigned_abs(), (b as i64).unsigned_abs());
+    Value::Void
+}
+
+fn euclid(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Returns the greatest common divisor of two integers, ignoring their signs.
+/// `GCD(0, 0)` is 0.
+fn gcd(_: &mut VM, args: &[Value]) -> Value {
+    let mut pair = (0, 0);
+    tee!(integer_pair(args, &mut pair));
+
+    Value::Number(euclid(pair.0, pair.1) as f64)
+}
+
+/// Returns the least common multiple of two integers, ignoring their signs.
+/// It is 0 if either is 0.
+fn lcm(_: &mut VM, args: &[Value]) -> Value {
+    let mut pair = (0, 0);
+    tee!(integer_pair(args, &mut pair));
+
+    let (a, b) = pair;
+    if a == 0 || b == 0 {
+        return Value::Number(0.);
+    }
+
+    let Some(lcm) = (a / euclid(a, b)).checked_mul(b) else {
+        fail!(
+            format!("the least common multiple of {a} and {b} is too large"),
+            BUILTIN
+        );
+    };
+
+    Value::Number(lcm as f64)
+}
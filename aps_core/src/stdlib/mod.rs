@@ -0,0 +1,95 @@
+use crate::{
+    fail,
+    vm::{Builtin, BuiltinPtr, Env, Value},
+};
+
+#[cfg(feature = "js")]
+pub mod canvas;
+pub mod collections;
+pub mod core;
+#[cfg(not(feature = "js"))]
+pub mod files;
+pub mod grid;
+pub mod maps;
+pub mod math;
+pub mod random;
+#[cfg(feature = "regex")]
+pub mod regex;
+pub mod robot;
+pub mod sets;
+pub mod stats;
+pub mod strings;
+#[cfg(not(feature = "js"))]
+pub mod terminal;
+pub mod turtle;
+
+/// A named group of builtins. Hosts pick the modules they want with
+/// [`register_module`], and other crates can define their own.
+#[derive(Clone, Copy)]
+pub struct Module {
+    pub name: &'static str,
+    pub builtins: &'static [(&'static str, BuiltinPtr)],
+}
+
+/// The modules registered by [`inject`].
+pub const DEFAULT_MODULES: &[Module] = &[
+    self::core::MODULE,
+    math::MODULE,
+    random::MODULE,
+    grid::MODULE,
+    collections::MODULE,
+    sets::MODULE,
+    maps::MODULE,
+    stats::MODULE,
+    strings::MODULE,
+    turtle::MODULE,
+    robot::MODULE,
+    #[cfg(feature = "regex")]
+    regex::MODULE,
+    #[cfg(feature = "js")]
+    canvas::MODULE,
+    #[cfg(not(feature = "js"))]
+    terminal::MODULE,
+    #[cfg(not(feature = "js"))]
+    files::MODULE,
+];
+
+pub fn register_module(env: &mut Env, module: Module) {
+    env.entries.extend(
+        module
+            .builtins
+            .iter()
+            .map(|&(name, ptr)| (name.into(), Value::Builtin(Builtin(ptr)))),
+    );
+}
+
+pub fn inject(env: &mut Env) {
+    for module in DEFAULT_MODULES {
+        register_module(env, *module);
+    }
+}
+
+/// The exception that `fail!(message, ARG arg)` returns, for helpers that
+/// return a `Result` with the value they read.
+fn arg_error(message: impl Into<String>, arg: usize) -> Value {
+    fail!(message.into(), ARG arg)
+}
+
+/// Checks the list index `idx`, which was given as the builtin's argument at
+/// index `arg`, and writes it into `out`.
+fn validate_index(idx: f64, arg: usize, out: &mut usize) -> Value {
+    if idx.floor() != idx {
+        fail!("array index is not an integer", ARG arg);
+    }
+
+    if idx < 1. {
+        fail!(
+            "array index out of range",
+            ARG arg,
+            (idx == 0.).then(|| "lists start at index 1, so the first item is at index 1".into())
+        );
+    }
+
+    *out = idx as usize;
+    Value::Void
+}
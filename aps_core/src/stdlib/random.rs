@@ -0,0 +1,98 @@
+#[cfg(not(feature = "js"))]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Random operations over lists. Outside the browser these draw from the VM's
+/// generator, so seeding it makes them repeatable.
+pub const MODULE: Module = Module {
+    name: "random",
+    builtins: &[
+        ("SHUFFLE", shuffle as BuiltinPtr),
+        ("CHOICE", choice),
+        ("SAMPLE", sample),
+    ],
+};
+
+/// A random integer from `0` up to but not including `n`, which must not be
+/// zero.
+#[cfg(not(feature = "js"))]
+pub(crate) fn below(vm: &mut VM, n: usize) -> usize {
+    vm.rng
+        .get_or_insert_with(StdRng::from_entropy)
+        .gen_range(0..n)
+}
+
+#[cfg(feature = "js")]
+pub(crate) fn below(_: &mut VM, n: usize) -> usize {
+    ((js_sys::Math::random() * n as f64) as usize).min(n - 1)
+}
+
+/// Puts the values of the list in a random order, in place. O(n).
+fn shuffle(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    // Fisher-Yates: each value swaps with one at or before it.
+    let items = &mut array.borrow_mut().items;
+    for i in (1..items.len()).rev() {
+        let j = below(vm, i + 1);
+        items.swap(i, j);
+    }
+
+    Value::Void
+}
+
+/// Returns a random value of the list.
+fn choice(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let len = array.borrow().items.len();
+    if len == 0 {
+        fail!("cannot CHOICE from an empty list", BUILTIN);
+    }
+
+    let idx = below(vm, len);
+    array.borrow().items[idx].clone()
+}
+
+/// Returns a new list of `n` different values of the list, in random order.
+/// O(len).
+fn sample(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    let Some(&Value::Number(n)) = args.get(1) else {
+        fail!("expected number for the second argument", ARG 1);
+    };
+
+    if n.floor() != n || n < 0. {
+        fail!(format!("{n} is not a non-negative integer"), ARG 1);
+    }
+    let mut items = array.borrow().items.clone();
+    if n as usize > items.len() {
+        fail!(
+            format!("cannot SAMPLE {n} values from a list of {}", items.len()),
+            ARG 1
+        );
+    }
+
+    // A partial Fisher-Yates shuffle that stops after the first `n` values.
+    let n = n as usize;
+    for i in 0..n {
+        let j = i + below(vm, items.len() - i);
+        items.swap(i, j);
+    }
+    items.truncate(n);
+
+    tee!(vm.add_items(n, n));
+    items.into()
+}
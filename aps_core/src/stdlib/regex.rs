@@ -0,0 +1,95 @@
+use ::regex::Regex;
+
+use super::{arg_error, Module};
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Regular expressions, using the syntax of the `regex` crate.
+pub const MODULE: Module = Module {
+    name: "regex",
+    builtins: &[
+        ("MATCHES", matches as BuiltinPtr),
+        ("MATCH_GROUPS", match_groups),
+        ("REGEX_REPLACE", regex_replace),
+    ],
+};
+
+/// Reads a string argument and a pattern argument, compiling the pattern.
+fn text_and_pattern(args: &[Value]) -> Result<(String, Regex), Value> {
+    let Some(Value::String(text)) = args.first() else {
+        return Err(arg_error("expected string for the first argument", 0));
+    };
+    let Some(Value::String(pattern)) = args.get(1) else {
+        return Err(arg_error(
+            "expected pattern string for the second argument",
+            1,
+        ));
+    };
+
+    match Regex::new(pattern.as_str()) {
+        Ok(regex) => Ok((text.as_str().into(), regex)),
+        Err(e) => {
+            // Syntax errors draw the pattern with a caret over several lines,
+            // so keep only the explanation at the end.
+            let e = e.to_string();
+            let reason = e.lines().last().unwrap_or_default();
+            Err(arg_error(
+                format!("invalid pattern: {}", reason.trim_start_matches("error: ")),
+                1,
+            ))
+        }
+    }
+}
+
+/// Whether the pattern matches anywhere in the string. Use `^` and `$` to
+/// match the whole string.
+fn matches(_: &mut VM, args: &[Value]) -> Value {
+    let (text, regex) = match text_and_pattern(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
+    };
+
+    Value::Bool(regex.is_match(&text))
+}
+
+/// The first match as a list: the matched text followed by each group, or an
+/// empty list if there is no match. Groups that did not take part in the
+/// match are empty strings.
+fn match_groups(_: &mut VM, args: &[Value]) -> Value {
+    let (text, regex) = match text_and_pattern(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
+    };
+
+    let groups = match regex.captures(&text) {
+        Some(captures) => captures
+            .iter()
+            .map(|group| group.map_or("", |group| group.as_str()).to_string())
+            .collect(),
+        None => Vec::<String>::new(),
+    };
+
+    groups.into()
+}
+
+/// Replaces every match of the pattern. `$1` in the replacement stands for the
+/// first group, and so on.
+fn regex_replace(vm: &mut VM, args: &[Value]) -> Value {
+    let (text, regex) = match text_and_pattern(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
+    };
+
+    let Some(Value::String(replacement)) = args.get(2) else {
+        fail!("expected string for the third argument", ARG 2);
+    };
+
+    // How long the result is depends on the matches, so it is only checked
+    // once built.
+    let replaced = regex.replace_all(&text, replacement.as_str()).into_owned();
+    _ = tee!(vm.check_string_len(replaced.chars().count()));
+
+    replaced.into()
+}
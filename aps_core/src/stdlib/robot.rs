@@ -0,0 +1,267 @@
+use std::fmt::{self, Display};
+
+use super::Module;
+use crate::{
+    fail,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// The robot from AP exam questions, which moves between the squares of a
+/// grid. The grid is kept on the VM as a [`Robot`], loaded by the host or with
+/// `ROBOT_GRID`.
+pub const MODULE: Module = Module {
+    name: "robot",
+    builtins: &[
+        ("MOVE_FORWARD", move_forward as BuiltinPtr),
+        ("ROTATE_LEFT", rotate_left),
+        ("ROTATE_RIGHT", rotate_right),
+        ("CAN_MOVE", can_move),
+        ("ROBOT_GRID", robot_grid),
+    ],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    /// The direction after turning right `turns` times.
+    fn turn(self, turns: u8) -> Self {
+        const ORDER: [Direction; 4] = [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ];
+        ORDER[(self as usize + turns as usize) % 4]
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Self::Up => '^',
+            Self::Right => '>',
+            Self::Down => 'v',
+            Self::Left => '<',
+        }
+    }
+}
+
+/// A grid of open and blocked squares with the robot on one of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Robot {
+    /// Rows from top to bottom, where `true` is a blocked square.
+    pub blocked: Vec<Vec<bool>>,
+    /// The robot's row and column, counting from 0 at the top left.
+    pub position: (usize, usize),
+    pub facing: Direction,
+}
+
+impl Robot {
+    /// Reads a grid written one row per line, where `.` is an open square, `#`
+    /// is a blocked one, and `^`, `>`, `v`, or `<` is the robot on an open
+    /// square, facing that way. Spaces between squares and blank lines are
+    /// ignored.
+    pub fn parse(layout: &str) -> Result<Self, String> {
+        let mut blocked = Vec::new();
+        let mut robot = None;
+
+        for line in layout.lines() {
+            let mut row = Vec::new();
+            for c in line.chars().filter(|c| !c.is_whitespace()) {
+                let facing = match c {
+                    '.' => None,
+                    '#' => {
+                        row.push(true);
+                        continue;
+                    }
+                    '^' => Some(Direction::Up),
+                    '>' => Some(Direction::Right),
+                    'v' => Some(Direction::Down),
+                    '<' => Some(Direction::Left),
+                    _ => {
+                        return Err(format!(
+                            "{c:?} is not a square; use '.', '#', or one of '^', '>', 'v', and '<' for the robot"
+                        ))
+                    }
+                };
+
+                if let Some(facing) = facing {
+                    if robot.is_some() {
+                        return Err("the grid has more than one robot".into());
+                    }
+                    robot = Some(((blocked.len(), row.len()), facing));
+                }
+                row.push(false);
+            }
+
+            if row.is_empty() {
+                continue;
+            }
+            if let Some(first) = blocked
+                .first()
+                .map(Vec::len)
+                .filter(|&len| len != row.len())
+            {
+                return Err(format!(
+                    "row {} has {} squares, but the first row has {first}",
+                    blocked.len() + 1,
+                    row.len()
+                ));
+            }
+            blocked.push(row);
+        }
+
+        let Some((position, facing)) = robot else {
+            return Err("the grid has no robot".into());
+        };
+
+        Ok(Self {
+            blocked,
+            position,
+            facing,
+        })
+    }
+
+    /// The square next to the robot in `direction`, if it is on the grid.
+    fn neighbor(&self, direction: Direction) -> Option<(usize, usize)> {
+        let (row, col) = self.position;
+        let (row, col) = match direction {
+            Direction::Up => (row.checked_sub(1)?, col),
+            Direction::Right => (row, col + 1),
+            Direction::Down => (row + 1, col),
+            Direction::Left => (row, col.checked_sub(1)?),
+        };
+        self.blocked.get(row)?.get(col)?;
+        Some((row, col))
+    }
+
+    fn is_open(&self, (row, col): (usize, usize)) -> bool {
+        !self.blocked[row][col]
+    }
+}
+
+/// The grid in the format read by [`Robot::parse`].
+impl Display for Robot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (r, row) in self.blocked.iter().enumerate() {
+            for (c, &blocked) in row.iter().enumerate() {
+                let square = match blocked {
+                    _ if (r, c) == self.position => self.facing.symbol(),
+                    true => '#',
+                    false => '.',
+                };
+                write!(f, "{square}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The exception for using the robot before [`VM::robot`] has a grid.
+fn no_robot() -> Value {
+    fail!(
+        "there is no robot grid",
+        BUILTIN,
+        Some("set one up with `ROBOT_GRID(layout)` first".into())
+    )
+}
+
+/// Moves the robot one square the way it is facing.
+fn move_forward(vm: &mut VM, _: &[Value]) -> Value {
+    let Some(robot) = vm.robot.as_mut() else {
+        return no_robot();
+    };
+
+    let Some(square) = robot.neighbor(robot.facing) else {
+        fail!(
+            "the robot cannot move off the grid",
+            BUILTIN,
+            Some("check `CAN_MOVE(\"forward\")` before moving".into())
+        );
+    };
+    if !robot.is_open(square) {
+        fail!(
+            "the robot cannot move into a blocked square",
+            BUILTIN,
+            Some("check `CAN_MOVE(\"forward\")` before moving".into())
+        );
+    }
+
+    robot.position = square;
+    Value::Void
+}
+
+/// Turns the robot a quarter turn counterclockwise.
+fn rotate_left(vm: &mut VM, _: &[Value]) -> Value {
+    let Some(robot) = vm.robot.as_mut() else {
+        return no_robot();
+    };
+    robot.facing = robot.facing.turn(3);
+    Value::Void
+}
+
+/// Turns the robot a quarter turn clockwise.
+fn rotate_right(vm: &mut VM, _: &[Value]) -> Value {
+    let Some(robot) = vm.robot.as_mut() else {
+        return no_robot();
+    };
+    robot.facing = robot.facing.turn(1);
+    Value::Void
+}
+
+/// Whether the square to the robot's `"forward"`, `"backward"`, `"left"`, or
+/// `"right"` is on the grid and open.
+fn can_move(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::String(direction)) = args.first() else {
+        fail!("expected a direction for the first argument", ARG 0);
+    };
+
+    let turns = match direction.as_str().to_ascii_lowercase().as_str() {
+        "forward" => 0,
+        "right" => 1,
+        "backward" => 2,
+        "left" => 3,
+        _ => fail!(
+            format!("{direction:?} is not a direction"),
+            ARG 0,
+            Some("use \"forward\", \"backward\", \"left\", or \"right\"".into())
+        ),
+    };
+
+    let Some(robot) = &vm.robot else {
+        return no_robot();
+    };
+    let square = robot.neighbor(robot.facing.turn(turns));
+    Value::Bool(square.is_some_and(|square| robot.is_open(square)))
+}
+
+/// Replaces the grid with `layout`, either a string with a line per row or a
+/// list of rows, as read by [`Robot::parse`].
+fn robot_grid(vm: &mut VM, args: &[Value]) -> Value {
+    let layout = match args.first() {
+        Some(Value::String(layout)) => layout.as_str().to_string(),
+        Some(Value::Array(rows)) => {
+            let mut layout = String::new();
+            for row in &rows.borrow().items {
+                let Value::String(row) = row else {
+                    fail!(format!("{row:?} is not a row of the grid"), ARG 0);
+                };
+                layout.push_str(row.as_str());
+                layout.push('\n');
+            }
+            layout
+        }
+        _ => fail!("expected a layout for the first argument", ARG 0),
+    };
+
+    match Robot::parse(&layout) {
+        Ok(robot) => vm.robot = Some(robot),
+        Err(message) => fail!(message, ARG 0),
+    }
+    Value::Void
+}
@@ -0,0 +1,112 @@
+use gc::{Gc, GcCell};
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Set, Value, VM},
+};
+
+/// Sets of unique numbers, strings, and booleans.
+pub const MODULE: Module = Module {
+    name: "sets",
+    builtins: &[
+        ("SET", set as BuiltinPtr),
+        ("SET_ADD", set_add),
+        ("SET_HAS", set_has),
+        ("UNION", union),
+        ("INTERSECTION", intersection),
+    ],
+};
+
+fn set(vm: &mut VM, args: &[Value]) -> Value {
+    let mut set = Set::default();
+
+    match args.first() {
+        Some(Value::Array(array)) => {
+            for item in array.borrow().items.iter() {
+                if !set.insert(item.clone()) {
+                    fail!(
+                        format!("{item:?} cannot be stored in a set (only numbers, strings, and booleans can)"),
+                        ARG 0
+                    );
+                }
+            }
+        }
+        Some(Value::Set(other)) => set = other.borrow().clone(),
+        Some(_) => fail!("expected a list for the first argument", ARG 0),
+        None => {}
+    }
+
+    let len = set.items.len();
+    tee!(vm.add_items(len, len));
+    Value::Set(Gc::new(GcCell::new(set)))
+}
+
+fn set_add(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Set(set)) = args.first() else {
+        fail!("expected set for the first argument", ARG 0);
+    };
+
+    let Some(val) = args.get(1) else {
+        fail!("expected value for the second argument", ARG 1);
+    };
+
+    let mut set = set.borrow_mut();
+    if !set.contains(val) {
+        tee!(vm.add_items(set.items.len() + 1, 1));
+    }
+
+    if !set.insert(val.clone()) {
+        fail!(
+            format!("{val:?} cannot be stored in a set (only numbers, strings, and booleans can)"),
+            ARG 1
+        );
+    }
+
+    Value::Void
+}
+
+fn set_has(_: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::Set(set)) = args.first() else {
+        fail!("expected set for the first argument", ARG 0);
+    };
+
+    let Some(val) = args.get(1) else {
+        fail!("expected value for the second argument", ARG 1);
+    };
+
+    Value::Bool(set.borrow().contains(val))
+}
+
+fn union(vm: &mut VM, args: &[Value]) -> Value {
+    let (Some(Value::Set(a)), Some(Value::Set(b))) = (args.first(), args.get(1)) else {
+        fail!("expected two sets", BUILTIN);
+    };
+
+    let mut set = a.borrow().clone();
+    for item in b.borrow().items.iter() {
+        set.insert(item.clone());
+    }
+
+    let len = set.items.len();
+    tee!(vm.add_items(len, len));
+    Value::Set(Gc::new(GcCell::new(set)))
+}
+
+fn intersection(vm: &mut VM, args: &[Value]) -> Value {
+    let (Some(Value::Set(a)), Some(Value::Set(b))) = (args.first(), args.get(1)) else {
+        fail!("expected two sets", BUILTIN);
+    };
+
+    let b = b.borrow();
+    let mut set = Set::default();
+    for item in a.borrow().items.iter() {
+        if b.contains(item) {
+            set.insert(item.clone());
+        }
+    }
+
+    let len = set.items.len();
+    tee!(vm.add_items(len, len));
+    Value::Set(Gc::new(GcCell::new(set)))
+}
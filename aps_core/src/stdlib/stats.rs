@@ -0,0 +1,101 @@
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Descriptive statistics over lists of numbers.
+pub const MODULE: Module = Module {
+    name: "stats",
+    builtins: &[
+        ("MEAN", mean as BuiltinPtr),
+        ("MEDIAN", median),
+        ("MODE", mode),
+        ("STDEV", stdev),
+    ],
+};
+
+/// Reads the numbers of the list in `args[0]` into `out`. `name` is the
+/// builtin, used in the error for an empty list.
+fn read_numbers(args: &[Value], name: &str, out: &mut Vec<f64>) -> Value {
+    let Some(Value::Array(array)) = args.first() else {
+        fail!("expected array for the first argument", ARG 0);
+    };
+
+    for (idx, item) in array.borrow().items.iter().enumerate() {
+        let &Value::Number(n) = item else {
+            fail!(
+                format!(
+                    "expected a list of numbers, found {item:?} at index {}",
+                    idx + 1
+                ),
+                ARG 0
+            );
+        };
+        out.push(n);
+    }
+
+    if out.is_empty() {
+        fail!(format!("cannot take the {name} of an empty list"), ARG 0);
+    }
+
+    Value::Void
+}
+
+fn average(numbers: &[f64]) -> f64 {
+    numbers.iter().sum::<f64>() / numbers.len() as f64
+}
+
+/// Returns the average of the list.
+fn mean(_: &mut VM, args: &[Value]) -> Value {
+    let mut numbers = Vec::new();
+    tee!(read_numbers(args, "MEAN", &mut numbers));
+
+    Value::Number(average(&numbers))
+}
+
+/// Returns the middle value of the list once sorted, or the average of the two
+/// middle values if its length is even. O(n log n).
+fn median(_: &mut VM, args: &[Value]) -> Value {
+    let mut numbers = Vec::new();
+    tee!(read_numbers(args, "MEDIAN", &mut numbers));
+
+    numbers.sort_by(f64::total_cmp);
+    let mid = numbers.len() / 2;
+    let median = if numbers.len() % 2 == 0 {
+        (numbers[mid - 1] + numbers[mid]) / 2.
+    } else {
+        numbers[mid]
+    };
+
+    Value::Number(median)
+}
+
+/// Returns the value that appears most often in the list. Ties go to the value
+/// that appears first. O(n^2).
+fn mode(_: &mut VM, args: &[Value]) -> Value {
+    let mut numbers = Vec::new();
+    tee!(read_numbers(args, "MODE", &mut numbers));
+
+    let mut best = (numbers[0], 0);
+    for &n in &numbers {
+        let count = numbers.iter().filter(|&&other| other == n).count();
+        if count > best.1 {
+            best = (n, count);
+        }
+    }
+
+    Value::Number(best.0)
+}
+
+/// Returns the population standard deviation of the list, which divides by its
+/// length rather than one less.
+fn stdev(_: &mut VM, args: &[Value]) -> Value {
+    let mut numbers = Vec::new();
+    tee!(read_numbers(args, "STDEV", &mut numbers));
+
+    let mean = average(&numbers);
+    let variance = numbers.iter().map(|n| (n - mean).powi(2)).sum::<f64>() / numbers.len() as f64;
+
+    Value::Number(variance.sqrt())
+}
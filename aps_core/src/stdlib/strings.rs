@@ -0,0 +1,136 @@
+use gc::Gc;
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Str, Value, VM},
+};
+
+/// Text procedures.
+pub const MODULE: Module = Module {
+    name: "strings",
+    builtins: &[
+        ("REPLACE", replace as BuiltinPtr),
+        ("REPEAT_STRING", repeat_string),
+        ("PAD_LEFT", pad_left),
+        ("PAD_RIGHT", pad_right),
+    ],
+};
+
+fn replace(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::String(s)) = args.first() else {
+        fail!("expected a string for the first argument", ARG 0);
+    };
+
+    let Some(Value::String(from)) = args.get(1) else {
+        fail!("expected a string for the second argument", ARG 1);
+    };
+
+    let Some(Value::String(to)) = args.get(2) else {
+        fail!("expected a string for the third argument", ARG 2);
+    };
+
+    if from.as_str().is_empty() {
+        fail!("the text to replace cannot be empty", ARG 1);
+    }
+
+    let (s_len, from_len, to_len) = (s.char_len(), from.char_len(), to.char_len());
+    let count = s.as_str().matches(from.as_str()).count();
+    _ = tee!(vm
+        .check_string_len((s_len - count * from_len).saturating_add(count.saturating_mul(to_len))));
+
+    Value::String(Gc::new(Str::new(
+        s.as_str().replace(from.as_str(), to.as_str()),
+    )))
+}
+
+fn repeat_string(vm: &mut VM, args: &[Value]) -> Value {
+    let Some(Value::String(s)) = args.first() else {
+        fail!("expected a string for the first argument", ARG 0);
+    };
+
+    let Some(&Value::Number(n)) = args.get(1) else {
+        fail!("expected a number for the second argument", ARG 1);
+    };
+
+    if n.floor() != n || n < 0. {
+        fail!(format!("{n} is not a non-negative integer"), ARG 1);
+    }
+
+    let n = n as usize;
+    let (Some(len), Some(_)) = (
+        s.char_len().checked_mul(n),
+        s.as_str()
+            .len()
+            .checked_mul(n)
+            .filter(|&bytes| bytes <= isize::MAX as usize),
+    ) else {
+        fail!("the string would be too long", ARG 1);
+    };
+    _ = tee!(vm.check_string_len(len));
+
+    Value::String(Gc::new(Str::new(s.as_str().repeat(n))))
+}
+
+/// Pads `args[0]` with `args[2]` (a space by default) until it is `args[1]`
+/// characters long. Values other than strings are padded as DISPLAY shows
+/// them, so numbers can be lined up in tables.
+fn pad(vm: &mut VM, args: &[Value], left: bool) -> Value {
+    let (text, len) = match args.first() {
+        Some(Value::String(s)) => (s.as_str().to_string(), s.char_len()),
+        Some(Value::Exception(_)) | None => {
+            fail!("expected a value for the first argument", ARG 0)
+        }
+        Some(value) => {
+            let text = value.to_string();
+            let len = text.chars().count();
+            (text, len)
+        }
+    };
+
+    let Some(&Value::Number(width)) = args.get(1) else {
+        fail!("expected a number for the second argument", ARG 1);
+    };
+
+    if width.floor() != width {
+        fail!(format!("{width} is not an integer"), ARG 1);
+    }
+
+    let fill = match args.get(2) {
+        None => ' ',
+        Some(Value::String(fill)) => {
+            let mut chars = fill.as_str().chars();
+            match (chars.next(), chars.next()) {
+                (Some(fill), None) => fill,
+                _ => fail!("expected a single character to pad with", ARG 2),
+            }
+        }
+        Some(_) => fail!("expected a string for the third argument", ARG 2),
+    };
+
+    let width = width.max(0.) as usize;
+    _ = tee!(vm.check_string_len(width));
+
+    let padding = width.saturating_sub(len);
+    let bytes = padding
+        .checked_mul(fill.len_utf8())
+        .and_then(|bytes| bytes.checked_add(text.len()));
+    if bytes.is_none_or(|bytes| bytes > isize::MAX as usize) {
+        fail!("the string would be too long", ARG 1);
+    }
+    let padding = fill.to_string().repeat(padding);
+
+    Value::String(Gc::new(Str::new(if left {
+        padding + &text
+    } else {
+        text + &padding
+    })))
+}
+
+fn pad_left(vm: &mut VM, args: &[Value]) -> Value {
+    pad(vm, args, true)
+}
+
+fn pad_right(vm: &mut VM, args: &[Value]) -> Value {
+    pad(vm, args, false)
+}
@@ -0,0 +1,53 @@
+use std::io::Write;
+
+use super::Module;
+use crate::{
+    fail,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Screen and cursor control for animations in the terminal. These do nothing
+/// when the output is not a terminal, such as when it is piped to a file.
+pub const MODULE: Module = Module {
+    name: "terminal",
+    builtins: &[
+        ("CLEAR_SCREEN", clear_screen as BuiltinPtr),
+        ("CURSOR_TO", cursor_to),
+    ],
+};
+
+fn write_escape(vm: &mut VM, escape: &str) -> Value {
+    if !vm.terminal {
+        return Value::Void;
+    }
+
+    let Ok(_) = vm.output.write_all(escape.as_bytes()) else {
+        fail!("failed to write output", BUILTIN);
+    };
+    let Ok(_) = vm.output.flush() else {
+        fail!("failed to flush output", BUILTIN);
+    };
+
+    Value::Void
+}
+
+/// Clears the terminal and moves the cursor to the top left corner.
+fn clear_screen(vm: &mut VM, _: &[Value]) -> Value {
+    write_escape(vm, "\x1b[2J\x1b[H")
+}
+
+/// `CURSOR_TO(row, col)` moves the cursor so that the next output starts at
+/// that row and column, counting from 1 at the top left corner.
+fn cursor_to(vm: &mut VM, args: &[Value]) -> Value {
+    let (Some(&Value::Number(row)), Some(&Value::Number(col))) = (args.first(), args.get(1)) else {
+        fail!("expected row and column numbers", BUILTIN);
+    };
+
+    for (idx, n) in [row, col].into_iter().enumerate() {
+        if n.floor() != n || n < 1. {
+            fail!(format!("{n} is not a positive integer"), ARG idx);
+        }
+    }
+
+    write_escape(vm, &format!("\x1b[{row};{col}H"))
+}
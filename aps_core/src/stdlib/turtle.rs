@@ -0,0 +1,186 @@
+use std::fmt::Write;
+
+use super::Module;
+use crate::{
+    fail, tee,
+    vm::{BuiltinPtr, Value, VM},
+};
+
+/// Turtle graphics: a pen that draws lines as it moves. The drawing is kept on
+/// the VM as a [`Turtle`] and can be exported with [`Turtle::to_svg`].
+pub const MODULE: Module = Module {
+    name: "turtle",
+    builtins: &[
+        ("PEN_DOWN", pen_down as BuiltinPtr),
+        ("PEN_UP", pen_up),
+        ("FORWARD", forward),
+        ("TURN", turn),
+        ("SET_COLOR", set_color),
+    ],
+};
+
+/// A line drawn by the turtle, in SVG coordinates where y grows downward.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+    pub color: String,
+}
+
+/// The turtle's position and everything it has drawn. It starts at the origin
+/// facing up with its pen down and drawing in black.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turtle {
+    pub position: (f64, f64),
+    /// Degrees clockwise from facing up.
+    pub heading: f64,
+    pub pen_down: bool,
+    pub color: String,
+    pub segments: Vec<Segment>,
+}
+
+impl Default for Turtle {
+    fn default() -> Self {
+        Self {
+            position: (0., 0.),
+            heading: 0.,
+            pen_down: true,
+            color: "black".into(),
+            segments: Vec::new(),
+        }
+    }
+}
+
+/// Formats a coordinate with at most two decimal places.
+fn coord(n: f64) -> f64 {
+    // Adding zero turns `-0` into `0`.
+    (n * 100.).round() / 100. + 0.
+}
+
+impl Turtle {
+    /// An SVG image of the drawing, sized to fit every line and the starting
+    /// point with a margin around them.
+    pub fn to_svg(&self) -> String {
+        const MARGIN: f64 = 10.;
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (0f64, 0f64, 0f64, 0f64);
+        for segment in &self.segments {
+            for (x, y) in [segment.from, segment.to] {
+                (min_x, min_y) = (min_x.min(x), min_y.min(y));
+                (max_x, max_y) = (max_x.max(x), max_y.max(y));
+            }
+        }
+
+        let (x, y) = (coord(min_x - MARGIN), coord(min_y - MARGIN));
+        let (width, height) = (
+            coord(max_x - min_x + MARGIN * 2.),
+            coord(max_y - min_y + MARGIN * 2.),
+        );
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{x} {y} {width} {height}\" width=\"{width}\" height=\"{height}\">\n"
+        );
+        for segment in &self.segments {
+            _ = writeln!(
+                svg,
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\" stroke-linecap=\"round\"/>",
+                coord(segment.from.0),
+                coord(segment.from.1),
+                coord(segment.to.0),
+                coord(segment.to.1),
+                segment.color,
+            );
+        }
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+fn number_arg(args: &[Value], out: &mut f64) -> Value {
+    let Some(&Value::Number(n)) = args.first() else {
+        fail!("expected number for the first argument", ARG 0);
+    };
+
+    if !n.is_finite() {
+        fail!(format!("{n} is not a finite number"), ARG 0);
+    }
+
+    *out = n;
+    Value::Void
+}
+
+/// Makes the turtle draw as it moves.
+fn pen_down(vm: &mut VM, _: &[Value]) -> Value {
+    vm.turtle.pen_down = true;
+    Value::Void
+}
+
+/// Makes the turtle move without drawing.
+fn pen_up(vm: &mut VM, _: &[Value]) -> Value {
+    vm.turtle.pen_down = false;
+    Value::Void
+}
+
+/// Moves the turtle `n` steps the way it is facing, or backward if `n` is
+/// negative.
+fn forward(vm: &mut VM, args: &[Value]) -> Value {
+    let mut n = 0.;
+    tee!(number_arg(args, &mut n));
+
+    let turtle = &mut vm.turtle;
+    let heading = turtle.heading.to_radians();
+    let from = turtle.position;
+    let to = (from.0 + n * heading.sin(), from.1 - n * heading.cos());
+
+    if turtle.pen_down {
+        turtle.segments.push(Segment {
+            from,
+            to,
+            color: turtle.color.clone(),
+        });
+    }
+    turtle.position = to;
+
+    Value::Void
+}
+
+/// Turns the turtle `deg` degrees clockwise, or counterclockwise if `deg` is
+/// negative.
+fn turn(vm: &mut VM, args: &[Value]) -> Value {
+    let mut deg = 0.;
+    tee!(number_arg(args, &mut deg));
+
+    vm.turtle.heading = (vm.turtle.heading + deg).rem_euclid(360.);
+    Value::Void
+}
+
+/// Sets the color of the lines drawn next, either by name, as `"#RRGGBB"`, or
+/// from red, green, and blue amounts from 0 to 255.
+fn set_color(vm: &mut VM, args: &[Value]) -> Value {
+    let color = match args {
+        [Value::String(name)] => {
+            let name = name.as_str();
+            // The name is written into the SVG as is.
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#') {
+                fail!(format!("{name:?} is not a color"), BUILTIN);
+            }
+            name.to_string()
+        }
+        [Value::Number(r), Value::Number(g), Value::Number(b)] => {
+            for n in [r, g, b] {
+                if n.floor() != *n || !(0. ..=255.).contains(n) {
+                    fail!(format!("{n} is not an integer from 0 to 255"), BUILTIN);
+                }
+            }
+            format!("#{:02x}{:02x}{:02x}", *r as u8, *g as u8, *b as u8)
+        }
+        _ => fail!(
+            "expected a color name or red, green, and blue numbers",
+            BUILTIN
+        ),
+    };
+
+    vm.turtle.color = color;
+    Value::Void
+}
@@ -1,26 +1,40 @@
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    borrow::Cow,
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Debug, Display},
     rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
+use codespan_reporting::diagnostic::{Diagnostic, Label};
 use gc::{Finalize, Gc, GcCell, Trace};
 #[cfg(not(feature = "js"))]
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
 
 use crate::{
     ast::{BinaryOpKind, Expr, Node, Procedure, Span, Stmt, UnaryOpKind},
-    fail, tee,
+    bytecode::{self, Backend, Chunk},
+    fail,
+    lexer::Token,
+    parser::Parser,
+    permissions::Permissions,
+    stdlib::{self, robot::Robot, turtle::Turtle},
+    tee, trace_span,
 };
 
 #[derive(Trace, Finalize, Clone)]
 pub enum Value {
     Void,
     Bool(bool),
-    Number(f32),
-    String(Gc<String>),
+    Number(f64),
+    String(Gc<Str>),
     Array(Gc<GcCell<Array>>),
+    Set(Gc<GcCell<Set>>),
+    Map(Gc<GcCell<Map>>),
     #[unsafe_ignore_trace]
     Builtin(Builtin),
     #[unsafe_ignore_trace]
@@ -34,8 +48,10 @@ impl PartialEq for Value {
         match (self, other) {
             (Self::Bool(l0), Self::Bool(r0)) => l0 == r0,
             (Self::Number(l0), Self::Number(r0)) => l0 == r0,
-            (Self::String(l0), Self::String(r0)) => l0 == r0,
+            (Self::String(l0), Self::String(r0)) => l0.as_str() == r0.as_str(),
             (Self::Array(l0), Self::Array(r0)) => l0 == r0,
+            (Self::Set(l0), Self::Set(r0)) => l0 == r0,
+            (Self::Map(l0), Self::Map(r0)) => l0 == r0,
             (Self::Builtin(l0), Self::Builtin(r0)) => l0.0 as usize == r0.0 as usize,
             (Self::Exception(_), Self::Exception(_)) => false,
             _ => false,
@@ -43,13 +59,126 @@ impl PartialEq for Value {
     }
 }
 
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Self::Number(value as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(Gc::new(Str::new(value)))
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(Gc::new(Str::new(value)))
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Self::Array(Gc::new(GcCell::new(Array {
+            items: value.into_iter().map(Into::into).collect(),
+        })))
+    }
+}
+
 #[derive(Debug, Finalize, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Exception {
     pub message: String,
     pub span: Span,
-    pub stack: Vec<Span>,
+    /// The PROCEDURE calls that led to the exception, innermost first.
+    pub stack: Vec<Frame>,
+    /// Advice for fixing a common mistake that causes this exception.
+    pub help: Option<String>,
+    /// The index of the argument a builtin's exception is about, which the VM
+    /// replaces with that argument's `span` where it was written.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub arg: Option<usize>,
+}
+
+/// A PROCEDURE call that was running when an exception was raised.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Frame {
+    /// The name of the PROCEDURE.
+    pub name: String,
+    /// Where the PROCEDURE was called.
+    pub call_span: Span,
+}
+
+impl Exception {
+    /// An error pointing at where the exception was raised, followed by a note
+    /// for each call that led there.
+    pub fn to_diagnostics(&self, fid: usize) -> Vec<Diagnostic<usize>> {
+        [Diagnostic::error()
+            .with_message(&self.message)
+            .with_labels(vec![Label::primary(fid, self.span)])
+            .with_notes(
+                self.help
+                    .iter()
+                    .map(|help| format!("help: {help}"))
+                    .collect(),
+            )]
+        .into_iter()
+        .chain(self.stack.chunk_by(|a, b| a == b).map(|calls| {
+            // Deep recursion makes the same call many times over, which is
+            // shown once.
+            let Frame { name, call_span } = &calls[0];
+            let message = match calls.len() {
+                1 => format!("in PROCEDURE {name}, called here"),
+                n => format!("in PROCEDURE {name}, called here {n} times"),
+            };
+            Diagnostic::note()
+                .with_message(message)
+                .with_labels(vec![Label::primary(fid, *call_span)])
+        }))
+        .collect()
+    }
+}
+
+/// Something suspicious that happened while running without stopping the
+/// program.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Warning {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Warning {
+    pub fn to_diagnostic(&self, fid: usize) -> Diagnostic<usize> {
+        Diagnostic::warning()
+            .with_message(&self.message)
+            .with_labels(vec![Label::primary(fid, self.span)])
+    }
 }
 
+/// Not every whole number from this size up can be stored exactly, so
+/// counting past it skips values.
+pub const EXACT_INTEGER_LIMIT: f64 = 9_007_199_254_740_992.;
+
+/// The most items a list or set, or characters a string built by a builtin,
+/// may have when [`VM::max_array_len`] or [`VM::max_string_len`] is not set.
+/// Anything larger could run out of memory, which aborts the process instead
+/// of raising an exception.
+pub const MAX_LEN: usize = 1 << 26;
+
 unsafe impl Trace for Exception {
     unsafe fn trace(&self) {}
 
@@ -60,6 +189,51 @@ unsafe impl Trace for Exception {
     fn finalize_glue(&self) {}
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Void => serializer.serialize_unit(),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::Number(n) => serializer.serialize_f64(*n),
+            Self::String(s) => serializer.serialize_str(s.as_str()),
+            Self::Array(array) => serializer.collect_seq(array.borrow().items.iter()),
+            Self::Set(set) => serializer.collect_seq(set.borrow().items.iter()),
+            // JSON only allows text keys.
+            Self::Map(map) => serializer.collect_map(
+                map.borrow()
+                    .entries
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value)),
+            ),
+            Self::Builtin(_) | Self::Procedure(_) => serializer.serialize_str(&self.to_string()),
+            Self::Exception(e) => e.serialize(serializer),
+        }
+    }
+}
+
+impl Value {
+    /// Moves the exception out of this value, leaving `Void` in its place.
+    pub fn take_exception(&mut self) -> Option<Exception> {
+        let Self::Exception(e) = self else {
+            return None;
+        };
+
+        let e = std::mem::replace(
+            e.as_mut(),
+            Exception {
+                message: String::new(),
+                span: Span { start: 0, end: 0 },
+                stack: Vec::new(),
+                help: None,
+                arg: None,
+            },
+        );
+        *self = Self::Void;
+        Some(e)
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -67,18 +241,8 @@ impl Display for Value {
             Self::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
             Self::Number(n) => write!(f, "{}", n),
             Self::Procedure(_) => write!(f, "<procedure>"),
-            Self::String(s) => {
-                let mut iter = s.chars();
-                while let Some(c) = iter.next() {
-                    if c == '\\' {
-                        iter.next();
-                        continue;
-                    }
-                    write!(f, "{c}")?;
-                }
-                Ok(())
-            }
-            Self::Exception(_) => unreachable!(),
+            Self::String(s) => write!(f, "{}", s.as_str()),
+            Self::Exception(e) => write!(f, "{}", e.message),
             Self::Array(array) => {
                 write!(f, "[")?;
                 let array = &array.borrow().items;
@@ -92,6 +256,32 @@ impl Display for Value {
                 }
                 write!(f, "]")
             }
+            Self::Set(set) => {
+                write!(f, "{{")?;
+                let set = &set.borrow().items;
+                let mut iter = set.iter();
+
+                if let Some(v0) = iter.next() {
+                    write!(f, "{:?}", v0)?;
+                    for itm in iter {
+                        write!(f, ", {:?}", itm)?;
+                    }
+                }
+                write!(f, "}}")
+            }
+            Self::Map(map) => {
+                write!(f, "{{")?;
+                let map = &map.borrow().entries;
+                let mut iter = map.iter();
+
+                if let Some((k0, v0)) = iter.next() {
+                    write!(f, "{:?}: {:?}", k0, v0)?;
+                    for (key, value) in iter {
+                        write!(f, ", {:?}: {:?}", key, value)?;
+                    }
+                }
+                write!(f, "}}")
+            }
             Self::Builtin(_) => write!(f, "<builtin>"),
         }
     }
@@ -100,7 +290,7 @@ impl Display for Value {
 impl Debug for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::String(s) => write!(f, "{:?}", s),
+            Self::String(s) => write!(f, "{:?}", s.as_str()),
             _ => write!(f, "{}", self),
         }
     }
@@ -127,25 +317,569 @@ impl Debug for Builtin {
     }
 }
 
+/// Strings longer than this many bytes cache the byte offset of every
+/// character the first time they are indexed.
+const CHAR_CACHE_THRESHOLD: usize = 64;
+
+/// An immutable string value. Positions count characters rather than bytes so
+/// multi-byte text is never split.
+#[derive(Finalize)]
+pub struct Str {
+    text: String,
+    ascii: bool,
+    offsets: OnceCell<Box<[usize]>>,
+}
+
+unsafe impl Trace for Str {
+    unsafe fn trace(&self) {}
+
+    unsafe fn root(&self) {}
+
+    unsafe fn unroot(&self) {}
+
+    fn finalize_glue(&self) {}
+}
+
+impl Str {
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self {
+            ascii: text.is_ascii(),
+            text,
+            offsets: OnceCell::new(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    fn offsets(&self) -> Option<&[usize]> {
+        if self.ascii || self.text.len() <= CHAR_CACHE_THRESHOLD {
+            return None;
+        }
+
+        Some(self.offsets.get_or_init(|| {
+            self.text
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain([self.text.len()])
+                .collect()
+        }))
+    }
+
+    /// Byte offset of the character at `idx` (0-based), allowing one past the
+    /// last character.
+    fn byte_offset(&self, idx: usize) -> Option<usize> {
+        if self.ascii {
+            return (idx <= self.text.len()).then_some(idx);
+        }
+
+        match self.offsets() {
+            Some(offsets) => offsets.get(idx).copied(),
+            None => self
+                .text
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain([self.text.len()])
+                .nth(idx),
+        }
+    }
+
+    /// The number of characters in the string.
+    pub fn char_len(&self) -> usize {
+        if self.ascii {
+            return self.text.len();
+        }
+
+        match self.offsets() {
+            Some(offsets) => offsets.len() - 1,
+            None => self.text.chars().count(),
+        }
+    }
+
+    /// The characters in `start..end` (0-based), or `None` if out of range.
+    pub fn slice(&self, start: usize, end: usize) -> Option<&str> {
+        if start > end {
+            return None;
+        }
+
+        Some(&self.text[self.byte_offset(start)?..self.byte_offset(end)?])
+    }
+
+    /// The character at `idx` (0-based) as a string slice.
+    pub fn char_at(&self, idx: usize) -> Option<&str> {
+        self.slice(idx, idx.checked_add(1)?)
+    }
+}
+
+impl Debug for Str {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.text)
+    }
+}
+
 #[derive(PartialEq, Debug, Finalize, Trace, Clone)]
 pub struct Array {
     pub items: Vec<Value>,
 }
 
+/// Counters collected while a program runs.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Stats {
+    pub statements: u64,
+    pub calls: u64,
+    /// Items added to lists and sets, counting each list made by a list
+    /// literal or `MAKE_GRID`.
+    pub items: u64,
+}
+
+/// The hashable subset of values, identifying members of a [`Set`] and keys
+/// of a [`Map`].
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Key {
+    Bool(bool),
+    Number(u64),
+    String(String),
+}
+
+impl Key {
+    pub fn new(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Bool(b) => Self::Bool(*b),
+            // `-0` and `0` are equal numbers, so they must hash the same.
+            Value::Number(n) if *n == 0. => Self::Number(0f64.to_bits()),
+            Value::Number(n) => Self::Number(n.to_bits()),
+            Value::String(s) => Self::String(s.as_str().to_owned()),
+            _ => return None,
+        })
+    }
+}
+
+/// An insertion-ordered collection of unique numbers, strings, and booleans.
+#[derive(Debug, Default, Finalize, Trace, Clone)]
+pub struct Set {
+    pub items: Vec<Value>,
+    #[unsafe_ignore_trace]
+    keys: HashSet<Key>,
+}
+
+impl Set {
+    /// Adds `value` unless an equal member exists. Returns `false` for
+    /// values that cannot be hashed.
+    pub fn insert(&mut self, value: Value) -> bool {
+        let Some(key) = Key::new(&value) else {
+            return false;
+        };
+
+        if self.keys.insert(key) {
+            self.items.push(value);
+        }
+        true
+    }
+
+    pub fn contains(&self, value: &Value) -> bool {
+        Key::new(value).is_some_and(|key| self.keys.contains(&key))
+    }
+}
+
+impl PartialEq for Set {
+    fn eq(&self, other: &Self) -> bool {
+        self.keys == other.keys
+    }
+}
+
+/// An insertion-ordered map from numbers, strings, and booleans to values.
+#[derive(Debug, Default, Finalize, Trace, Clone)]
+pub struct Map {
+    /// Keys and their values, in the order the keys were first added.
+    pub entries: Vec<(Value, Value)>,
+    /// The position of each key in `entries`.
+    #[unsafe_ignore_trace]
+    positions: HashMap<Key, usize>,
+}
+
+impl Map {
+    /// Sets the value of `key`, keeping its place if it was already there.
+    /// Returns `false` for keys that cannot be hashed.
+    pub fn insert(&mut self, key: Value, value: Value) -> bool {
+        let Some(hashed) = Key::new(&key) else {
+            return false;
+        };
+
+        match self.positions.get(&hashed) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.positions.insert(hashed, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        true
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        let &i = self.positions.get(&Key::new(key)?)?;
+        Some(&self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &Value) -> bool {
+        Key::new(key).is_some_and(|key| self.positions.contains_key(&key))
+    }
+
+    /// Takes `key` out of the map, returning its value if it was there.
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let i = self.positions.remove(&Key::new(key)?)?;
+        let (_, value) = self.entries.remove(i);
+        for position in self.positions.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+}
+
+/// Maps are equal when they have the same keys with equal values, in any
+/// order.
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries.len() == other.entries.len()
+            && self
+                .entries
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+/// A variable being given a value, as reported to [`VM::on_assign`].
+pub struct Assignment<'v> {
+    pub name: &'v str,
+    /// The value the variable had before, if it was defined.
+    pub old: Option<&'v Value>,
+    pub new: &'v Value,
+    /// The assignment statement, or the variable of a `FOR EACH` loop.
+    pub span: Span,
+}
+
+/// See [`VM::on_assign`].
+pub type AssignHook = dyn FnMut(&Assignment);
+
+/// A plain-English description of a step the program took, as reported to
+/// [`VM::on_explain`], such as "assign 5 to x".
+pub struct Explanation {
+    pub text: String,
+    /// The statement, or the condition of a block.
+    pub span: Span,
+}
+
+/// See [`VM::on_explain`].
+pub type ExplainHook = dyn FnMut(&Explanation);
+
+/// See [`VM::on_display`].
+#[cfg(feature = "js")]
+pub type DisplayHook = dyn FnMut(&str);
+
+/// Answers `INPUT` calls instead of the user, e.g. with answers prepared for
+/// grading. A queue of answers or a closure taking the prompt works as one.
+pub trait InputProvider {
+    /// The answer to an `INPUT` call that showed `prompt`, or `None` if there
+    /// are no answers left.
+    fn answer(&mut self, prompt: &str) -> Option<String>;
+}
+
+impl InputProvider for VecDeque<String> {
+    fn answer(&mut self, _: &str) -> Option<String> {
+        self.pop_front()
+    }
+}
+
+impl<F: FnMut(&str) -> Option<String>> InputProvider for F {
+    fn answer(&mut self, prompt: &str) -> Option<String> {
+        self(prompt)
+    }
+}
+
 pub struct VM<'a> {
     pub source: &'a str,
 
+    /// Created from entropy on first use unless a host seeds it.
     #[cfg(not(feature = "js"))]
-    pub rng: Option<ThreadRng>,
+    pub rng: Option<StdRng>,
+
+    /// Where `DISPLAY` and `INPUT` prompts are written. Defaults to stdout.
+    #[cfg(not(feature = "js"))]
+    pub output: Box<dyn std::io::Write>,
+
+    /// Whether `output` is a terminal, so that the terminal control builtins
+    /// may write escape codes to it. Defaults to whether stdout is one.
+    #[cfg(not(feature = "js"))]
+    pub terminal: bool,
+
+    /// Whether `DISPLAY_COLOR` may write color codes to `output`. Defaults to
+    /// whether stdout is a terminal, unless `NO_COLOR` is set.
+    #[cfg(not(feature = "js"))]
+    pub color: bool,
+
+    /// Answers `INPUT` calls instead of prompting the user on stdin (or with
+    /// `window.prompt` in the browser).
+    pub input: Option<Box<dyn InputProvider>>,
+
+    /// Called with the text of each `DISPLAY` instead of logging it to the
+    /// browser console.
+    #[cfg(feature = "js")]
+    pub on_display: Option<Box<DisplayHook>>,
+
+    pub stats: Stats,
+
+    /// What the turtle graphics builtins have drawn.
+    pub turtle: Turtle,
+
+    /// The grid the robot builtins move the robot around, if one was loaded.
+    pub robot: Option<Robot>,
+
+    /// Called after each assignment to a variable, including the variable of
+    /// a `FOR EACH` loop.
+    pub on_assign: Option<Box<AssignHook>>,
+
+    /// Called as each statement runs with a description of what it did.
+    pub on_explain: Option<Box<ExplainHook>>,
+
+    /// Whether to warn about arithmetic results that reach
+    /// [`EXACT_INTEGER_LIMIT`].
+    pub check_precision: bool,
+
+    /// Warnings raised while running, at most one per expression.
+    pub warnings: Vec<Warning>,
+
+    /// The most items a single list or set may hold, or [`MAX_LEN`] if not
+    /// set.
+    pub max_array_len: Option<usize>,
+
+    /// The most items the program may add to lists and sets in total, as
+    /// counted by [`Stats::items`]. Unlike [`VM::max_array_len`], this also
+    /// stops programs that build many lists.
+    pub max_items: Option<usize>,
+
+    /// The most characters a string built by a builtin, such as
+    /// `REPEAT_STRING`, may have, or [`MAX_LEN`] if not set.
+    pub max_string_len: Option<usize>,
+
+    /// The most PROCEDURE calls that may be running at once, so that
+    /// recursion that never stops fails with an exception instead of
+    /// overflowing the stack. Tail calls reuse their caller's frame and are
+    /// not counted. Calls also fail once the thread's stack is nearly used
+    /// up. Defaults to [`DEFAULT_MAX_DEPTH`].
+    pub max_depth: usize,
+
+    /// How many PROCEDURE calls are running.
+    depth: usize,
+
+    /// When another thread, such as a Ctrl-C handler, sets this flag, the
+    /// program stops with an exception at the next statement. The exception's
+    /// stack shows where it was.
+    pub interrupt: Option<Arc<AtomicBool>>,
+
+    /// How many more statements and expressions the program may evaluate,
+    /// if it is limited with [`VM::set_max_steps`].
+    fuel: Option<u64>,
+
+    /// What builtins with effects outside the program may do. Nothing is
+    /// allowed by default.
+    pub permissions: Permissions,
+
+    /// How programs are run. Defaults to [`Backend::Tree`].
+    pub backend: Backend,
+
+    /// Set by a tail RETURN for [`VM::call`] to make in place of the
+    /// PROCEDURE that is returning.
+    pub(crate) tail_call: Option<TailCall>,
+
+    /// The bytecode of each PROCEDURE called so far with
+    /// [`Backend::Bytecode`], by the PROCEDURE's span.
+    chunks: HashMap<Span, Rc<Chunk>>,
+}
+
+/// A call to a PROCEDURE by a RETURN marked as `tail`.
+pub(crate) struct TailCall {
+    pub(crate) proc: Rc<Procedure>,
+    pub(crate) args: Vec<Value>,
+    pub(crate) span: Span,
+}
+
+/// How deep PROCEDURE calls may nest unless [`VM::max_depth`] is changed.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// How much stack a PROCEDURE call must leave free. One call can take many
+/// frames of the interpreter, especially in debug builds.
+#[cfg(not(target_arch = "wasm32"))]
+const STACK_RED_ZONE: usize = 1024 * 1024;
+
+/// Whether the thread's stack is too close to running out for another
+/// PROCEDURE call, which would otherwise abort the process before
+/// [`VM::max_depth`] is reached.
+fn stack_exhausted() -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    return stacker::remaining_stack().is_some_and(|left| left < STACK_RED_ZONE);
+    #[cfg(target_arch = "wasm32")]
+    false
+}
+
+/// The help for an exception from running out of [`VM::set_max_steps`].
+pub(crate) const STEPS_HELP: &str =
+    "the program ran for too long; check for a loop whose condition never changes";
+
+/// Advice for indexing a list at 0, written as `list[0]`.
+fn index_help(idx: f64, list: &str) -> Option<String> {
+    (idx == 0.).then(|| format!("lists start at index 1, so the first item is `{list}[1]`"))
+}
+
+/// Advice for using `value`, written as `text`, as a number alongside `other`.
+fn number_help(value: &Value, other: &Value, text: &str) -> Option<String> {
+    let Value::String(s) = value else {
+        return None;
+    };
+
+    if s.as_str().trim().parse::<f64>().is_ok() {
+        Some(format!(
+            "this is text, not a number; convert it with `NUMBER({text})` first"
+        ))
+    } else if let Value::Number(_) = other {
+        Some(
+            "text cannot be used as a number; if it came from INPUT, the answer was not a number"
+                .into(),
+        )
+    } else {
+        None
+    }
+}
+
+/// Applies `kind` to `val`, which was written at `span`.
+pub(crate) fn unary_op(kind: UnaryOpKind, val: Value, span: Span) -> Value {
+    if let UnaryOpKind::Not = kind {
+        let Value::Bool(b) = val else {
+            fail!(format!("{val:?} is not a boolean"), span);
+        };
+
+        return Value::Bool(!b);
+    }
+
+    let Value::Number(n) = val else {
+        fail!(format!("{val:?} is not a boolean"), span);
+    };
+    Value::Number(if let UnaryOpKind::Pos = kind { n } else { -n })
+}
+
+/// Fails unless `count`, written at `span`, can be how many times a `REPEAT n
+/// TIMES` loop runs.
+pub(crate) fn check_repeat_count(count: &Value, span: Span) -> Value {
+    let Value::Number(n) = *count else {
+        fail!(format!("{count:?} is not a number"), span);
+    };
+
+    if n < 0. {
+        fail!(format!("{count:?} is not positive"), span);
+    }
+
+    if n.floor() != n {
+        fail!(format!("{count:?} is not an integer"), span);
+    }
+
+    Value::Void
+}
+
+/// Fails unless `key`, written at `span`, can be a key of a [`Map`].
+pub(crate) fn check_key(key: &Value, span: Span) -> Value {
+    if Key::new(key).is_none() {
+        fail!(
+            format!("{key:?} cannot be a map key (only numbers, strings, and booleans can)"),
+            span
+        );
+    }
+
+    Value::Void
+}
+
+/// Fails unless `proc` takes `args` arguments. `span` is the call.
+pub(crate) fn check_arity(proc: &Procedure, args: usize, span: Span) -> Value {
+    if args != proc.params.len() {
+        fail!(
+            format!("expected {} arguments, found {args}", proc.params.len()),
+            span
+        );
+    }
+
+    Value::Void
+}
+
+/// The items a `FOR EACH` loop visits, or the keys of a map. Lists, sets, and
+/// maps are read again on each iteration, so changes the loop makes to items
+/// not yet visited are seen.
+pub(crate) struct ForEach {
+    items: Value,
+    /// Strings cannot change, so their characters are split up once rather
+    /// than found again on every iteration.
+    chars: Vec<char>,
+    /// How many items there were when the loop started. Items added since are
+    /// not visited.
+    pub(crate) len: usize,
+}
+
+impl ForEach {
+    /// Fails with the exception as `Err` unless `items`, written at `span`, is
+    /// a list, set, map, or string.
+    pub(crate) fn new(items: Value, span: Span) -> Result<Self, Value> {
+        let chars: Vec<_> = match &items {
+            Value::String(s) => s.as_str().chars().collect(),
+            _ => Vec::new(),
+        };
+        let len = match &items {
+            Value::Array(array) => array.borrow().items.len(),
+            Value::Set(set) => set.borrow().items.len(),
+            Value::Map(map) => map.borrow().entries.len(),
+            Value::String(_) => chars.len(),
+            _ => {
+                return Err(Value::Exception(Box::new(Exception {
+                    message: format!("{items:?} is not an array"),
+                    span,
+                    stack: Vec::new(),
+                    help: None,
+                    arg: None,
+                })))
+            }
+        };
+
+        Ok(Self { items, chars, len })
+    }
+
+    /// The item at `i`, counting from 0, or `None` once the loop is done.
+    pub(crate) fn get(&self, i: usize) -> Option<Value> {
+        if i >= self.len {
+            return None;
+        }
+
+        match &self.items {
+            Value::Array(array) => array.borrow().items.get(i).cloned(),
+            Value::Set(set) => set.borrow().items.get(i).cloned(),
+            Value::Map(map) => map.borrow().entries.get(i).map(|(key, _)| key.clone()),
+            _ => self.chars.get(i).map(|c| c.to_string().into()),
+        }
+    }
 }
 
 // Inspired by burdonsmith's rust_lisp implementation
-pub struct Env<'a> {
-    pub parent: Option<Rc<RefCell<Env<'a>>>>,
+pub struct Env {
+    pub parent: Option<Rc<RefCell<Env>>>,
     pub entries: HashMap<String, Value>,
 }
 
-impl Env<'_> {
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Env {
     pub fn new() -> Self {
         Self {
             parent: None,
@@ -170,81 +904,165 @@ impl<'a> VM<'a> {
             source,
             #[cfg(not(feature = "js"))]
             rng: None,
+            #[cfg(not(feature = "js"))]
+            output: Box::new(std::io::stdout()),
+            #[cfg(not(feature = "js"))]
+            terminal: std::io::IsTerminal::is_terminal(&std::io::stdout()),
+            #[cfg(not(feature = "js"))]
+            color: std::io::IsTerminal::is_terminal(&std::io::stdout())
+                && std::env::var_os("NO_COLOR").is_none(),
+            input: None,
+            #[cfg(feature = "js")]
+            on_display: None,
+            stats: Stats::default(),
+            turtle: Turtle::default(),
+            robot: None,
+            on_assign: None,
+            on_explain: None,
+            check_precision: false,
+            warnings: Vec::new(),
+            max_array_len: None,
+            max_items: None,
+            max_string_len: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            interrupt: None,
+            fuel: None,
+            permissions: Permissions::default(),
+            backend: Backend::default(),
+            tail_call: None,
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Runs programs with `backend` instead of the tree-walker.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Stops the program with an exception once it has evaluated `max`
+    /// statements and expressions in total, or never if `None`. Unlike
+    /// [`VM::interrupt`], this needs no other thread to stop a program that
+    /// never ends, so it works in the browser.
+    pub fn set_max_steps(&mut self, max: Option<u64>) {
+        self.fuel = max;
+        // Bytecode only counts steps if it was compiled with a limit.
+        self.chunks.clear();
+    }
+
+    /// Uses up one of the steps left by [`VM::set_max_steps`], or returns
+    /// `false` if there are none left.
+    pub(crate) fn take_step(&mut self) -> bool {
+        match &mut self.fuel {
+            Some(0) => false,
+            Some(fuel) => {
+                *fuel -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Parses `source` as a single expression and evaluates it in `env`. Parse
+    /// errors are reported as exceptions, and all spans point into `source`.
+    ///
+    /// Names are read from the VM's source while evaluating, so PROCEDUREs
+    /// defined by other text can only be called if `source` extends that text.
+    pub fn eval_str(&mut self, source: &'a str, env: Rc<RefCell<Env>>) -> Result<Value, Exception> {
+        let mut parser = Parser::new((), source.as_bytes());
+        parser.lex.next();
+
+        let expr = parser.parse_expr(0);
+
+        if parser.diagnostics.is_empty() && parser.lex.token != Token::EOF {
+            return Err(Exception {
+                message: format!(
+                    "expected end of expression, found {}",
+                    parser.lex.token.as_ref()
+                ),
+                span: parser.lex.span(),
+                stack: Vec::new(),
+                help: None,
+                arg: None,
+            });
+        }
+
+        let (Ok(expr), None) = (expr, parser.diagnostics.first()) else {
+            let diagnostic = &parser.diagnostics[0];
+            let span = diagnostic
+                .labels
+                .first()
+                .map_or(Span { start: 0, end: 0 }, |label| Span {
+                    start: label.range.start as u32,
+                    end: label.range.end as u32,
+                });
+            return Err(Exception {
+                message: diagnostic.message.clone(),
+                span,
+                stack: Vec::new(),
+                help: None,
+                arg: None,
+            });
+        };
+
+        let source = std::mem::replace(&mut self.source, source);
+        let mut value = self.eval_expr(&expr, env);
+        self.source = source;
+
+        match value.take_exception() {
+            Some(e) => Err(e),
+            None => Ok(value),
         }
     }
 
     pub fn eval_expr(&mut self, expr: &Expr, env: Rc<RefCell<Env>>) -> Value {
+        if !self.take_step() {
+            fail!(
+                "execution limit exceeded",
+                expr.span(),
+                Some(STEPS_HELP.into())
+            );
+        }
+
         match expr {
-            Expr::Void => unreachable!(),
-            Expr::BinaryLiteral { .. } | Expr::HexLiteral { .. } => panic!(),
+            Expr::Void => Value::Void,
             Expr::Index { value, index, span } => {
                 let v = tee!(self.eval_expr(value, env.clone()));
 
-                let Value::Array(array) = &v else {
-					fail!(format!("{v:?} is not an array"), *span);
-				};
+                if !matches!(v, Value::Array(_) | Value::String(_) | Value::Map(_)) {
+                    fail!(format!("{v:?} is not an array"), *span);
+                }
 
                 let idx = tee!(self.eval_expr(index, env));
-                let Value::Number(idx) = idx else {
-					fail!(format!("{idx:?} is not an integer"), *span);
-				};
-
-                let array = array.borrow();
-                match array.items.get(idx as u32 as usize - 1) {
-                    Some(v) => v.clone(),
-                    None => fail!(
-                        format!(
-                            "index {idx} is out of array range (length: {})",
-                            array.items.len()
-                        ),
-                        *span
-                    ),
-                }
+                self.index(&v, idx, value.span(), index.span(), *span)
             }
             Expr::True { .. } => Value::Bool(true),
             Expr::False { .. } => Value::Bool(false),
-            Expr::IntegerLiteral { span } | Expr::FloatLiteral { span } => Value::Number(
-                self.source[Into::<std::ops::Range<_>>::into(*span)]
-                    .parse()
-                    .unwrap(),
-            ),
+            &Expr::IntegerLiteral { value, .. }
+            | &Expr::FloatLiteral { value, .. }
+            | &Expr::BinaryLiteral { value, .. }
+            | &Expr::HexLiteral { value, .. } => Value::Number(value),
             &Expr::Identifier { span } => {
                 let name = &self.source[Into::<std::ops::Range<_>>::into(span)];
 
                 let Some(v) = env.borrow().get(name) else {
-					fail!(format!("'{}' is not defined", name), span);
-				};
+                    fail!(format!("'{}' is not defined", name), span);
+                };
 
                 v
             }
-            &Expr::StringLiteral { span } => Value::String(Gc::new(String::from(
-                &self.source[Into::<std::ops::Range<_>>::into(Span {
-                    start: span.start + 1,
-                    end: span.end - 1,
-                })],
-            ))),
-            Expr::UnaryOp { kind, value, .. } => 'blk: {
+            Expr::StringLiteral { value, .. } => Value::String(Gc::new(Str::new(value.as_str()))),
+            Expr::UnaryOp { kind, value, .. } => {
                 let val = tee!(self.eval_expr(value, env));
-
-                if let UnaryOpKind::Not = kind {
-                    let Value::Bool(b) = val else {
-						fail!(format!("{val:?} is not a boolean"), value.span());
-					};
-
-                    break 'blk Value::Bool(!b);
-                }
-
-                let Value::Number(n) = val else {
-					fail!(format!("{val:?} is not a boolean"), value.span());
-				};
-                Value::Number(if let UnaryOpKind::Pos = kind { n } else { -n })
+                unary_op(*kind, val, value.span())
             }
             Expr::BinaryOp { kind, lhs, rhs } => match kind {
                 BinaryOpKind::And => 'blk: {
                     let lhs_value = tee!(self.eval_expr(lhs, env.clone()));
                     let Value::Bool(b1) = lhs_value else {
-						fail!(format!("{lhs_value:?} is not a boolean"), lhs.span());
-					};
+                        fail!(format!("{lhs_value:?} is not a boolean"), lhs.span());
+                    };
 
                     if !b1 {
                         break 'blk Value::Bool(false);
@@ -252,16 +1070,16 @@ impl<'a> VM<'a> {
 
                     let rhs_value = tee!(self.eval_expr(rhs, env));
                     let Value::Bool(b2) = rhs_value else {
-						fail!(format!("{rhs_value:?} is not a boolean"), rhs.span());
-					};
+                        fail!(format!("{rhs_value:?} is not a boolean"), rhs.span());
+                    };
 
                     Value::Bool(b2)
                 }
                 BinaryOpKind::Or => 'blk: {
                     let lhs_value = tee!(self.eval_expr(lhs, env.clone()));
                     let Value::Bool(b1) = lhs_value else {
-						fail!(format!("{lhs_value:?} is not a boolean"), lhs.span());
-					};
+                        fail!(format!("{lhs_value:?} is not a boolean"), lhs.span());
+                    };
 
                     if b1 {
                         break 'blk Value::Bool(true);
@@ -269,58 +1087,44 @@ impl<'a> VM<'a> {
 
                     let rhs_value = tee!(self.eval_expr(rhs, env));
                     let Value::Bool(b2) = rhs_value else {
-						fail!(format!("{rhs_value:?} is not a boolean"), rhs.span());
-					};
+                        fail!(format!("{rhs_value:?} is not a boolean"), rhs.span());
+                    };
 
                     Value::Bool(b2)
                 }
-                BinaryOpKind::Equal => {
+                _ => {
                     let lhs_value = tee!(self.eval_expr(lhs, env.clone()));
                     let rhs_value = tee!(self.eval_expr(rhs, env));
-
-                    Value::Bool(lhs_value == rhs_value)
+                    self.binary_op(*kind, lhs_value, rhs_value, lhs.span(), rhs.span())
                 }
-                BinaryOpKind::NotEqual => {
-                    let lhs_value = tee!(self.eval_expr(lhs, env.clone()));
-                    let rhs_value = tee!(self.eval_expr(rhs, env));
-
-                    Value::Bool(lhs_value != rhs_value)
-                }
-                BinaryOpKind::Add
-                | BinaryOpKind::Sub
-                | BinaryOpKind::Mul
-                | BinaryOpKind::Div
-                | BinaryOpKind::Mod
-                | BinaryOpKind::Greater
-                | BinaryOpKind::GreaterEqual
-                | BinaryOpKind::Less
-                | BinaryOpKind::LessEqual => {
-                    let lhs_value = tee!(self.eval_expr(lhs, env.clone()));
-                    let rhs_value = tee!(self.eval_expr(rhs, env));
+            },
+            Expr::Paren { value, .. } => tee!(self.eval_expr(value, env)),
+            Expr::If {
+                cond,
+                scope,
+                else_ifs,
+                els,
+                ..
+            } => {
+                let branches = core::iter::once((cond.as_ref(), scope)).chain(
+                    else_ifs
+                        .iter()
+                        .map(|else_if| (&else_if.cond, &else_if.scope)),
+                );
+
+                for (cond, scope) in branches {
+                    let c = tee!(self.eval_expr(cond, env.clone()));
+                    let Value::Bool(b) = c else {
+                        fail!(format!("{c:?} is not a boolean"), cond.span());
+                    };
 
-                    let Value::Number(n1) = lhs_value else {
-						fail!(format!("{lhs_value:?} is not a number"), lhs.span());
-					};
-
-                    let Value::Number(n2) = rhs_value else {
-						fail!(format!("{rhs_value:?} is not a number"), rhs.span());
-					};
-
-                    match kind {
-                        BinaryOpKind::Add => Value::Number(n1 + n2),
-                        BinaryOpKind::Sub => Value::Number(n1 - n2),
-                        BinaryOpKind::Mul => Value::Number(n1 * n2),
-                        BinaryOpKind::Div => Value::Number(n1 / n2),
-                        BinaryOpKind::Mod => Value::Number(n1 % n2),
-                        BinaryOpKind::Greater => Value::Bool(n1 > n2),
-                        BinaryOpKind::GreaterEqual => Value::Bool(n1 >= n2),
-                        BinaryOpKind::Less => Value::Bool(n1 < n2),
-                        BinaryOpKind::LessEqual => Value::Bool(n1 <= n2),
-                        _ => unreachable!(),
+                    if b {
+                        return self.eval_scope_value(scope, env);
                     }
                 }
-            },
-            Expr::Paren { value, .. } => tee!(self.eval_expr(value, env)),
+
+                self.eval_scope_value(els, env)
+            }
             Expr::ArrayLiteral { values, .. } => {
                 let mut items = Vec::with_capacity(values.len());
 
@@ -328,124 +1132,818 @@ impl<'a> VM<'a> {
                     items.push(tee!(self.eval_expr(v, env.clone())));
                 }
 
+                let mut res = self.add_items(items.len(), items.len());
+                if let Value::Exception(e) = &mut res {
+                    e.span = expr.span();
+                    return res;
+                }
+
                 Value::Array(Gc::new(GcCell::new(Array { items })))
             }
-            Expr::FnCall { calle, args, span } => 'blk: {
+            Expr::MapLiteral { entries, .. } => {
+                let mut values = Vec::with_capacity(entries.len());
+
+                for (key, value) in entries.iter() {
+                    values.push((
+                        tee!(self.eval_expr(key, env.clone())),
+                        tee!(self.eval_expr(value, env.clone())),
+                    ));
+                }
+
+                let keys: Vec<_> = entries.iter().map(|(key, _)| key.span()).collect();
+                self.map(values, &keys, expr.span())
+            }
+            Expr::FnCall { calle, args, span } => {
                 let v = tee!(self.eval_expr(calle, env.clone()));
 
-                if let Value::Procedure(proc) = &v {
-                    if args.len() != proc.params.len() {
-                        fail!(
-                            format!(
-                                "expected {} arguments, found {}",
-                                proc.params.len(),
-                                args.len()
-                            ),
-                            *span
-                        );
-                    }
+                let (Value::Procedure(_) | Value::Builtin(_)) = &v else {
+                    fail!(format!("{v:?} is not a function"), calle.span());
+                };
+
+                let mut argv = Vec::with_capacity(args.len());
+
+                for arg in args.iter() {
+                    argv.push(tee!(self.eval_expr(arg, env.clone())));
+                }
+
+                self.call(&v, &argv, args, *span, env)
+            }
+        }
+    }
+
+    /// Calls a procedure or builtin with already evaluated arguments. `span` is
+    /// the call site used for errors and exception stacks, and `arg_exprs` are
+    /// where the arguments were written, if they were, for errors about one
+    /// argument.
+    pub fn call(
+        &mut self,
+        callee: &Value,
+        args: &[Value],
+        arg_exprs: &[Expr],
+        span: Span,
+        env: Rc<RefCell<Env>>,
+    ) -> Value {
+        self.call_with(callee, args, |idx| arg_exprs.get(idx).map(Expr::span), span, env)
+    }
+
+    /// Like [`VM::call`], but `arg_span` gives where an argument was written
+    /// from its index.
+    pub(crate) fn call_with(
+        &mut self,
+        callee: &Value,
+        args: &[Value],
+        arg_span: impl Fn(usize) -> Option<Span>,
+        span: Span,
+        env: Rc<RefCell<Env>>,
+    ) -> Value {
+        self.stats.calls += 1;
+
+        match callee {
+            Value::Procedure(proc) => {
+                tee!(check_arity(proc, args.len(), span));
+
+                if self.depth >= self.max_depth {
+                    fail!(
+                        "maximum recursion depth exceeded",
+                        span,
+                        Some(format!(
+                            "PROCEDUREs can only call each other {} deep; check that a recursive PROCEDURE stops calling itself",
+                            self.max_depth
+                        ))
+                    );
+                }
+                if stack_exhausted() {
+                    fail!(
+                        "maximum recursion depth exceeded",
+                        span,
+                        Some(format!(
+                            "the interpreter ran out of stack after PROCEDUREs called each other {} deep; check that a recursive PROCEDURE stops calling itself",
+                            self.depth
+                        ))
+                    );
+                }
+                self.depth += 1;
+
+                let (mut proc, mut args) = (proc.clone(), Cow::Borrowed(args));
+                let name = proc.name;
+                let mut entries = HashMap::new();
+                let mut tail_span = None;
+                let res = loop {
+                    trace_span!("call", procedure = self.text(proc.name));
 
                     let mut child_env = Env {
                         parent: Some(env.clone()),
-                        entries: HashMap::new(),
+                        entries,
                     };
 
-                    for (idx, arg) in args.iter().enumerate() {
-                        let argv = tee!(self.eval_expr(arg, env.clone()));
+                    for (param, arg) in proc.params.iter().zip(args.iter()) {
                         child_env.entries.insert(
-                            self.source[Into::<std::ops::Range<_>>::into(proc.params[idx])].into(),
-                            argv,
+                            self.source[Into::<std::ops::Range<_>>::into(*param)].into(),
+                            arg.clone(),
                         );
                     }
 
-                    let res = self.eval_scope(&proc.scope, Rc::new(RefCell::new(child_env)));
+                    let frame = Rc::new(RefCell::new(child_env));
+                    let mut res = self.run_body(&proc, frame.clone());
+
+                    // A tail call replaces this frame instead of nesting
+                    // inside it, so recursion through RETURN does not grow the
+                    // stack or the chain of environments. The called
+                    // PROCEDURE keeps this frame's variables, such as those a
+                    // PROCEDURE defined inside this one reads, under its own
+                    // parameters.
+                    if let Some(tail) = self.tail_call.take() {
+                        self.stats.calls += 1;
+                        proc = tail.proc;
+                        args = Cow::Owned(tail.args);
+                        entries = std::mem::take(&mut frame.borrow_mut().entries);
+                        tail_span = Some(tail.span);
+                        continue;
+                    }
 
-                    if let Value::Exception(e) = &res {
-                        let mut e = e.clone();
-                        e.stack.push(*span);
-                        break 'blk Value::Exception(e);
-                    };
+                    // The exception is updated in place rather than cloned so
+                    // that unwinding deep recursion does not copy the stack at
+                    // each frame. Of the tail calls, only the last is still
+                    // known.
+                    if let Value::Exception(e) = &mut res {
+                        if let Some(call_span) = tail_span {
+                            e.stack.push(Frame {
+                                name: self.text(proc.name).into(),
+                                call_span,
+                            });
+                        }
+                        e.stack.push(Frame {
+                            name: self.text(name).into(),
+                            call_span: span,
+                        });
+                    }
+                    break res;
+                };
 
-                    break 'blk res;
+                self.depth -= 1;
+                res
+            }
+            Value::Builtin(calle) => {
+                trace_span!("builtin", name = self.callee_name(span));
+
+                // Builtins fail without knowing where they were called, so
+                // the call site is filled in on the exception they return.
+                let mut res = calle.0(self, args);
+                if let Value::Exception(e) = &mut res {
+                    #[cfg(feature = "trace")]
+                    tracing::debug!(message = %e.message, "builtin failed");
+
+                    e.span = e.arg.take().and_then(arg_span).unwrap_or(span);
+                    e.stack.clear();
                 }
+                res
+            }
+            _ => fail!(format!("{callee:?} is not a function"), span),
+        }
+    }
 
-                if let Value::Builtin(calle) = &v {
-                    let mut oargs = Vec::with_capacity(args.len());
+    /// Runs the top-level statements of a program in `env` with
+    /// [`VM::backend`].
+    pub fn run(&mut self, scope: &[Stmt], env: Rc<RefCell<Env>>) -> Value {
+        match self.backend() {
+            Backend::Tree => self.eval_scope(scope, env),
+            Backend::Bytecode => {
+                self.run_chunk(&bytecode::compile(scope, self.fuel.is_some()), env)
+            }
+        }
+    }
 
-                    for arg in args.iter() {
-                        oargs.push(tee!(self.eval_expr(arg, env.clone())));
-                    }
+    /// Runs the body of `proc` in `env`, which holds its arguments, with
+    /// [`VM::backend`]. Each PROCEDURE is compiled once, on its first call.
+    fn run_body(&mut self, proc: &Rc<Procedure>, env: Rc<RefCell<Env>>) -> Value {
+        match self.backend() {
+            Backend::Tree => self.eval_scope(&proc.scope, env),
+            Backend::Bytecode => {
+                let steps = self.fuel.is_some();
+                let chunk = self
+                    .chunks
+                    .entry(proc.span)
+                    .or_insert_with(|| Rc::new(bytecode::compile(&proc.scope, steps)))
+                    .clone();
+                self.run_chunk(&chunk, env)
+            }
+        }
+    }
 
-                    let res = calle.0(self, &oargs);
+    /// [`VM::backend`], unless a hook needs what only the tree-walker reports.
+    fn backend(&self) -> Backend {
+        match self.on_explain {
+            Some(_) => Backend::Tree,
+            None => self.backend,
+        }
+    }
 
-                    break 'blk if let Value::Exception(e) = &res {
-                        Value::Exception(Box::new(Exception {
-                            message: e.message.clone(),
-                            span: *span,
-                            stack: Vec::new(),
-                        }))
-                    } else {
-                        res
-                    };
+    /// The name a call at `span` was written with, such as `DISPLAY` in
+    /// `DISPLAY(x)`, for tracing.
+    #[cfg(feature = "trace")]
+    fn callee_name(&self, span: Span) -> &'a str {
+        let text = self.text(span);
+        text[..text.find('(').unwrap_or(text.len())].trim()
+    }
+
+    /// Runs `RETURN call(args)` where the RETURN is marked as `tail`. A call
+    /// to a PROCEDURE is left in [`VM::tail_call`] for [`VM::call`] to make
+    /// once this frame has unwound.
+    fn tail_return(
+        &mut self,
+        stmt_span: Span,
+        call: &Expr,
+        calle: &Expr,
+        args: &[Expr],
+        span: Span,
+        env: Rc<RefCell<Env>>,
+    ) -> Value {
+        let callee = tee!(self.eval_expr(calle, env.clone()));
+        let mut argv = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            argv.push(tee!(self.eval_expr(arg, env.clone())));
+        }
+
+        let Value::Procedure(proc) = &callee else {
+            let v = tee!(self.call(&callee, &argv, args, span, env));
+            self.explain(stmt_span, |_| match &v {
+                Value::Void => "return".into(),
+                v => format!("return {v:?}"),
+            });
+            return v;
+        };
+
+        tee!(check_arity(proc, argv.len(), span));
+
+        self.explain(stmt_span, |vm| {
+            format!("return the result of {}", vm.text(call.span()))
+        });
+
+        // Nothing runs after a tail RETURN, so the Void given back here
+        // unwinds straight to `VM::call`, which makes the call.
+        self.tail_call = Some(TailCall {
+            proc: proc.clone(),
+            args: argv,
+            span,
+        });
+        Value::Void
+    }
+
+    /// Applies `kind` to `lhs_value`, written at `lhs`, and `rhs_value`,
+    /// written at `rhs`. AND and OR are left to the caller, since they skip
+    /// evaluating `rhs` when `lhs` decides the result.
+    pub(crate) fn binary_op(
+        &mut self,
+        kind: BinaryOpKind,
+        lhs_value: Value,
+        rhs_value: Value,
+        lhs: Span,
+        rhs: Span,
+    ) -> Value {
+        let span = Span {
+            start: lhs.start,
+            end: rhs.end,
+        };
+
+        let comparison = match kind {
+            BinaryOpKind::Equal => return Value::Bool(lhs_value == rhs_value),
+            BinaryOpKind::NotEqual => return Value::Bool(lhs_value != rhs_value),
+            BinaryOpKind::Greater
+            | BinaryOpKind::GreaterEqual
+            | BinaryOpKind::Less
+            | BinaryOpKind::LessEqual => true,
+            _ => false,
+        };
+        match (&lhs_value, &rhs_value) {
+            // Text is ordered character by character, as in a dictionary but
+            // with all uppercase letters before lowercase ones.
+            (Value::String(s1), Value::String(s2)) if comparison => {
+                let ordering = s1.as_str().cmp(s2.as_str());
+                return Value::Bool(match kind {
+                    BinaryOpKind::Greater => ordering.is_gt(),
+                    BinaryOpKind::GreaterEqual => ordering.is_ge(),
+                    BinaryOpKind::Less => ordering.is_lt(),
+                    _ => ordering.is_le(),
+                });
+            }
+            (Value::String(_), Value::Number(_)) | (Value::Number(_), Value::String(_))
+                if comparison =>
+            {
+                let (text, text_span) = match &lhs_value {
+                    Value::String(_) => (&lhs_value, lhs),
+                    _ => (&rhs_value, rhs),
+                };
+                let help = match text {
+                    Value::String(s) if s.as_str().trim().parse::<f64>().is_ok() => format!(
+                        "convert the text to a number with `NUMBER({})` first",
+                        self.text(text_span)
+                    ),
+                    _ => "text can only be compared with text, and numbers with numbers".into(),
+                };
+                fail!(
+                    format!("cannot compare {lhs_value:?} with {rhs_value:?}"),
+                    span,
+                    Some(help)
+                );
+            }
+            _ => {}
+        }
+
+        let Value::Number(n1) = lhs_value else {
+            fail!(
+                format!("{lhs_value:?} is not a number"),
+                lhs,
+                number_help(&lhs_value, &rhs_value, self.text(lhs))
+            );
+        };
+
+        let Value::Number(n2) = rhs_value else {
+            fail!(
+                format!("{rhs_value:?} is not a number"),
+                rhs,
+                number_help(&rhs_value, &lhs_value, self.text(rhs))
+            );
+        };
+
+        let n = match kind {
+            BinaryOpKind::Add => n1 + n2,
+            BinaryOpKind::Sub => n1 - n2,
+            BinaryOpKind::Mul => n1 * n2,
+            BinaryOpKind::Div => n1 / n2,
+            BinaryOpKind::Mod => n1 % n2,
+            BinaryOpKind::Greater => return Value::Bool(n1 > n2),
+            BinaryOpKind::GreaterEqual => return Value::Bool(n1 >= n2),
+            BinaryOpKind::Less => return Value::Bool(n1 < n2),
+            BinaryOpKind::LessEqual => return Value::Bool(n1 <= n2),
+            _ => fail!("unsupported operator", lhs),
+        };
+
+        if self.check_precision && n.is_finite() && n.abs() >= EXACT_INTEGER_LIMIT {
+            self.warn_imprecise(n, span);
+        }
+
+        Value::Number(n)
+    }
+
+    /// The item of `v`, a list, string, or map written at `value`, at `idx`,
+    /// written at `index`. `span` is the whole `value[index]`.
+    pub(crate) fn index(
+        &self,
+        v: &Value,
+        idx: Value,
+        value: Span,
+        index: Span,
+        span: Span,
+    ) -> Value {
+        if let Value::Map(map) = v {
+            tee!(check_key(&idx, index));
+            let Some(v) = map.borrow().get(&idx).cloned() else {
+                fail!(
+                    format!("key {idx:?} is not in the map"),
+                    span,
+                    Some(format!(
+                        "check with `CONTAINS_KEY({}, {})` first",
+                        self.text(value),
+                        self.text(index)
+                    ))
+                );
+            };
+            return v;
+        }
+
+        let Value::Number(idx) = idx else {
+            fail!(format!("{idx:?} is not an integer"), span);
+        };
+
+        if idx.floor() != idx {
+            fail!(format!("index {idx} is not an integer"), index);
+        }
+
+        // Strings are indexed by character, like lists by item.
+        if let Value::String(s) = v {
+            return match (idx as usize)
+                .checked_sub(1)
+                .and_then(|idx| s.char_at(idx))
+            {
+                Some(c) => c.to_string().into(),
+                None => fail!(
+                    format!(
+                        "index {idx} is out of string range (length: {})",
+                        s.char_len()
+                    ),
+                    span,
+                    index_help(idx, self.text(value))
+                ),
+            };
+        }
+
+        let Value::Array(array) = v else {
+            unreachable!();
+        };
+        let array = array.borrow();
+        match (idx as usize)
+            .checked_sub(1)
+            .and_then(|idx| array.items.get(idx))
+        {
+            Some(v) => v.clone(),
+            None => fail!(
+                format!(
+                    "index {idx} is out of array range (length: {})",
+                    array.items.len()
+                ),
+                span,
+                index_help(idx, self.text(value))
+            ),
+        }
+    }
+
+    /// Fails unless `indexv`, written at `index`, is a position in `rootv`,
+    /// the list written at `root`, that can be assigned to, or a key for
+    /// `rootv`, a map. `span` is the assignment statement.
+    pub(crate) fn check_index(
+        &self,
+        rootv: &Value,
+        indexv: &Value,
+        root: Span,
+        index: Span,
+        span: Span,
+    ) -> Value {
+        let array = match rootv {
+            Value::Array(array) => array,
+            Value::Map(_) => return check_key(indexv, index),
+            _ => unreachable!(),
+        };
+
+        let Value::Number(idx) = *indexv else {
+            fail!(format!("{indexv:?} is not a number"), index);
+        };
+
+        if idx.floor() != idx {
+            fail!(format!("index {idx} is not an integer"), index);
+        }
+
+        let len = array.borrow().items.len();
+        if !(1..=len).contains(&(idx as usize)) {
+            fail!(
+                format!("index is out of bounds: the length is {len:?} but the index is {idx}"),
+                span,
+                index_help(idx, self.text(root))
+            );
+        }
+
+        Value::Void
+    }
+
+    /// Assigns `value` to `rootv[indexv]` after [`VM::check_index`], checking
+    /// again in case evaluating `value` changed the list.
+    pub(crate) fn store_index(
+        &mut self,
+        rootv: &Value,
+        indexv: &Value,
+        value: Value,
+        root: Span,
+        index: Span,
+        span: Span,
+    ) -> Value {
+        tee!(self.check_index(rootv, indexv, root, index, span));
+
+        if let Value::Map(map) = rootv {
+            let mut map = map.borrow_mut();
+            if !map.contains_key(indexv) {
+                let mut res = self.add_items(map.entries.len() + 1, 1);
+                if let Value::Exception(e) = &mut res {
+                    e.span = span;
+                    return res;
                 }
+            }
+            map.insert(indexv.clone(), value);
+            return Value::Void;
+        }
+
+        let (Value::Array(array), Value::Number(idx)) = (rootv, indexv) else {
+            unreachable!();
+        };
+        array.borrow_mut().items[*idx as usize - 1] = value;
+        Value::Void
+    }
+
+    /// Builds the map written at `span` from its entries, whose keys were
+    /// written at `keys`. A key written twice keeps the last value.
+    pub(crate) fn map(&mut self, entries: Vec<(Value, Value)>, keys: &[Span], span: Span) -> Value {
+        let mut map = Map::default();
+        for ((key, value), &key_span) in entries.into_iter().zip(keys) {
+            tee!(check_key(&key, key_span));
+            map.insert(key, value);
+        }
+
+        let mut res = self.add_items(map.entries.len(), map.entries.len());
+        if let Value::Exception(e) = &mut res {
+            e.span = span;
+            return res;
+        }
+
+        Value::Map(Gc::new(GcCell::new(map)))
+    }
+
+    /// Assigns the items of `v`, written at `value`, to the variables named at
+    /// `names`, as `a, b <- v` does. `span` is the statement.
+    pub(crate) fn destructure(
+        &mut self,
+        env: &Rc<RefCell<Env>>,
+        names: &[Span],
+        v: Value,
+        value: Span,
+        span: Span,
+    ) -> Value {
+        let Value::Array(array) = &v else {
+            fail!(format!("{v:?} is not an array"), value);
+        };
+
+        let items = array.borrow().items.clone();
+        if items.len() != names.len() {
+            fail!(
+                format!(
+                    "cannot assign {} items to {} variables",
+                    items.len(),
+                    names.len()
+                ),
+                span
+            );
+        }
+
+        for (&name, item) in names.iter().zip(items) {
+            self.assign(env, name, item.clone(), span);
+            self.explain(span, |vm| format!("assign {item:?} to {}", vm.text(name)));
+        }
+
+        Value::Void
+    }
+
+    /// Defines `proc` in `env` under its name.
+    pub(crate) fn define_procedure(&mut self, env: &Rc<RefCell<Env>>, proc: Rc<Procedure>) {
+        let (name, span) = (self.text(proc.name), proc.span);
+        env.borrow_mut()
+            .entries
+            .insert(name.into(), Value::Procedure(proc));
+        self.explain(span, |_| format!("define procedure {name}"));
+    }
+
+    /// Runs the statements of an imported file, `scope`, in variables of their
+    /// own, and then defines the PROCEDUREs they defined in `env`.
+    pub(crate) fn import(
+        &mut self,
+        path: &str,
+        scope: Option<&[Stmt]>,
+        span: Span,
+        env: &Rc<RefCell<Env>>,
+    ) -> Value {
+        let Some(scope) = scope else {
+            fail!(
+                format!("\"{path}\" was not loaded"),
+                span,
+                Some("IMPORT only works in programs run from a file, such as with `aps run`".into())
+            );
+        };
+
+        let mut module = Env::new();
+        stdlib::inject(&mut module);
+        let module = Rc::new(RefCell::new(module));
+        tee!(self.run(scope, module.clone()));
+
+        let procedures = module
+            .borrow()
+            .entries
+            .iter()
+            .filter(|(_, value)| matches!(value, Value::Procedure(_)))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        env.borrow_mut().entries.extend(procedures);
+        self.explain(span, |_| format!("import {path}"));
+        Value::Void
+    }
+
+    fn warn_imprecise(&mut self, n: f64, span: Span) {
+        if self.warnings.iter().any(|warning| warning.span == span) {
+            return;
+        }
+
+        self.warnings.push(Warning {
+            message: format!(
+                "{n} may have been rounded; whole numbers are only exact below {EXACT_INTEGER_LIMIT}"
+            ),
+            span,
+        });
+    }
+
+    /// Accounts for adding `added` items to a list or set, which will then
+    /// hold `len` items. Fails without a location, like a builtin, if that
+    /// goes past [`VM::max_array_len`] or [`VM::max_items`].
+    pub fn add_items(&mut self, len: usize, added: usize) -> Value {
+        let max = self.max_array_len.unwrap_or(MAX_LEN);
+        if len > max {
+            fail!(format!("a list cannot hold more than {max} items"), BUILTIN);
+        }
+
+        self.stats.items += added as u64;
+        if let Some(max) = self.max_items.filter(|&max| self.stats.items > max as u64) {
+            fail!(
+                format!("the program cannot add more than {max} items to lists"),
+                BUILTIN
+            );
+        }
+
+        Value::Void
+    }
+
+    /// Fails without a location, like a builtin, if a string of `len`
+    /// characters would be longer than [`VM::max_string_len`]. Builtins check
+    /// before building the string where they can.
+    pub fn check_string_len(&self, len: usize) -> Value {
+        let max = self.max_string_len.unwrap_or(MAX_LEN);
+        if len > max {
+            fail!(
+                format!("a string cannot be longer than {max} characters"),
+                BUILTIN
+            );
+        }
+
+        Value::Void
+    }
+
+    /// Reports a step to [`VM::on_explain`]. The text is only built if there
+    /// is a hook to receive it.
+    fn explain(&mut self, span: Span, text: impl FnOnce(&Self) -> String) {
+        if self.on_explain.is_none() {
+            return;
+        }
+
+        let text = text(self);
+        if let Some(on_explain) = &mut self.on_explain {
+            on_explain(&Explanation { text, span });
+        }
+    }
+
+    /// Sets the variable named at `name` where it is defined, or in `env` if it
+    /// is not defined yet. `span` is the assignment statement.
+    pub(crate) fn assign(&mut self, env: &Rc<RefCell<Env>>, name: Span, value: Value, span: Span) {
+        let name = self.text(name);
+        let mut cur_env = env.clone();
+        let mut old = None;
+        loop {
+            if let Some(assigner) = cur_env.borrow_mut().entries.get_mut(name) {
+                old = Some(std::mem::replace(assigner, value.clone()));
+                break;
+            };
+            let b = cur_env.borrow();
+            let child = match &b.parent {
+                Some(p) => p.clone(),
+                _ => {
+                    drop(b);
+                    env.borrow_mut().entries.insert(name.into(), value.clone());
+                    break;
+                }
+            };
+            drop(b);
+            cur_env = child;
+        }
+
+        if let Some(on_assign) = &mut self.on_assign {
+            on_assign(&Assignment {
+                name,
+                old: old.as_ref(),
+                new: &value,
+                span,
+            });
+        }
+    }
+
+    /// Sets the variable named at `span` in `env` itself, as a `FOR EACH` loop
+    /// does for its item.
+    pub(crate) fn define(&mut self, env: &Rc<RefCell<Env>>, span: Span, value: Value) {
+        let name = self.text(span);
+        let old = env.borrow_mut().entries.insert(name.into(), value.clone());
+
+        if let Some(on_assign) = &mut self.on_assign {
+            on_assign(&Assignment {
+                name,
+                old: old.as_ref(),
+                new: &value,
+                span,
+            });
+        }
+    }
 
-                fail!(format!("{v:?} is not a function"), calle.span());
+    /// The source text at `span`.
+    pub(crate) fn text(&self, span: Span) -> &'a str {
+        &self.source[Into::<std::ops::Range<_>>::into(span)]
+    }
+
+    /// Like [`VM::eval_scope`], but when `scope` ends with an expression and
+    /// does not RETURN first, gives that expression's value instead of
+    /// [`Value::Void`].
+    pub fn eval_scope_value(&mut self, scope: &[Stmt], env: Rc<RefCell<Env>>) -> Value {
+        let Some((Stmt::Expr(expr), rest)) = scope.split_last() else {
+            return self.eval_scope(scope, env);
+        };
+
+        match tee!(self.eval_scope(rest, env.clone())) {
+            Value::Void => {
+                self.stats.statements += 1;
+                self.eval_expr(expr, env)
             }
+            value => value,
         }
     }
 
+    /// Whether [`VM::interrupt`] has been set.
+    pub(crate) fn interrupted(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+    }
+
     pub fn eval_scope(&mut self, scope: &[Stmt], env: Rc<RefCell<Env>>) -> Value {
         for stmt in scope.iter() {
+            self.stats.statements += 1;
+
+            if self.interrupted() {
+                fail!("the program was interrupted", stmt.span());
+            }
+            if !self.take_step() {
+                fail!(
+                    "execution limit exceeded",
+                    stmt.span(),
+                    Some(STEPS_HELP.into())
+                );
+            }
+
             match stmt {
-                Stmt::Expr(e) => _ = tee!(self.eval_expr(e, env.clone())),
+                Stmt::Expr(e) => {
+                    let v = tee!(self.eval_expr(e, env.clone()));
+                    self.explain(e.span(), |vm| match (e, &v) {
+                        (Expr::FnCall { .. }, Value::Void) => format!("call {}", vm.text(e.span())),
+                        (Expr::FnCall { .. }, _) => {
+                            format!("call {}, which returned {v:?}", vm.text(e.span()))
+                        }
+                        _ => format!("evaluate {} to {v:?}", vm.text(e.span())),
+                    });
+                }
                 Stmt::VarAssign { name, value } => {
                     let v = tee!(self.eval_expr(value, env.clone())).clone();
-                    let mut cur_env = env.clone();
-                    let name = self.source[Into::<std::ops::Range<_>>::into(*name)].to_string();
-                    loop {
-                        if let Some(assigner) = cur_env.borrow_mut().entries.get_mut(&name) {
-                            *assigner = v.clone();
-                            break;
-                        };
-                        let b = cur_env.borrow();
-                        let child = match &b.parent {
-                            Some(p) => p.clone(),
-                            _ => {
-                                drop(b);
-                                env.borrow_mut().entries.insert(name, v.clone());
-                                break;
-                            }
-                        };
-                        drop(b);
-                        cur_env = child;
-                    }
+                    self.assign(&env, *name, v.clone(), stmt.span());
+                    self.explain(stmt.span(), |vm| {
+                        format!("assign {v:?} to {}", vm.text(*name))
+                    });
+                }
+                Stmt::Destructure { names, value, .. } => {
+                    let v = tee!(self.eval_expr(value, env.clone()));
+                    tee!(self.destructure(&env, names, v, value.span(), stmt.span()));
                 }
                 Stmt::Procedure(proc) => {
                     // TODO: this clone is wildly inefficient
-                    env.borrow_mut().entries.insert(
-                        self.source[Into::<std::ops::Range<_>>::into(proc.name)].into(),
-                        Value::Procedure(Rc::new(proc.clone())),
-                    );
+                    self.define_procedure(&env, Rc::new(proc.clone()));
                 }
                 Stmt::IndexAssign { root, index, value } => {
                     let rootv = tee!(self.eval_expr(root, env.clone()));
-                    let Value::Array(rootv) = &rootv else {
-						fail!(format!("{rootv:?} is not an array"), root.span());
-					};
+                    if !matches!(rootv, Value::Array(_) | Value::Map(_)) {
+                        fail!(format!("{rootv:?} is not an array"), root.span());
+                    }
 
                     let indexv = tee!(self.eval_expr(index, env.clone()));
-                    let Value::Number(idx) = &indexv else {
-						fail!(format!("{indexv:?} is not a number"), index.span());
-					};
-
-                    let mut rootv = rootv.borrow_mut();
-                    let Some(vptr) = rootv.items.get_mut(*idx as u32 as usize - 1) else {
-						fail!(format!("index is out of bounds: the length is {:?} but the index is {idx}", rootv.items.len()), stmt.span());
-					};
+                    tee!(self.check_index(&rootv, &indexv, root.span(), index.span(), stmt.span()));
+
+                    // The list is not borrowed while the value is evaluated,
+                    // since the value may read or change it.
+                    let v = tee!(self.eval_expr(value, env.clone()));
+                    tee!(self.store_index(
+                        &rootv,
+                        &indexv,
+                        v.clone(),
+                        root.span(),
+                        index.span(),
+                        stmt.span()
+                    ));
+
+                    self.explain(stmt.span(), |vm| {
+                        format!("assign {v:?} to {}[{indexv:?}]", vm.text(root.span()))
+                    });
+                }
+                Stmt::Return { value, tail, .. } => {
+                    let mut call = value;
+                    while let Expr::Paren { value, .. } = call {
+                        call = value;
+                    }
+                    if let (true, Expr::FnCall { calle, args, span }) = (tail, call) {
+                        return self.tail_return(stmt.span(), call, calle, args, *span, env);
+                    }
 
-                    *vptr = tee!(self.eval_expr(value, env.clone()));
+                    let v = tee!(self.eval_expr(value, env));
+                    self.explain(stmt.span(), |_| match &v {
+                        Value::Void => "return".into(),
+                        v => format!("return {v:?}"),
+                    });
+                    return v;
                 }
-                Stmt::Return { value, .. } => return self.eval_expr(value, env),
                 Stmt::If {
                     cond,
                     scope,
@@ -454,8 +1952,17 @@ impl<'a> VM<'a> {
                 } => 'blk: {
                     let c1 = tee!(self.eval_expr(cond, env.clone()));
                     let Value::Bool(b) = c1 else {
-						fail!(format!("{c1:?} is not a boolean"), cond.span());
-					};
+                        fail!(format!("{c1:?} is not a boolean"), cond.span());
+                    };
+
+                    self.explain(cond.span(), |vm| {
+                        format!(
+                            "condition {} is {}, {} IF block",
+                            vm.text(cond.span()),
+                            if b { "TRUE" } else { "FALSE" },
+                            if b { "entering" } else { "skipping" }
+                        )
+                    });
 
                     if b {
                         let scope_val = tee!(self.eval_scope(scope, env.clone()));
@@ -468,64 +1975,91 @@ impl<'a> VM<'a> {
                     }
 
                     for else_if in else_ifs.iter() {
-                        let Value::Bool(b) = tee!(self.eval_expr(&else_if.cond, env.clone())) else {
-							panic!();
-						};
+                        let c = tee!(self.eval_expr(&else_if.cond, env.clone()));
+                        let Value::Bool(b) = c else {
+                            fail!(format!("{c:?} is not a boolean"), else_if.cond.span());
+                        };
+
+                        self.explain(else_if.cond.span(), |vm| {
+                            format!(
+                                "condition {} is {}, {} ELSE IF block",
+                                vm.text(else_if.cond.span()),
+                                if b { "TRUE" } else { "FALSE" },
+                                if b { "entering" } else { "skipping" }
+                            )
+                        });
 
                         if b {
                             let scope_val = tee!(self.eval_scope(&else_if.scope, env.clone()));
 
                             let Value::Void = scope_val else {
-								return scope_val;
-							};
+                                return scope_val;
+                            };
 
                             break 'blk;
                         }
                     }
 
                     if let Some(els) = els {
+                        self.explain(stmt.span(), |_| {
+                            "no condition was TRUE, entering ELSE block".into()
+                        });
                         let scope_val = tee!(self.eval_scope(els, env.clone()));
 
                         let Value::Void = scope_val else {
-							return scope_val;
-						};
+                            return scope_val;
+                        };
 
                         break 'blk;
                     }
                 }
                 Stmt::RepeatN { n: n_expr, scope } => {
                     let count = tee!(self.eval_expr(n_expr, env.clone()));
+                    tee!(check_repeat_count(&count, n_expr.span()));
 
                     let Value::Number(n) = count else {
-						fail!(format!("{count:?} is not a number"), n_expr.span());
-					};
-
-                    if n < 0. {
-                        fail!(format!("{count:?} is not positive"), n_expr.span());
-                    }
-
-                    if n.floor() != n {
-                        fail!(format!("{count:?} is not an integer"), n_expr.span());
-                    }
-
+                        unreachable!();
+                    };
                     let mut n = n as u32;
+                    self.explain(n_expr.span(), |_| match n {
+                        1 => "repeat the block 1 time".into(),
+                        n => format!("repeat the block {n} times"),
+                    });
 
                     while n > 0 {
                         let val = tee!(self.eval_scope(scope, env.clone()));
 
                         let Value::Void = val else {
-							return val;
-						};
+                            return val;
+                        };
 
                         n -= 1;
                     }
                 }
                 Stmt::RepeatUntil { cond, scope } => loop {
+                    // An empty block has no statements to check for an
+                    // interrupt.
+                    if self.interrupted() {
+                        fail!("the program was interrupted", cond.span());
+                    }
+
                     let val = tee!(self.eval_expr(cond, env.clone()));
 
                     let Value::Bool(b) = val else {
-						fail!(format!("{val:?} is not a boolean"), cond.span());
-					};
+                        fail!(format!("{val:?} is not a boolean"), cond.span());
+                    };
+
+                    self.explain(cond.span(), |vm| {
+                        format!(
+                            "condition {} is {}",
+                            vm.text(cond.span()),
+                            if b {
+                                "TRUE, leaving REPEAT UNTIL loop"
+                            } else {
+                                "FALSE, repeating the block"
+                            }
+                        )
+                    });
 
                     if b {
                         break;
@@ -533,42 +2067,60 @@ impl<'a> VM<'a> {
 
                     let val = tee!(self.eval_scope(scope, env.clone()));
                     let Value::Void = val else {
-						return val;
-					};
+                        return val;
+                    };
                 },
+                Stmt::Import { span, path, scope } => {
+                    tee!(self.import(path, scope.as_deref(), *span, &env));
+                }
+                Stmt::Custom {
+                    span,
+                    handler,
+                    args,
+                } => {
+                    let Some(callee) = env.borrow().get(handler) else {
+                        fail!(format!("'{handler}' is not defined"), *span);
+                    };
+
+                    let mut argv = Vec::with_capacity(args.len());
+
+                    for arg in args.iter() {
+                        argv.push(tee!(self.eval_expr(arg, env.clone())));
+                    }
+
+                    _ = tee!(self.call(&callee, &argv, args, *span, env.clone()));
+                    self.explain(*span, |vm| format!("run {}", vm.text(*span)));
+                }
                 Stmt::For {
                     alias,
+                    index,
                     array,
                     scope,
                 } => {
                     let arr = tee!(self.eval_expr(array, env.clone()));
-                    let Value::Array(arr) = &arr else {
-						fail!(format!("{:?} is not an array", array), array.span());
-					};
+                    let items = match ForEach::new(arr, array.span()) {
+                        Ok(items) => items,
+                        Err(e) => return e,
+                    };
+                    let len = items.len;
 
                     let mut i = 0;
-                    let len = arr.borrow().items.len();
-
-                    loop {
-                        if i >= len {
-                            break;
+                    while let Some(val) = items.get(i) {
+                        self.define(&env, *alias, val.clone());
+                        if let Some(index) = index {
+                            self.define(&env, *index, Value::Number((i + 1) as f64));
                         }
 
-                        let arrb = arr.borrow();
-                        let Some(val) = arrb.items.get(i) else {
-							break;
-						};
-
-                        env.borrow_mut().entries.insert(
-                            self.source[alias.start as usize..alias.end as usize].into(),
-                            val.clone(),
-                        );
+                        self.explain(*alias, |vm| {
+                            let name = vm.text(*alias);
+                            format!("assign {val:?} to {name}, item {} of {len}", i + 1)
+                        });
 
                         let scope_val = tee!(self.eval_scope(scope, env.clone()));
 
                         let Value::Void = scope_val else {
-							return scope_val;
-						};
+                            return scope_val;
+                        };
 
                         i += 1;
                     }
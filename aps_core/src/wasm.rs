@@ -1,28 +1,28 @@
-use crate::{
-    lexer::Token,
-    parser::Parser,
-    stdlib,
-    vm::{Env, Value, VM},
-};
-use codespan_reporting::{
-    diagnostic::{Diagnostic, Label},
-    files::{self, SimpleFiles},
-    term::{
-        self,
-        termcolor::{ColorChoice, StandardStream, WriteColor},
-    },
-};
-use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, collections::HashMap, io::Write as WriteIO, rc::Rc};
-use std::{fmt::Write as WriteFmt, slice::Iter};
-use wasm_bindgen::{prelude::*, JsObject};
+#[cfg(not(feature = "js-compact"))]
+use crate::engine::RunOutcome;
+#[cfg(feature = "js-compact")]
+use crate::vm::Stats;
+use crate::{engine::Engine, parser::Parser, vm::Value};
+#[cfg(feature = "js-compact")]
+use codespan_reporting::diagnostic::{LabelStyle, Severity};
+#[cfg(not(feature = "js-compact"))]
+use codespan_reporting::term::{self, termcolor::WriteColor};
+use codespan_reporting::{diagnostic::Diagnostic, files::SimpleFiles};
+use js_sys::{Array, Function, Object};
+use serde::Serialize;
+use std::collections::BTreeMap;
+#[cfg(not(feature = "js-compact"))]
+use std::io::Write as WriteIO;
+use wasm_bindgen::{prelude::*, JsCast};
 
+#[cfg(not(feature = "js-compact"))]
 struct S(String);
 
+#[cfg(not(feature = "js-compact"))]
+
 impl WriteIO for S {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0
-            .write_str(unsafe { std::str::from_utf8_unchecked(buf) });
+        self.0.push_str(&String::from_utf8_lossy(buf));
         Ok(buf.len())
     }
 
@@ -31,6 +31,7 @@ impl WriteIO for S {
     }
 }
 
+#[cfg(not(feature = "js-compact"))]
 impl WriteColor for S {
     fn supports_color(&self) -> bool {
         false
@@ -51,117 +52,225 @@ pub fn validate(input: &str) -> Result<JsValue, JsValue> {
     let fid = files.add("<file>", &input);
 
     let mut parser = Parser::new(fid, input.as_bytes());
-    parser.lex.next();
-
-    _ = parser.parse_scope(true);
-
-    if parser.diagnostics.len() == 0 && parser.lex.token != Token::EOF {
-        parser.diagnostics.push(
-            Diagnostic::error()
-                .with_message(format!(
-                    "expected statement, found {}",
-                    parser.lex.token.as_ref(),
-                ))
-                .with_labels(vec![Label::primary(parser.fid, parser.lex.span())
-                    .with_message(format!("expected statement"))]),
-        );
-    }
 
-    Ok(serde_wasm_bindgen::to_value(&parser.diagnostics)?)
+    _ = parser.parse_program();
+
+    #[cfg(feature = "js-compact")]
+    let diagnostics = compact(&parser.diagnostics);
+    #[cfg(not(feature = "js-compact"))]
+    let diagnostics = parser.diagnostics;
+
+    Ok(serde_wasm_bindgen::to_value(&diagnostics)?)
+}
+
+/// A diagnostic reduced to what an editor needs to show it, for hosts that
+/// render errors themselves.
+#[cfg(feature = "js-compact")]
+#[derive(Serialize)]
+struct CompactDiagnostic {
+    severity: &'static str,
+    message: String,
+    /// Where the primary label is, as byte offsets.
+    start: usize,
+    end: usize,
+    /// The message of the primary label.
+    label: String,
+    notes: Vec<String>,
+}
+
+#[cfg(feature = "js-compact")]
+fn compact(diagnostics: &[Diagnostic<usize>]) -> Vec<CompactDiagnostic> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let label = diagnostic
+                .labels
+                .iter()
+                .find(|label| label.style == LabelStyle::Primary);
+
+            CompactDiagnostic {
+                severity: match diagnostic.severity {
+                    Severity::Bug => "bug",
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Note => "note",
+                    Severity::Help => "help",
+                },
+                message: diagnostic.message.clone(),
+                start: label.map_or(0, |label| label.range.start),
+                end: label.map_or(0, |label| label.range.end),
+                label: label.map(|label| label.message.clone()).unwrap_or_default(),
+                notes: diagnostic.notes.clone(),
+            }
+        })
+        .collect()
+}
+
+/// What `interpret` returns: the run's [`RunOutcome`] along with its errors
+/// rendered as they would appear in a terminal.
+#[cfg(not(feature = "js-compact"))]
+#[derive(Serialize)]
+struct Report {
+    #[serde(flatten)]
+    outcome: RunOutcome,
+    log: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: Option<BTreeMap<String, Value>>,
 }
 
-#[derive(Serialize, Deserialize)]
-enum RunStatus {
-    Ok,
-    Data {
-        log: String,
-        errors: Vec<Diagnostic<usize>>,
-    },
+/// What `interpret` returns with `js-compact`: the value, statistics, and
+/// the parse errors or exception as compact diagnostics, leaving the
+/// rendering to the host.
+#[cfg(feature = "js-compact")]
+#[derive(Serialize)]
+struct Report {
+    value: Value,
+    diagnostics: Vec<CompactDiagnostic>,
+    stats: Stats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: Option<BTreeMap<String, Value>>,
 }
 
 #[wasm_bindgen]
 pub fn interpret(input: &str) -> Result<JsValue, JsValue> {
+    run(input, Engine::new(), false)
+}
+
+/// Like `interpret`, but also returns `variables`, an object of the global
+/// variables as they were when the program stopped, for showing next to the
+/// output. PROCEDUREs are left out.
+#[wasm_bindgen]
+pub fn interpret_with_variables(input: &str) -> Result<JsValue, JsValue> {
+    run(input, Engine::new(), true)
+}
+
+/// Like `interpret`, but stops the program with an exception once it has run
+/// `max_steps` statements and expressions, so that a loop that never ends
+/// does not hang the page.
+#[wasm_bindgen]
+pub fn interpret_with_max_steps(input: &str, max_steps: u32) -> Result<JsValue, JsValue> {
+    run(input, Engine::new().with_max_steps(max_steps.into()), false)
+}
+
+/// Like `interpret`, but answers `INPUT` calls by calling `answer` with the
+/// prompt instead of showing one. `answer` returns the answer as a string, or
+/// `undefined` when it has none left.
+#[wasm_bindgen]
+pub fn interpret_with_input(input: &str, answer: Function) -> Result<JsValue, JsValue> {
+    let engine = Engine::new().with_input_provider(move |prompt: &str| {
+        answer
+            .call1(&JsValue::NULL, &prompt.into())
+            .ok()?
+            .as_string()
+    });
+    run(input, engine, false)
+}
+
+/// Like `interpret`, but calls `on_output` with the text of each `DISPLAY` as
+/// the program shows it, so a page can render output as it arrives, and
+/// answers `INPUT` calls like `interpret_with_input` does with `get_input`.
+#[wasm_bindgen]
+pub fn interpret_with_io(
+    input: &str,
+    on_output: Function,
+    get_input: Function,
+) -> Result<JsValue, JsValue> {
+    let engine = Engine::new()
+        .on_display(move |text| {
+            _ = on_output.call1(&JsValue::NULL, &text.into());
+        })
+        .with_input_provider(move |prompt: &str| {
+            get_input
+                .call1(&JsValue::NULL, &prompt.into())
+                .ok()?
+                .as_string()
+        });
+    run(input, engine, false)
+}
+
+/// Like `interpret`, but first defines a global variable for each property of
+/// `globals`.
+#[wasm_bindgen]
+pub fn interpret_with_globals(input: &str, globals: JsValue) -> Result<JsValue, JsValue> {
+    let globals: Object = globals.dyn_into()?;
+
+    let mut engine = Engine::new();
+    for entry in Object::entries(&globals).iter() {
+        let entry = Array::from(&entry);
+        let name = entry.get(0).as_string().unwrap_or_default();
+        engine = engine.with_global(name, js_to_value(&entry.get(1))?);
+    }
+
+    run(input, engine, false)
+}
+
+fn js_to_value(value: &JsValue) -> Result<Value, JsValue> {
+    if let Some(value) = value.as_bool() {
+        Ok(value.into())
+    } else if let Some(value) = value.as_f64() {
+        Ok(Value::Number(value))
+    } else if let Some(value) = value.as_string() {
+        Ok(value.into())
+    } else if Array::is_array(value) {
+        let items = Array::from(value)
+            .iter()
+            .map(|item| js_to_value(&item))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items.into())
+    } else {
+        Err(JsValue::from_str(
+            "globals must be booleans, numbers, strings, or arrays",
+        ))
+    }
+}
+
+/// The global variables of a finished run, sorted by name, if `snapshot` was
+/// asked for.
+fn variables(
+    outcome: &crate::engine::RunOutcome,
+    snapshot: bool,
+) -> Option<BTreeMap<String, Value>> {
+    snapshot.then(|| {
+        outcome
+            .globals()
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::Procedure(_)))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    })
+}
+
+#[cfg(feature = "js-compact")]
+fn run(input: &str, engine: Engine, snapshot: bool) -> Result<JsValue, JsValue> {
+    let outcome = engine.run(input);
+
+    let report = Report {
+        diagnostics: compact(&outcome.errors(0)),
+        variables: variables(&outcome, snapshot),
+        value: outcome.value,
+        stats: outcome.stats,
+    };
+    Ok(report.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+#[cfg(not(feature = "js-compact"))]
+fn run(input: &str, engine: Engine, snapshot: bool) -> Result<JsValue, JsValue> {
     let mut files = SimpleFiles::new();
     let fid = files.add("<file>", &input);
 
-    let mut parser = Parser::new(fid, input.as_bytes());
-    parser.lex.next();
-
-    let scope = parser.parse_scope(true);
-
-    if parser.diagnostics.len() != 0 || parser.lex.token != Token::EOF {
-        let mut writer = S(String::new());
-        let config = codespan_reporting::term::Config::default();
-
-        if parser.lex.token != Token::EOF {
-            parser.diagnostics.push(
-                Diagnostic::error()
-                    .with_message(format!(
-                        "expected statement, found {}",
-                        parser.lex.token.as_ref(),
-                    ))
-                    .with_labels(vec![Label::primary(parser.fid, parser.lex.span())
-                        .with_message(format!("expected statement"))]),
-            );
-        }
-
-        for diagnostic in parser.diagnostics.iter() {
-            term::emit(&mut writer, &config, &files, diagnostic);
-        }
-
-        return Ok(serde_wasm_bindgen::to_value(&RunStatus::Data {
-            log: writer.0,
-            errors: parser.diagnostics,
-        })?);
-    }
+    let outcome = engine.run(input);
 
-    let mut vm = VM::new(input);
-
-    let mut env = Env::new();
-    stdlib::inject(&mut env);
-    let value = vm.eval_scope(&scope.unwrap(), Rc::new(RefCell::new(env)));
-
-    if let Value::Exception(e) = &value {
-        let config = codespan_reporting::term::Config::default();
-        let mut writer = S(String::new());
-
-        term::emit(
-            &mut writer,
-            &config,
-            &files,
-            &Diagnostic::error()
-                .with_message(&e.message)
-                .with_labels(vec![Label::primary(fid, e.span)]),
-        )
-        .unwrap();
-
-        for itm in e.stack.iter() {
-            term::emit(
-                &mut writer,
-                &config,
-                &files,
-                &Diagnostic::note()
-                    .with_message("called here")
-                    .with_labels(vec![Label::primary(fid, *itm)]),
-            )
-            .unwrap();
-        }
-
-        let mut diags = [Diagnostic::error()
-            .with_message(&e.message)
-            .with_labels(vec![Label::primary(fid, e.span)])]
-        .into_iter()
-        .chain(e.stack.iter().map(|itm| {
-            Diagnostic::note()
-                .with_message("called here")
-                .with_labels(vec![Label::primary(fid, *itm)])
-        }));
-
-        return Ok(serde_wasm_bindgen::to_value(&RunStatus::Data {
-            log: writer.0,
-            errors: diags.collect(),
-        })?);
+    let config = codespan_reporting::term::Config::default();
+    let mut writer = S(String::new());
+    for diagnostic in outcome.errors(fid).iter() {
+        term::emit(&mut writer, &config, &files, diagnostic)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
     }
 
-    Ok(serde_wasm_bindgen::to_value(&RunStatus::Ok)?)
+    let report = Report {
+        variables: variables(&outcome, snapshot),
+        outcome,
+        log: writer.0,
+    };
+    Ok(report.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
 }
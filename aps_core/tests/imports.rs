@@ -0,0 +1,74 @@
+use std::{env, fs, path::PathBuf};
+
+use aps_core::{
+    engine::{parse_with, Engine},
+    imports::{self, Program},
+    permissions::Permissions,
+};
+
+/// Writes `files` to a directory of their own for the test `name` and loads
+/// the first of them.
+fn load(name: &str, files: &[(&str, &str)]) -> Program {
+    let dir = env::temp_dir().join(format!("aps-imports-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (file, source) in files {
+        fs::write(dir.join(file), source).unwrap();
+    }
+
+    let path: PathBuf = dir.join(files[0].0);
+    let options = Engine::new().parse_options();
+    let program = imports::load(
+        &path,
+        files[0].1,
+        parse_with(files[0].1, options),
+        options,
+        &Permissions::default(),
+    );
+    fs::remove_dir_all(dir).unwrap();
+    program
+}
+
+#[test]
+fn files_that_import_each_other_are_a_cycle() {
+    let program = load(
+        "cycle",
+        &[
+            ("main.aps", "IMPORT \"a.aps\""),
+            ("a.aps", "IMPORT \"b.aps\""),
+            ("b.aps", "IMPORT \"a.aps\""),
+        ],
+    );
+
+    let [diagnostic] = &program.diagnostics[..] else {
+        panic!("{:?}", program.diagnostics);
+    };
+    assert_eq!(diagnostic.message, "\"a.aps\" is already being imported");
+    assert_eq!(
+        diagnostic.notes,
+        ["the files import each other in a cycle: a.aps \u{2192} b.aps \u{2192} a.aps"]
+    );
+
+    // The error points at the IMPORT in b.aps.
+    let (fid, range) = program.relocate_range(diagnostic.labels[0].range.clone());
+    assert!(program.files[fid].name.ends_with("b.aps"));
+    assert_eq!(range, 0..14);
+}
+
+#[test]
+fn syntax_errors_in_an_imported_file_point_into_it() {
+    let program = load(
+        "syntax",
+        &[
+            ("main.aps", "IMPORT \"lib.aps\"\nDISPLAY(1)"),
+            ("lib.aps", "x <- 1\ny <- (2"),
+        ],
+    );
+
+    let mut diagnostics = program.diagnostics.clone();
+    assert!(!diagnostics.is_empty());
+    program.relocate(&mut diagnostics);
+    for label in diagnostics.iter().flat_map(|d| &d.labels) {
+        assert!(program.files[label.file_id].name.ends_with("lib.aps"));
+        assert!(label.range.start >= "x <- 1\n".len());
+    }
+}
@@ -0,0 +1,63 @@
+use aps_core::{
+    ast::MAX_NESTING,
+    bytecode::Backend,
+    engine::{run_isolated, Engine},
+};
+
+// Parsing right up to the limit takes more stack in debug builds than the
+// test harness gives each test, so programs run on their own thread.
+fn messages(source: &str) -> Vec<String> {
+    run_isolated(source, |engine| engine)
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
+
+#[test]
+fn deeply_nested_parentheses_are_an_error() {
+    let depth = 200_000;
+    let source = format!("x <- {}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(messages(&source), ["expression nested too deeply"]);
+}
+
+#[test]
+fn deeply_nested_negation_is_an_error() {
+    let source = format!("x <- {}1", "- ".repeat(2_000_000));
+    assert_eq!(messages(&source), ["expression nested too deeply"]);
+}
+
+#[test]
+fn long_operator_and_index_chains_are_an_error() {
+    let sum = format!("x <- 1{}", " + 1".repeat(300_000));
+    assert_eq!(messages(&sum), ["expression nested too deeply"]);
+
+    let index = format!("x <- a{}", "[1]".repeat(300_000));
+    assert_eq!(messages(&index), ["expression nested too deeply"]);
+}
+
+#[test]
+fn deeply_nested_blocks_are_an_error() {
+    let depth = 100_000;
+    let source = format!("{}{}", "IF (TRUE) {\n".repeat(depth), "}\n".repeat(depth));
+    let messages = messages(&source);
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].ends_with("nested too deeply"), "{messages:?}");
+}
+
+#[test]
+fn nesting_under_the_limit_runs_on_both_backends() {
+    let depth = MAX_NESTING as usize - 10;
+    let source = format!(
+        "DISPLAY({}1{})\nDISPLAY(0{})",
+        "(".repeat(depth),
+        ")".repeat(depth),
+        " + 1".repeat(depth)
+    );
+    for backend in [Backend::Tree, Backend::Bytecode] {
+        let outcome = run_isolated(&source, |engine: Engine| engine.with_backend(backend));
+        assert!(outcome.diagnostics.is_empty(), "{:?}", outcome.diagnostics);
+        assert!(outcome.exception.is_none(), "{:?}", outcome.exception);
+        assert_eq!(outcome.output, format!("1\n{depth}\n"));
+    }
+}
@@ -0,0 +1,99 @@
+use aps_core::{
+    bytecode::Backend,
+    engine::{Engine, RunOutcome},
+};
+
+/// Runs `source` on both backends, checking that they agree. RANDOM is
+/// seeded so that they can.
+fn run(source: &str) -> RunOutcome {
+    let [tree, bytecode] = [Backend::Tree, Backend::Bytecode].map(|backend| {
+        Engine::new()
+            .capture_output()
+            .with_seed(17)
+            .with_backend(backend)
+            .run(source)
+    });
+    assert!(tree.diagnostics.is_empty(), "{:?}", tree.diagnostics);
+    assert_eq!(tree.captured_output, bytecode.captured_output);
+    assert_eq!(
+        tree.exception.as_ref().map(|e| &e.message),
+        bytecode.exception.as_ref().map(|e| &e.message)
+    );
+    tree
+}
+
+fn output(source: &str) -> String {
+    let outcome = run(source);
+    assert!(outcome.exception.is_none(), "{:?}", outcome.exception);
+    outcome.captured_output
+}
+
+fn error(source: &str) -> String {
+    run(source)
+        .exception
+        .expect("the program should fail")
+        .message
+}
+
+#[test]
+fn integers_past_2_24_display_exactly() {
+    assert_eq!(output("DISPLAY(16777217)"), "16777217\n");
+    assert_eq!(output("DISPLAY(16777216 + 1)"), "16777217\n");
+    assert_eq!(output("DISPLAY(9007199254740991)"), "9007199254740991\n");
+}
+
+#[test]
+fn loop_counters_count_past_2_24() {
+    let source = "
+i <- 16777215
+REPEAT 5 TIMES
+{
+  i <- i + 1
+}
+DISPLAY(i)
+REPEAT UNTIL (i = 16777225)
+{
+  i <- i + 1
+}
+DISPLAY(i)
+";
+    assert_eq!(output(source), "16777220\n16777225\n");
+}
+
+#[test]
+fn random_keeps_large_bounds_exact() {
+    assert_eq!(output("DISPLAY(RANDOM(16777217, 16777217))"), "16777217\n");
+    assert_eq!(
+        error("DISPLAY(RANDOM(16777218, 16777217))"),
+        "the range start 16777218 is greater than its end 16777217"
+    );
+
+    let source = "
+REPEAT 100 TIMES
+{
+  DISPLAY(RANDOM(9007199254740000, 9007199254740991))
+}
+";
+    let outcome = run(source);
+    assert!(outcome.exception.is_none(), "{:?}", outcome.exception);
+    for line in outcome.captured_output.lines() {
+        let n = line.parse::<u64>().unwrap();
+        assert!((9007199254740000..=9007199254740991).contains(&n), "{n}");
+    }
+}
+
+#[test]
+fn large_indices_are_validated_exactly() {
+    assert_eq!(
+        error("list <- [1, 2]\nDISPLAY(list[16777217])"),
+        "index 16777217 is out of array range (length: 2)"
+    );
+    assert_eq!(
+        error("list <- [1, 2]\nlist[16777217] <- 3"),
+        "index is out of bounds: the length is 2 but the index is 16777217"
+    );
+    assert_eq!(
+        error("list <- [1, 2]\nlist[16777216.5] <- 3"),
+        "index 16777216.5 is not an integer"
+    );
+}
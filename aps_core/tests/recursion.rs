@@ -0,0 +1,64 @@
+use std::thread;
+
+use aps_core::{
+    bytecode::Backend,
+    engine::{run_isolated, Engine},
+    vm::{Exception, Frame},
+};
+
+const FOREVER: &str = "
+PROCEDURE forever(n)
+{
+  DISPLAY(forever(n + 1))
+}
+forever(0)
+";
+
+fn assert_recursion_stack(exception: &Exception) {
+    assert_eq!(exception.message, "maximum recursion depth exceeded");
+    assert!(exception.stack.len() > 1);
+
+    let (outer, inner) = exception.stack.split_last().unwrap();
+    let text =
+        |frame: &Frame| &FOREVER[frame.call_span.start as usize..frame.call_span.end as usize];
+    assert_eq!(text(outer), "forever(0)");
+    for frame in inner {
+        assert_eq!(frame.name, "forever");
+        assert_eq!(text(frame), "forever(n + 1)");
+    }
+}
+
+#[test]
+fn unbounded_recursion_hits_the_depth_limit() {
+    for backend in [Backend::Tree, Backend::Bytecode] {
+        let outcome = run_isolated(FOREVER, |engine: Engine| {
+            engine.with_max_depth(50).with_backend(backend)
+        });
+        let exception = outcome.exception.expect("recursion should fail");
+        assert_recursion_stack(&exception);
+        assert_eq!(exception.stack.len(), 50);
+        assert!(exception.help.unwrap().contains(" 50 deep"));
+    }
+}
+
+#[test]
+fn unbounded_recursion_on_a_small_stack_is_an_exception() {
+    for backend in [Backend::Tree, Backend::Bytecode] {
+        // No limit on calls, so only the stack stops the recursion.
+        let exception = thread::Builder::new()
+            .stack_size(4 * 1024 * 1024)
+            .spawn(move || {
+                let outcome = Engine::new()
+                    .capture_output()
+                    .with_backend(backend)
+                    .with_max_depth(usize::MAX)
+                    .run(FOREVER);
+                outcome.exception.expect("recursion should fail")
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_recursion_stack(&exception);
+        assert!(exception.help.unwrap().contains("ran out of stack"));
+    }
+}
@@ -0,0 +1,57 @@
+use aps_core::{bytecode::Backend, engine::Engine, parser::Dialect};
+
+const COUNTDOWN: &str = "
+PROCEDURE count(n, total)
+{
+  IF (n = 0)
+  {
+    RETURN total
+  }
+  RETURN count(n - 1, total + 1)
+}
+DISPLAY(count(1000000, 0))
+";
+
+fn run(source: &str, backend: Backend) -> String {
+    let outcome = Engine::new()
+        .capture_output()
+        .with_backend(backend)
+        .run(source);
+    assert!(outcome.diagnostics.is_empty(), "{:?}", outcome.diagnostics);
+    assert!(outcome.exception.is_none(), "{:?}", outcome.exception);
+    outcome.captured_output
+}
+
+#[test]
+fn a_million_tail_calls_on_the_tree_walker() {
+    assert_eq!(run(COUNTDOWN, Backend::Tree).trim(), "1000000");
+}
+
+#[test]
+fn a_million_tail_calls_on_bytecode() {
+    assert_eq!(run(COUNTDOWN, Backend::Bytecode).trim(), "1000000");
+}
+
+#[test]
+fn tail_called_procedures_see_their_caller_variables() {
+    let source = "
+PROCEDURE outer(x)
+{
+  PROCEDURE inner()
+  {
+    RETURN x + 1
+  }
+  RETURN inner()
+}
+DISPLAY(outer(4))
+";
+    for backend in [Backend::Tree, Backend::Bytecode] {
+        let outcome = Engine::new()
+            .capture_output()
+            .with_dialect(Dialect::Extended)
+            .with_backend(backend)
+            .run(source);
+        assert!(outcome.exception.is_none(), "{:?}", outcome.exception);
+        assert_eq!(outcome.captured_output.trim(), "5");
+    }
+}
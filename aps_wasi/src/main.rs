@@ -0,0 +1,64 @@
+//! A minimal runner for sandboxes such as wasmtime, where the full `aps` CLI
+//! and its dependencies are not available. Build it with
+//! `cargo build -p aps_wasi --target wasm32-wasip1`.
+//!
+//! `aps_wasi FILE` runs `FILE`, answering `INPUT` from stdin. Without `FILE`,
+//! the program itself is read from stdin. Errors are written to stderr without
+//! color, and the exit code is 1 if the program failed to parse or run.
+
+use std::{
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use aps_core::{encoding, engine::Engine};
+use codespan_reporting::{
+    files::SimpleFiles,
+    term::{
+        self,
+        termcolor::{ColorChoice, StandardStream},
+    },
+};
+
+fn main() -> ExitCode {
+    let (name, bytes) = match std::env::args().nth(1) {
+        Some(path) => match std::fs::read(&path) {
+            Ok(bytes) => (path, bytes),
+            Err(e) => {
+                eprintln!("could not read {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => {
+            let mut bytes = Vec::new();
+            if let Err(e) = io::stdin().read_to_end(&mut bytes) {
+                eprintln!("could not read the program from stdin: {e}");
+                return ExitCode::FAILURE;
+            }
+            ("<stdin>".into(), bytes)
+        }
+    };
+
+    let decoded = encoding::decode(&bytes);
+    let mut files = SimpleFiles::new();
+    let fid = files.add(name.as_str(), decoded.text.as_str());
+
+    let errors = match decoded.invalid.is_empty() {
+        true => Engine::new().run(&decoded.text).errors(fid),
+        false => decoded.diagnostics(fid),
+    };
+
+    let writer = StandardStream::stderr(ColorChoice::Never);
+    let config = term::Config::default();
+    for diagnostic in &errors {
+        if let Err(e) = term::emit(&mut writer.lock(), &config, &files, diagnostic) {
+            eprintln!("could not report an error: {e}");
+        }
+    }
+
+    if errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
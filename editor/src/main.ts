@@ -126,6 +126,37 @@ console.log = (...args) => {
   old(...args);
 };
 
+// The drawing functions used by DRAW_RECT, DRAW_CIRCLE, DRAW_LINE, and
+// CLEAR_CANVAS. The canvas only shows once a program draws on it.
+const $canvas = document.querySelector<HTMLCanvasElement>(".terminal .canvas")!;
+const ctx = $canvas.getContext("2d")!;
+
+(globalThis as any).apsCanvas = {
+  drawRect(x: number, y: number, width: number, height: number, color: string) {
+    $canvas.hidden = false;
+    ctx.fillStyle = color;
+    ctx.fillRect(x, y, width, height);
+  },
+  drawCircle(x: number, y: number, radius: number, color: string) {
+    $canvas.hidden = false;
+    ctx.fillStyle = color;
+    ctx.beginPath();
+    ctx.arc(x, y, radius, 0, Math.PI * 2);
+    ctx.fill();
+  },
+  drawLine(x1: number, y1: number, x2: number, y2: number, color: string) {
+    $canvas.hidden = false;
+    ctx.strokeStyle = color;
+    ctx.beginPath();
+    ctx.moveTo(x1, y1);
+    ctx.lineTo(x2, y2);
+    ctx.stroke();
+  },
+  clear() {
+    ctx.clearRect(0, 0, $canvas.width, $canvas.height);
+  },
+};
+
 const oldPrompt = window.prompt;
 
 window.prompt = (msg) => {
@@ -214,32 +245,35 @@ const markers: any[] = [];
 
 function run() {
   term.write("\r$ aps run <file>\r\n");
+  (globalThis as any).apsCanvas.clear();
+  $canvas.hidden = true;
   const result = interpret(editor.getValue());
 
   markers.length = 0;
-  if (result.Data) {
-    term.write(result.Data.log.replaceAll("\n", "\r\n"));
-
-    for (let i = 0; i < result.Data.errors.length; i++) {
-      const model = editor.getModel()!;
-      const start = model.getPositionAt(
-        result.Data.errors[i].labels[0].range.start,
-      );
-      const end = model.getPositionAt(
-        result.Data.errors[i].labels[0].range.end,
-      );
-      markers[i] = {
-        startLineNumber: start.lineNumber,
-        endLineNumber: end.lineNumber,
-        startColumn: start.column,
-        endColumn: end.column,
-        message: result.Data.errors[i].message,
-        severity: monaco.MarkerSeverity.Error,
-      };
-    }
-
-    monaco.editor.setModelMarkers(editor.getModel()!, "owner", markers);
+  term.write(result.log.replaceAll("\n", "\r\n"));
+
+  const errors = result.exception
+    ? [{ message: result.exception.message, range: result.exception.span }]
+    : result.diagnostics.map((diagnostic: any) => ({
+        message: diagnostic.message,
+        range: diagnostic.labels[0].range,
+      }));
+
+  const model = editor.getModel()!;
+  for (let i = 0; i < errors.length; i++) {
+    const start = model.getPositionAt(errors[i].range.start);
+    const end = model.getPositionAt(errors[i].range.end);
+    markers[i] = {
+      startLineNumber: start.lineNumber,
+      endLineNumber: end.lineNumber,
+      startColumn: start.column,
+      endColumn: end.column,
+      message: errors[i].message,
+      severity: monaco.MarkerSeverity.Error,
+    };
   }
+
+  monaco.editor.setModelMarkers(model, "owner", markers);
   term.write("$ ");
 }
 